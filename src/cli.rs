@@ -0,0 +1,475 @@
+//! Command-line subcommands
+//!
+//! Without a subcommand the binary runs as a hook (JSON on stdin). Subcommands
+//! help develop and debug configs outside Claude Code.
+
+use crate::audit;
+use crate::config::Config;
+use crate::policy::PolicyFile;
+use crate::trace::Trace;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "\
+Usage: claude-bash-hook [SUBCOMMAND]
+
+Without a subcommand (or with arguments that aren't one), reads a PreToolUse
+hook request as JSON on stdin.
+
+Subcommands:
+  check [--cwd DIR] [--mode MODE] [--config FILE] [--nu] -- COMMAND
+      Print the decision for COMMAND and how it was reached
+  lint [--cwd DIR] [--config FILE]
+      Report unknown permissions, shadowed rules and other config mistakes
+  log [--config FILE] [--decision DECISION] [--command NAME] [-n COUNT]
+      Show recent audit log entries, newest last
+  suggest-rules [--config FILE] [--min COUNT]
+      Propose rules for commands that were asked or passed through repeatedly
+  test [--config FILE] POLICY
+      Run the cases in POLICY and report decisions that don't match";
+
+/// Run a subcommand if one was given, returning its exit code
+/// Returns None for no arguments or unknown ones, which run the hook as before
+/// subcommands existed
+pub fn run(args: &[String]) -> Option<i32> {
+    let (subcommand, rest) = args.split_first()?;
+    let code = match subcommand.as_str() {
+        "check" => check(rest),
+        "lint" => lint(rest),
+        "log" => log(rest),
+        "suggest-rules" => suggest_rules(rest),
+        "test" => test(rest),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            0
+        }
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Options shared by subcommands that analyze commands
+#[derive(Debug, Default)]
+struct AnalyzeOptions {
+    cwd: Option<String>,
+    mode: Option<String>,
+    config: Option<String>,
+    nushell: bool,
+    positional: Vec<String>,
+}
+
+impl AnalyzeOptions {
+    /// Parse options; everything after `--` (or unrecognized words) is positional
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            let mut value = |name: &str| {
+                iter.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{} requires a value", name))
+            };
+            match arg.as_str() {
+                "--cwd" => options.cwd = Some(value("--cwd")?),
+                "--mode" => options.mode = Some(value("--mode")?),
+                "--config" => options.config = Some(value("--config")?),
+                "--nu" => options.nushell = true,
+                "--" => {
+                    options.positional.extend(iter.by_ref().cloned());
+                }
+                _ if arg.starts_with("--") => return Err(format!("Unknown option '{}'", arg)),
+                _ => options.positional.push(arg.clone()),
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Load the config the hook would use, or the one given with --config
+    fn load_config(&self) -> Result<Config, String> {
+        match self.config {
+            Some(ref path) => {
+                let config = Config::load(Path::new(path))?;
+                config.warn_invalid();
+                Ok(config)
+            }
+            None => Ok(Config::load_for_cwd(self.cwd.as_deref())),
+        }
+    }
+}
+
+/// `check`: print the decision for a command and the trace that led to it
+fn check(args: &[String]) -> i32 {
+    let options = match AnalyzeOptions::parse(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if options.positional.is_empty() {
+        eprintln!("No command given\n\n{}", USAGE);
+        return 2;
+    }
+    let config = match options.load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    let command = options.positional.join(" ");
+    let edit_mode = crate::edits_allowed(options.mode.as_deref());
+    let mut trace = Trace::default();
+    let result = if options.nushell {
+        crate::analyze_nushell_command_traced(
+            &command,
+            &config,
+            edit_mode,
+            options.cwd.as_deref(),
+            &mut trace,
+        )
+    } else {
+        crate::analyze_command_traced(
+            &command,
+            &config,
+            edit_mode,
+            options.cwd.as_deref(),
+            &mut trace,
+        )
+    };
+
+    println!("decision: {}", result.permission.as_str());
+    println!("reason: {}", result.reason);
+    if let Some(ref suggestion) = result.suggestion {
+        println!("suggestion: {}", suggestion);
+    }
+    if !trace.events.is_empty() {
+        println!("\ntrace:");
+        for line in trace.render().lines() {
+            println!("  {}", line);
+        }
+    }
+
+    0
+}
+
+/// `lint`: report problems in the config the hook would use
+fn lint(args: &[String]) -> i32 {
+    let options = match AnalyzeOptions::parse(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if !options.positional.is_empty() {
+        eprintln!(
+            "Unexpected argument '{}'\n\n{}",
+            options.positional[0], USAGE
+        );
+        return 2;
+    }
+    let config = match options.config {
+        Some(ref path) => match Config::load(Path::new(path)) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        },
+        None => Config::merge_for_cwd(options.cwd.as_deref()),
+    };
+
+    let issues = crate::lint::lint(&config);
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    if issues.is_empty() {
+        println!("No issues found");
+        0
+    } else {
+        println!("{} issue(s) found", issues.len());
+        1
+    }
+}
+
+/// Options for the `log` subcommand
+#[derive(Debug)]
+struct LogOptions {
+    config: Option<String>,
+    decision: Option<String>,
+    command: Option<String>,
+    limit: usize,
+}
+
+impl LogOptions {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self {
+            config: None,
+            decision: None,
+            command: None,
+            limit: 20,
+        };
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            let mut value = |name: &str| {
+                iter.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{} requires a value", name))
+            };
+            match arg.as_str() {
+                "--config" => options.config = Some(value("--config")?),
+                "--decision" => options.decision = Some(value("--decision")?),
+                "--command" => options.command = Some(value("--command")?),
+                "-n" => {
+                    let count = value("-n")?;
+                    options.limit = count
+                        .parse()
+                        .map_err(|_| format!("Invalid count '{}'", count))?;
+                }
+                _ => return Err(format!("Unknown option '{}'", arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// `log`: show recent audit entries matching the filters
+fn log(args: &[String]) -> i32 {
+    let options = match LogOptions::parse(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    let config = match options.config {
+        Some(ref path) => match Config::load(Path::new(path)) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        },
+        None => Config::merge_for_cwd(None),
+    };
+    if !config.audit.enabled {
+        eprintln!("Note: audit logging is disabled (set enabled = true under [audit])");
+    }
+
+    let entries = match audit::read_entries(&config.audit) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let matching: Vec<_> = entries
+        .iter()
+        .filter(|e| options.decision.as_ref().is_none_or(|d| e.decision == *d))
+        .filter(|e| {
+            options
+                .command
+                .as_ref()
+                .is_none_or(|name| e.involves_command(name))
+        })
+        .collect();
+
+    for entry in &matching[matching.len().saturating_sub(options.limit)..] {
+        let handler = entry.handler.as_deref().unwrap_or("-");
+        println!(
+            "{} {:<11} {} ({})",
+            entry.timestamp, entry.decision, entry.command, handler
+        );
+    }
+
+    0
+}
+
+/// `suggest-rules`: propose rules from the ask/passthrough audit history
+fn suggest_rules(args: &[String]) -> i32 {
+    let mut config_path = None;
+    let mut min_count = 3;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = iter.next();
+        match (arg.as_str(), value) {
+            ("--config", Some(path)) => config_path = Some(path.clone()),
+            ("--min", Some(count)) => match count.parse() {
+                Ok(count) => min_count = count,
+                Err(_) => {
+                    eprintln!("Invalid count '{}'\n\n{}", count, USAGE);
+                    return 2;
+                }
+            },
+            _ => {
+                eprintln!("Invalid option '{}'\n\n{}", arg, USAGE);
+                return 2;
+            }
+        }
+    }
+
+    let config = match config_path {
+        Some(ref path) => match Config::load(Path::new(path)) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        },
+        None => Config::merge_for_cwd(None),
+    };
+    let entries = match audit::read_entries(&config.audit) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    if entries.is_empty() {
+        eprintln!("No audit entries found (enable logging with [audit] enabled = true)");
+        return 1;
+    }
+
+    let proposals = crate::learn::propose_rules(&config, &entries, min_count);
+    if proposals.is_empty() {
+        println!(
+            "# No command was asked or passed through {} or more times",
+            min_count
+        );
+    }
+    for proposal in proposals {
+        println!("{}", proposal.to_toml());
+    }
+
+    0
+}
+
+/// `test`: run a policy file and report mismatched decisions
+fn test(args: &[String]) -> i32 {
+    let options = match AnalyzeOptions::parse(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    let [policy_path] = options.positional.as_slice() else {
+        eprintln!("Expected exactly one policy file\n\n{}", USAGE);
+        return 2;
+    };
+    let policy_path = Path::new(policy_path);
+    let policy = match PolicyFile::load(policy_path) {
+        Ok(policy) => policy,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    // --config wins over the policy's config; otherwise each case loads the
+    // config the hook would use for its cwd
+    let fixed_config = match options.config.as_ref().map(PathBuf::from) {
+        Some(path) => Some(Config::load(&path)),
+        None => policy.config_path(policy_path).map(|p| Config::load(&p)),
+    };
+    let fixed_config = match fixed_config.transpose() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    let mut passed = 0;
+    let mut failed = 0;
+    for case in &policy.cases {
+        let cwd_config;
+        let config = match fixed_config {
+            Some(ref config) => config,
+            None => {
+                cwd_config = Config::merge_for_cwd(case.cwd.as_deref());
+                &cwd_config
+            }
+        };
+
+        match case.run(config) {
+            Ok(None) => passed += 1,
+            Ok(Some(failure)) => {
+                failed += 1;
+                println!(
+                    "FAIL {}: expected {}, got {} by {}: {}",
+                    failure.command,
+                    failure.expected,
+                    failure.actual.as_str(),
+                    failure.handler,
+                    failure.reason
+                );
+            }
+            Err(e) => {
+                failed += 1;
+                println!("FAIL {}: {}", case.command, e);
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", passed, failed);
+    if failed > 0 { 1 } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_unknown_arguments_run_the_hook() {
+        assert_eq!(run(&[]), None);
+        assert_eq!(run(&args(&["--verbose"])), None);
+        assert_eq!(run(&args(&["hook"])), None);
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = AnalyzeOptions::parse(&args(&[
+            "--cwd",
+            "/tmp/x",
+            "--mode",
+            "acceptEdits",
+            "--",
+            "rm -rf --foo",
+        ]))
+        .unwrap();
+        assert_eq!(options.cwd.as_deref(), Some("/tmp/x"));
+        assert_eq!(options.mode.as_deref(), Some("acceptEdits"));
+        assert_eq!(options.positional, ["rm -rf --foo"]);
+    }
+
+    #[test]
+    fn test_parse_missing_value() {
+        assert!(AnalyzeOptions::parse(&args(&["--cwd"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_log_options() {
+        let options =
+            LogOptions::parse(&args(&["--decision", "ask", "--command", "git", "-n", "5"]))
+                .unwrap();
+        assert_eq!(options.decision.as_deref(), Some("ask"));
+        assert_eq!(options.command.as_deref(), Some("git"));
+        assert_eq!(options.limit, 5);
+        assert!(LogOptions::parse(&args(&["-n", "many"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_unknown_option() {
+        assert!(AnalyzeOptions::parse(&args(&["--bogus", "ls"])).is_err());
+    }
+}
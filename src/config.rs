@@ -1,24 +1,49 @@
 //! Configuration loading and rule matching
 
 use glob_match::glob_match;
+use regex::Regex;
 use serde::Deserialize;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Embedded default configuration
 const DEFAULT_CONFIG: &str = include_str!("../config.default.toml");
 
+/// File name of project-local config overlays
+const PROJECT_CONFIG_FILE: &str = ".claude-bash-hook.toml";
+
+/// Reference to the embedded default config in `extends` and `include`
+const BUILTIN_DEFAULT: &str = "builtin:default";
+
 /// Permission levels (ordered by restrictiveness)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Permission {
     Allow = 0,
     Passthrough = 1,
+    #[default]
     Ask = 2,
     Deny = 3,
 }
 
-impl Default for Permission {
-    fn default() -> Self {
-        Permission::Ask
+impl Permission {
+    /// The lowercase name used in config files and hook output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::Allow => "allow",
+            Permission::Passthrough => "passthrough",
+            Permission::Ask => "ask",
+            Permission::Deny => "deny",
+        }
+    }
+
+    /// Parse a permission name, returning None for unknown values
+    pub fn parse(s: &str) -> Option<Permission> {
+        match s.to_lowercase().as_str() {
+            "allow" => Some(Permission::Allow),
+            "passthrough" => Some(Permission::Passthrough),
+            "ask" => Some(Permission::Ask),
+            "deny" => Some(Permission::Deny),
+            _ => None,
+        }
     }
 }
 
@@ -30,6 +55,24 @@ pub struct PermissionResult {
     pub suggestion: Option<String>,
 }
 
+/// The rule that decided a command (for tracing)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleMatch {
+    /// Index of the rule within its config (project or user)
+    pub index: usize,
+    /// The pattern of the rule that matched (e.g., "git status")
+    pub pattern: String,
+    /// Whether the rule came from a project config overlay
+    pub project: bool,
+}
+
+impl std::fmt::Display for RuleMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source = if self.project { "project rule" } else { "rule" };
+        write!(f, "{} #{} \"{}\"", source, self.index + 1, self.pattern)
+    }
+}
+
 /// Main configuration structure
 #[derive(Debug, Deserialize)]
 pub struct Config {
@@ -37,6 +80,10 @@ pub struct Config {
     #[serde(default = "default_permission")]
     pub default: String,
 
+    /// Permission for commands whose name is an expansion (e.g., `$CMD -rf /`)
+    #[serde(default = "default_permission")]
+    pub dynamic_commands: String,
+
     /// Enable AI-powered advice for permission decisions
     #[serde(default)]
     pub enable_advice: bool,
@@ -52,6 +99,66 @@ pub struct Config {
     /// Command suggestions
     #[serde(default)]
     pub suggestions: Vec<Suggestion>,
+
+    /// Decision audit log
+    #[serde(default)]
+    pub audit: AuditConfig,
+
+    /// Environment variable checks
+    #[serde(default)]
+    pub env: EnvConfig,
+
+    /// Script content inspection
+    #[serde(default)]
+    pub scripts: ScriptsConfig,
+
+    /// Git push and destructive command policy
+    #[serde(default)]
+    pub git: GitConfig,
+
+    /// Number of leading rules that came from a project config overlay
+    #[serde(skip)]
+    project_rule_count: usize,
+}
+
+/// Project-local overlay loaded from `.claude-bash-hook.toml`
+#[derive(Debug, Default, Deserialize)]
+pub struct ProjectConfig {
+    /// Default permission override (can only tighten the user default)
+    #[serde(default)]
+    pub default: Option<String>,
+
+    /// Project rules, checked before user rules
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+
+    /// Wrapper configurations, which are ignored: a wrapper's arguments are
+    /// checked as the inner command, so a project could declare `dd` a wrapper
+    /// and bypass the user's rules for it
+    #[serde(default)]
+    pub wrappers: Vec<WrapperConfig>,
+
+    /// Additional command suggestions
+    #[serde(default)]
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl ProjectConfig {
+    /// Load a project overlay from a file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read project config: {}", e))?;
+
+        toml::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+}
+
+/// Find the nearest project config by walking up from a directory
+pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file())
 }
 
 fn default_permission() -> String {
@@ -78,6 +185,65 @@ pub struct Rule {
     /// Required working directory (glob pattern, e.g., "/home/user/Projects/linux")
     #[serde(default)]
     pub cwd: Option<String>,
+
+    /// Regex the arguments (joined with spaces) must match, e.g. "(^| )-n prod( |$)"
+    /// A rule whose regex doesn't compile matches and asks
+    #[serde(default)]
+    pub args_regex: Option<ConfigRegex>,
+
+    /// Space-separated globs the arguments must match one for one, e.g.
+    /// "-s https://api.internal/*"; a rule with an invalid glob matches and asks
+    #[serde(default)]
+    pub args_glob: Option<String>,
+
+    /// Flags that must not be present for the rule to match, also when given a
+    /// value (`--data=x`, `-XPOST`)
+    #[serde(default)]
+    pub not_flags: Vec<String>,
+}
+
+/// A regex from the config, compiled once when the config is loaded
+/// An invalid regex never matches; rules treat one as matching and ask
+#[derive(Debug, Clone)]
+pub struct ConfigRegex {
+    source: String,
+    regex: Result<Regex, regex::Error>,
+}
+
+impl ConfigRegex {
+    pub fn new(source: &str) -> Self {
+        ConfigRegex {
+            source: source.to_string(),
+            regex: Regex::new(source),
+        }
+    }
+
+    /// The regex as written
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Why the regex doesn't compile, if it doesn't
+    pub fn error(&self) -> Option<&regex::Error> {
+        self.regex.as_ref().err()
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.as_ref().is_ok_and(|re| re.is_match(text))
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfigRegex {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|source| ConfigRegex::new(&source))
+    }
+}
+
+impl Rule {
+    /// Whether the rule restricts arguments beyond its command patterns
+    pub fn has_args_constraints(&self) -> bool {
+        self.args_regex.is_some() || self.args_glob.is_some() || !self.not_flags.is_empty()
+    }
 }
 
 /// Host-based permission rule
@@ -109,18 +275,472 @@ pub struct Suggestion {
     /// Suggestion message
     pub message: String,
 
-    /// Optional regex pattern for more specific matching
+    /// Optional regex matched against the full command line
+    #[serde(default)]
+    pub pattern: Option<ConfigRegex>,
+}
+
+/// Audit log settings (`[audit]`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Write a JSONL entry for every decision
+    pub enabled: bool,
+
+    /// Log file path (supports `~/`)
+    pub path: String,
+
+    /// Rotate when the log would grow past this size
+    pub max_size_mb: u64,
+
+    /// Number of rotated files to keep (audit.jsonl.1, .2, ...)
+    pub keep: usize,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "~/.local/state/claude-bash-hook/audit.jsonl".to_string(),
+            max_size_mb: 10,
+            keep: 3,
+        }
+    }
+}
+
+/// Environment variable settings (`[env]`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EnvConfig {
+    /// Variables (glob patterns) that change what other programs load or run
+    pub risky: Vec<String>,
+
+    /// Permission for commands that set a risky variable
+    pub permission: String,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        let risky = [
+            "LD_*",
+            "DYLD_*",
+            "PATH",
+            "BASH_ENV",
+            "ENV",
+            "PROMPT_COMMAND",
+            "IFS",
+            "GIT_SSH",
+            "GIT_SSH_COMMAND",
+            "GIT_EXEC_PATH",
+            "GIT_CONFIG_*",
+            "GIT_ASKPASS",
+            "GIT_EXTERNAL_DIFF",
+            "GIT_PAGER",
+            "GIT_EDITOR",
+            "GIT_SEQUENCE_EDITOR",
+            "SSH_ASKPASS",
+            "PAGER",
+            "MANPAGER",
+            "LESSOPEN",
+            "LESSCLOSE",
+            "EDITOR",
+            "VISUAL",
+            "PYTHONPATH",
+            "PYTHONSTARTUP",
+            "NODE_OPTIONS",
+            "PERL5OPT",
+            "PERL5LIB",
+            "RUBYOPT",
+            "RUSTC_WRAPPER",
+        ];
+        Self {
+            risky: risky.iter().map(|v| v.to_string()).collect(),
+            permission: "ask".to_string(),
+        }
+    }
+}
+
+impl EnvConfig {
+    /// Whether setting a variable is risky
+    pub fn is_risky(&self, name: &str) -> bool {
+        self.risky.iter().any(|pattern| glob_match(pattern, name))
+    }
+}
+
+/// Script inspection settings (`[scripts]`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScriptsConfig {
+    /// Read executed scripts and analyze their contents
+    pub inspect: bool,
+
+    /// Scripts larger than this are not inspected (asks instead)
+    pub max_size_kb: u64,
+
+    /// How deep scripts running other scripts are followed
+    pub max_depth: usize,
+}
+
+impl Default for ScriptsConfig {
+    fn default() -> Self {
+        Self {
+            inspect: false,
+            max_size_kb: 64,
+            max_depth: 3,
+        }
+    }
+}
+
+/// Git settings (`[git]`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GitConfig {
+    /// Branches (glob patterns, e.g. "release/*") whose updates use the `protected` permission
+    pub protected_branches: Vec<String>,
+
+    /// Permission for each kind of push
+    pub permissions: GitPermissions,
+
+    /// Pushes to a remote whose URL matches get the rule's permission; first match wins
+    pub remotes: Vec<GitRemoteRule>,
+
+    /// Settings for commands run in matching directories; first match wins
+    pub overrides: Vec<GitOverride>,
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        Self {
+            protected_branches: vec!["main".to_string(), "master".to_string()],
+            permissions: GitPermissions::default(),
+            remotes: Vec::new(),
+            overrides: Vec::new(),
+        }
+    }
+}
+
+/// Permission for each kind of push and destructive command (`[git.permissions]`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GitPermissions {
+    /// Fast-forward of an unprotected branch or a new tag
+    pub push: String,
+    /// Any update of a protected branch (the stricter of this and the push's kind)
+    pub protected: String,
+    /// Force push without a lease (`-f`, `+branch`)
+    pub force: String,
+    /// Force push with `--force-with-lease`
+    pub force_with_lease: String,
+    /// Deleting a remote ref (`--delete`, `:branch`)
+    pub delete: String,
+    /// `--mirror` pushes and mirror remotes
+    pub mirror: String,
+    /// `--prune`
+    pub prune: String,
+    /// Local commands whose losses stay recoverable for a while (`branch -D`,
+    /// `stash drop`, `reset --hard` leaving commits behind)
+    pub recoverable: String,
+    /// Local commands that lose work for good (uncommitted changes,
+    /// untracked files, reflog entries, pruned objects)
+    pub unrecoverable: String,
+}
+
+impl Default for GitPermissions {
+    fn default() -> Self {
+        let ask = || "ask".to_string();
+        Self {
+            push: "allow".to_string(),
+            protected: ask(),
+            force: ask(),
+            force_with_lease: "allow".to_string(),
+            delete: ask(),
+            mirror: ask(),
+            prune: ask(),
+            recoverable: ask(),
+            unrecoverable: ask(),
+        }
+    }
+}
+
+impl GitPermissions {
+    /// Each permission with its name, for linting
+    pub fn named(&self) -> [(&'static str, &str); 9] {
+        [
+            ("push", &self.push),
+            ("protected", &self.protected),
+            ("force", &self.force),
+            ("force_with_lease", &self.force_with_lease),
+            ("delete", &self.delete),
+            ("mirror", &self.mirror),
+            ("prune", &self.prune),
+            ("recoverable", &self.recoverable),
+            ("unrecoverable", &self.unrecoverable),
+        ]
+    }
+}
+
+/// Permission for pushes to matching remotes (`[[git.remotes]]`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitRemoteRule {
+    /// Glob matched against the remote URL as `host:path` without `.git`
+    /// (e.g., "github.com:myorg/*" matches git@github.com:myorg/app.git and
+    /// https://github.com/myorg/app)
+    pub url: String,
+
+    /// Permission for every push to the remote
+    pub permission: String,
+}
+
+/// Git settings for a directory (`[[git.overrides]]`), replacing the top-level ones it sets
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitOverride {
+    /// Directory glob the command runs in (e.g., "~/work/**")
+    pub cwd: String,
+
+    #[serde(default)]
+    pub protected_branches: Option<Vec<String>>,
+
+    #[serde(default)]
+    pub permissions: Option<GitPermissions>,
+
+    /// Checked before the top-level remote rules
+    #[serde(default)]
+    pub remotes: Vec<GitRemoteRule>,
+}
+
+/// The git settings in effect for a directory
+#[derive(Debug)]
+pub struct GitPolicy<'a> {
+    pub protected_branches: &'a [String],
+    pub permissions: &'a GitPermissions,
+    pub remotes: Vec<&'a GitRemoteRule>,
+}
+
+/// A single config file as written, before layering
+///
+/// Layers are merged from lowest to highest precedence: the `extends` base,
+/// then each `include` in order, then the file itself. Rules, wrappers and
+/// suggestions of higher layers are checked first; scalar settings are taken
+/// from the highest layer that sets them.
+#[derive(Debug, Deserialize)]
+struct ConfigLayer {
+    /// Base config: "builtin:default" or a path
+    #[serde(default)]
+    extends: Option<String>,
+
+    /// Additional config files (paths or globs, relative to this file)
+    #[serde(default)]
+    include: Vec<String>,
+
+    #[serde(default)]
+    default: Option<String>,
+
+    #[serde(default)]
+    dynamic_commands: Option<String>,
+
+    #[serde(default)]
+    enable_advice: Option<bool>,
+
+    #[serde(default)]
+    audit: Option<AuditConfig>,
+
+    #[serde(default)]
+    env: Option<EnvConfig>,
+
+    #[serde(default)]
+    scripts: Option<ScriptsConfig>,
+
     #[serde(default)]
-    pub pattern: Option<String>,
+    git: Option<GitConfig>,
+
+    #[serde(default)]
+    rules: Vec<Rule>,
+
+    #[serde(default)]
+    wrappers: Vec<WrapperConfig>,
+
+    #[serde(default)]
+    suggestions: Vec<Suggestion>,
+}
+
+/// Resolves `extends`/`include` references into an ordered list of layers
+#[derive(Default)]
+struct LayerLoader {
+    /// Layers from lowest to highest precedence
+    layers: Vec<ConfigLayer>,
+    /// Sources currently being loaded (for cycle detection)
+    stack: Vec<String>,
+    /// Sources already loaded (included twice is not an error, just skipped)
+    loaded: Vec<String>,
+}
+
+impl LayerLoader {
+    /// Load a config source and everything it references
+    fn load_source(
+        &mut self,
+        key: String,
+        content: &str,
+        dir: Option<&Path>,
+    ) -> Result<(), String> {
+        if self.stack.contains(&key) {
+            let mut cycle = self.stack.clone();
+            cycle.push(key);
+            return Err(format!("Config include cycle: {}", cycle.join(" -> ")));
+        }
+        if self.loaded.contains(&key) {
+            return Ok(());
+        }
+
+        let layer: ConfigLayer =
+            toml::from_str(content).map_err(|e| format!("Failed to parse {}: {}", key, e))?;
+
+        self.stack.push(key.clone());
+        if let Some(ref base) = layer.extends {
+            self.load_reference(base, dir)?;
+        }
+        for include in &layer.include {
+            self.load_reference(include, dir)?;
+        }
+        self.stack.pop();
+
+        self.loaded.push(key);
+        self.layers.push(layer);
+        Ok(())
+    }
+
+    /// Load a file path
+    fn load_path(&mut self, path: &Path) -> Result<(), String> {
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+        let content = std::fs::read_to_string(&canonical)
+            .map_err(|e| format!("Failed to read config {}: {}", canonical.display(), e))?;
+        self.load_source(
+            canonical.to_string_lossy().to_string(),
+            &content,
+            canonical.parent(),
+        )
+    }
+
+    /// Load an `extends`/`include` reference
+    fn load_reference(&mut self, reference: &str, dir: Option<&Path>) -> Result<(), String> {
+        if reference == BUILTIN_DEFAULT {
+            return self.load_source(BUILTIN_DEFAULT.to_string(), DEFAULT_CONFIG, None);
+        }
+        for path in expand_include(reference, dir)? {
+            self.load_path(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Merge the loaded layers into a single config
+    fn into_config(self) -> Config {
+        let mut config = Config {
+            default: default_permission(),
+            dynamic_commands: default_permission(),
+            enable_advice: false,
+            rules: Vec::new(),
+            wrappers: Vec::new(),
+            suggestions: Vec::new(),
+            audit: AuditConfig::default(),
+            env: EnvConfig::default(),
+            scripts: ScriptsConfig::default(),
+            git: GitConfig::default(),
+            project_rule_count: 0,
+        };
+        let mut default = None;
+        let mut dynamic_commands = None;
+        let mut enable_advice = None;
+        let mut audit = None;
+        let mut env = None;
+        let mut scripts = None;
+        let mut git = None;
+
+        for layer in self.layers.into_iter().rev() {
+            default = default.or(layer.default);
+            dynamic_commands = dynamic_commands.or(layer.dynamic_commands);
+            enable_advice = enable_advice.or(layer.enable_advice);
+            audit = audit.or(layer.audit);
+            env = env.or(layer.env);
+            scripts = scripts.or(layer.scripts);
+            git = git.or(layer.git);
+            config.rules.extend(layer.rules);
+            config.wrappers.extend(layer.wrappers);
+            config.suggestions.extend(layer.suggestions);
+        }
+
+        if let Some(default) = default {
+            config.default = default;
+        }
+        if let Some(dynamic_commands) = dynamic_commands {
+            config.dynamic_commands = dynamic_commands;
+        }
+        config.enable_advice = enable_advice.unwrap_or(false);
+        config.audit = audit.unwrap_or_default();
+        config.env = env.unwrap_or_default();
+        config.scripts = scripts.unwrap_or_default();
+        config.git = git.unwrap_or_default();
+        config
+    }
+}
+
+/// A rule's `args_regex` or `args_glob` that can't be used, as `args_regex '...'`
+fn invalid_args_matcher(rule: &Rule) -> Option<String> {
+    if let Some(ref pattern) = rule.args_regex
+        && pattern.error().is_some()
+    {
+        return Some(format!("args_regex '{}'", pattern.as_str()));
+    }
+    rule.args_glob
+        .as_ref()
+        .filter(|glob| crate::lint::glob_syntax_problem(glob).is_some())
+        .map(|glob| format!("args_glob '{}'", glob))
+}
+
+/// Expand an include reference into file paths
+/// Supports `~/` and a glob in the file name (e.g., "conf.d/*.toml"); glob matches are sorted
+fn expand_include(reference: &str, dir: Option<&Path>) -> Result<Vec<PathBuf>, String> {
+    let expanded = match reference.strip_prefix("~/") {
+        Some(rest) => Path::new(&std::env::var("HOME").unwrap_or_default()).join(rest),
+        None => PathBuf::from(reference),
+    };
+    let path = match dir {
+        Some(dir) if expanded.is_relative() => dir.join(expanded),
+        _ => expanded,
+    };
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if !file_name.contains(['*', '?', '[', '{']) {
+        return Ok(vec![path]);
+    }
+
+    let parent = path.parent().unwrap_or(Path::new("."));
+    let Ok(entries) = std::fs::read_dir(parent) else {
+        // A missing conf.d directory just means nothing to include
+        return Ok(vec![]);
+    };
+    let mut matches: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            p.file_name()
+                .is_some_and(|n| glob_match(&file_name, &n.to_string_lossy()))
+        })
+        .collect();
+    matches.sort();
+    Ok(matches)
 }
 
 impl Config {
-    /// Load configuration from a file
+    /// Load configuration from a file, resolving `extends` and `include`
     pub fn load(path: &Path) -> Result<Self, String> {
-        let content =
-            std::fs::read_to_string(path).map_err(|e| format!("Failed to read config: {}", e))?;
-
-        toml::from_str(&content).map_err(|e| format!("Failed to parse config: {}", e))
+        let mut loader = LayerLoader::default();
+        loader.load_path(path)?;
+        Ok(loader.into_config())
     }
 
     /// Load from default location or return default config
@@ -138,76 +758,200 @@ impl Config {
         Self::default()
     }
 
+    /// Load the user and project config for `cwd`, warning about problems
+    /// that change decisions (see [`crate::lint::validate`])
+    pub fn load_for_cwd(cwd: Option<&str>) -> Self {
+        let config = Self::merge_for_cwd(cwd);
+        config.warn_invalid();
+        config
+    }
+
+    /// Print problems that change decisions as warnings
+    pub fn warn_invalid(&self) {
+        for issue in crate::lint::validate(self) {
+            eprintln!("Warning: config {}", issue);
+        }
+    }
+
+    /// Load the user config and overlay the nearest project config above `cwd`
+    pub fn merge_for_cwd(cwd: Option<&str>) -> Self {
+        let mut config = Self::load_or_default();
+
+        let start = cwd
+            .map(PathBuf::from)
+            .or_else(|| std::env::current_dir().ok());
+        if let Some(path) = start.as_deref().and_then(find_project_config) {
+            match ProjectConfig::load(&path) {
+                Ok(project) => {
+                    if !project.wrappers.is_empty() {
+                        eprintln!(
+                            "Warning: {}: [[wrappers]] are ignored in project configs",
+                            path.display()
+                        );
+                    }
+                    config.merge_project(project)
+                }
+                Err(e) => eprintln!("Warning: {}", e),
+            }
+        }
+
+        config
+    }
+
+    /// Merge a project overlay on top of this config
+    /// Project rules are checked first, suggestions are appended and wrappers
+    /// are ignored
+    pub fn merge_project(&mut self, project: ProjectConfig) {
+        if let Some(default) = project.default
+            && self.parse_permission(&default) > self.parse_permission(&self.default)
+        {
+            self.default = default;
+        }
+
+        self.project_rule_count += project.rules.len();
+        let mut rules = project.rules;
+        rules.append(&mut self.rules);
+        self.rules = rules;
+
+        self.suggestions.extend(project.suggestions);
+    }
+
+    /// Number of leading rules that came from a project config overlay
+    pub fn project_rule_count(&self) -> usize {
+        self.project_rule_count
+    }
+
+    /// The git settings for a command running in `cwd` (overrides only apply
+    /// when the directory is known)
+    pub fn git_policy(&self, cwd: Option<&str>) -> GitPolicy<'_> {
+        let git = &self.git;
+        let over = cwd.and_then(|cwd| {
+            git.overrides
+                .iter()
+                .find(|o| self.matches_cwd(&o.cwd, Some(cwd)))
+        });
+        GitPolicy {
+            protected_branches: over
+                .and_then(|o| o.protected_branches.as_deref())
+                .unwrap_or(&git.protected_branches),
+            permissions: over
+                .and_then(|o| o.permissions.as_ref())
+                .unwrap_or(&git.permissions),
+            remotes: over
+                .into_iter()
+                .flat_map(|o| &o.remotes)
+                .chain(&git.remotes)
+                .collect(),
+        }
+    }
+
     /// Get wrapper config by command name
     pub fn get_wrapper(&self, name: &str) -> Option<&WrapperConfig> {
         self.wrappers.iter().find(|w| w.command == name)
     }
 
     /// Check a command against rules
+    #[cfg(test)]
     pub fn check_command(&self, name: &str, args: &[String]) -> PermissionResult {
         self.check_command_with_cwd(name, args, None)
     }
 
     /// Check a command against rules with an optional cwd override
+    #[cfg(test)]
     pub fn check_command_with_cwd(
         &self,
         name: &str,
         args: &[String],
         cwd: Option<&str>,
     ) -> PermissionResult {
-        // First check for suggestions
-        let suggestion = self.find_suggestion(name, args);
-
-        // Then match against rules
-        for rule in &self.rules {
-            if let Some(result) =
-                self.match_rule_with_cwd(rule, name, args, cwd, suggestion.clone())
-            {
-                return result;
-            }
-        }
+        self.explain_command_with_cwd(name, args, cwd).0
+    }
 
-        // Return default
-        PermissionResult {
-            permission: self.parse_permission(&self.default),
-            reason: "No matching rule".to_string(),
-            suggestion,
-        }
+    /// Check a command against rules, also returning the rule that decided it
+    pub fn explain_command_with_cwd(
+        &self,
+        name: &str,
+        args: &[String],
+        cwd: Option<&str>,
+    ) -> (PermissionResult, Option<RuleMatch>) {
+        self.first_match(name, args, |rule, suggestion| {
+            self.match_rule_with_cwd(rule, name, args, cwd, suggestion)
+        })
     }
 
-    /// Check a command with host information
-    pub fn check_command_with_host(
+    /// Check a command with host information, also returning the rule that decided it
+    pub fn explain_command_with_host(
         &self,
         name: &str,
         args: &[String],
         host: Option<&str>,
-    ) -> PermissionResult {
+    ) -> (PermissionResult, Option<RuleMatch>) {
+        self.first_match(name, args, |rule, suggestion| {
+            self.match_rule_with_host(rule, name, args, host, suggestion)
+        })
+    }
+
+    /// Find the first rule matching a command (first match wins)
+    fn first_match(
+        &self,
+        name: &str,
+        args: &[String],
+        match_rule: impl Fn(&Rule, Option<String>) -> Option<(PermissionResult, String)>,
+    ) -> (PermissionResult, Option<RuleMatch>) {
+        // First check for suggestions
         let suggestion = self.find_suggestion(name, args);
 
-        for rule in &self.rules {
-            if let Some(result) =
-                self.match_rule_with_host(rule, name, args, host, suggestion.clone())
-            {
-                return result;
+        // Then match against rules
+        for (index, rule) in self.rules.iter().enumerate() {
+            if let Some((result, pattern)) = match_rule(rule, suggestion.clone()) {
+                // Project rules can tighten but never loosen a user-level deny
+                if index < self.project_rule_count && result.permission < Permission::Deny {
+                    let user_match = self.rules[self.project_rule_count..]
+                        .iter()
+                        .enumerate()
+                        .find_map(|(i, r)| match_rule(r, None).map(|m| (i, m)));
+                    if let Some((i, (denied, pattern))) = user_match
+                        && denied.permission == Permission::Deny
+                    {
+                        return (
+                            PermissionResult {
+                                suggestion,
+                                ..denied
+                            },
+                            Some(self.rule_match(self.project_rule_count + i, pattern)),
+                        );
+                    }
+                }
+                return (result, Some(self.rule_match(index, pattern)));
             }
         }
 
-        PermissionResult {
-            permission: self.parse_permission(&self.default),
-            reason: "No matching rule".to_string(),
-            suggestion,
-        }
+        // Return default
+        (
+            PermissionResult {
+                permission: self.parse_permission(&self.default),
+                reason: "No matching rule".to_string(),
+                suggestion,
+            },
+            None,
+        )
     }
 
-    /// Match a single rule
-    fn match_rule(
-        &self,
-        rule: &Rule,
-        name: &str,
-        args: &[String],
-        suggestion: Option<String>,
-    ) -> Option<PermissionResult> {
-        self.match_rule_with_cwd(rule, name, args, None, suggestion)
+    /// Describe the rule at an index of the merged rule list
+    pub fn rule_match(&self, index: usize, pattern: String) -> RuleMatch {
+        if index < self.project_rule_count {
+            RuleMatch {
+                index,
+                pattern,
+                project: true,
+            }
+        } else {
+            RuleMatch {
+                index: index - self.project_rule_count,
+                pattern,
+                project: false,
+            }
+        }
     }
 
     /// Match a single rule with optional cwd override
@@ -218,20 +962,19 @@ impl Config {
         args: &[String],
         cwd: Option<&str>,
         suggestion: Option<String>,
-    ) -> Option<PermissionResult> {
+    ) -> Option<(PermissionResult, String)> {
         for pattern in &rule.commands {
             if self.matches_pattern_with_cwd(pattern, name, args, cwd) {
                 // Check cwd constraint if present
-                if let Some(ref cwd_pattern) = rule.cwd {
-                    if !self.matches_cwd(cwd_pattern, cwd) {
-                        continue;
-                    }
+                if let Some(ref cwd_pattern) = rule.cwd
+                    && !self.matches_cwd(cwd_pattern, cwd)
+                {
+                    continue;
+                }
+                if !self.matches_args(rule, args) {
+                    continue;
                 }
-                return Some(PermissionResult {
-                    permission: self.parse_permission(&rule.permission),
-                    reason: rule.reason.clone(),
-                    suggestion,
-                });
+                return Some((self.rule_result(rule, suggestion), pattern.clone()));
             }
         }
         None
@@ -274,6 +1017,52 @@ impl Config {
         glob_match(&expanded, &cwd_str)
     }
 
+    /// Check a rule's argument constraints (args_regex, args_glob, not_flags)
+    /// An invalid matcher matches, so that the rule asks (see [`Config::rule_result`])
+    fn matches_args(&self, rule: &Rule, args: &[String]) -> bool {
+        if invalid_args_matcher(rule).is_some() {
+            return true;
+        }
+        if rule.not_flags.iter().any(|flag| self.has_flag(args, flag)) {
+            return false;
+        }
+
+        // One glob per argument, so `*` can't take in the arguments after it
+        if let Some(ref pattern) = rule.args_glob {
+            let globs: Vec<&str> = pattern.split_whitespace().collect();
+            if globs.len() != args.len()
+                || !globs
+                    .iter()
+                    .zip(args)
+                    .all(|(glob, arg)| glob_match(glob, arg))
+            {
+                return false;
+            }
+        }
+        if let Some(ref pattern) = rule.args_regex {
+            return pattern.is_match(&args.join(" "));
+        }
+
+        true
+    }
+
+    /// The decision of a rule that matched: its permission, or ask when an
+    /// argument matcher is invalid and so can't tell which commands it meant
+    fn rule_result(&self, rule: &Rule, suggestion: Option<String>) -> PermissionResult {
+        if let Some(matcher) = invalid_args_matcher(rule) {
+            return PermissionResult {
+                permission: Permission::Ask,
+                reason: format!("{} (invalid {})", rule.reason, matcher),
+                suggestion,
+            };
+        }
+        PermissionResult {
+            permission: self.parse_permission(&rule.permission),
+            reason: rule.reason.clone(),
+            suggestion,
+        }
+    }
+
     /// Match a rule with host checking
     fn match_rule_with_host(
         &self,
@@ -282,14 +1071,17 @@ impl Config {
         args: &[String],
         host: Option<&str>,
         suggestion: Option<String>,
-    ) -> Option<PermissionResult> {
+    ) -> Option<(PermissionResult, String)> {
         for pattern in &rule.commands {
             if self.matches_pattern(pattern, name, args) {
                 // Check cwd constraint if present
-                if let Some(ref cwd_pattern) = rule.cwd {
-                    if !self.matches_cwd(cwd_pattern, None) {
-                        continue;
-                    }
+                if let Some(ref cwd_pattern) = rule.cwd
+                    && !self.matches_cwd(cwd_pattern, None)
+                {
+                    continue;
+                }
+                if !self.matches_args(rule, args) {
+                    continue;
                 }
                 // Check if this is a host-checking rule
                 if rule.permission == "check_host" {
@@ -297,27 +1089,29 @@ impl Config {
                         // Match against host rules
                         for host_rule in &rule.host_rules {
                             if glob_match(&host_rule.pattern, h) {
-                                return Some(PermissionResult {
-                                    permission: self.parse_permission(&host_rule.permission),
-                                    reason: format!("{} (host: {})", rule.reason, h),
-                                    suggestion,
-                                });
+                                return Some((
+                                    PermissionResult {
+                                        permission: self.parse_permission(&host_rule.permission),
+                                        reason: format!("{} (host: {})", rule.reason, h),
+                                        suggestion,
+                                    },
+                                    pattern.clone(),
+                                ));
                             }
                         }
                     }
                     // No host or no matching host rule - use ask as default
-                    return Some(PermissionResult {
-                        permission: Permission::Ask,
-                        reason: format!("{} (unknown host)", rule.reason),
-                        suggestion,
-                    });
+                    return Some((
+                        PermissionResult {
+                            permission: Permission::Ask,
+                            reason: format!("{} (unknown host)", rule.reason),
+                            suggestion,
+                        },
+                        pattern.clone(),
+                    ));
                 }
 
-                return Some(PermissionResult {
-                    permission: self.parse_permission(&rule.permission),
-                    reason: rule.reason.clone(),
-                    suggestion,
-                });
+                return Some((self.rule_result(rule, suggestion), pattern.clone()));
             }
         }
         None
@@ -337,18 +1131,19 @@ impl Config {
         }
 
         // If command is absolute and pattern is relative, try matching relative to cwd
-        if let Some(cwd) = cwd {
-            if name.starts_with('/') && !pattern.starts_with('/') {
-                let cwd_with_slash = if cwd.ends_with('/') {
-                    cwd.to_string()
-                } else {
-                    format!("{}/", cwd)
-                };
-                if let Some(relative) = name.strip_prefix(&cwd_with_slash) {
-                    if self.matches_pattern(pattern, relative, args) {
-                        return true;
-                    }
-                }
+        if let Some(cwd) = cwd
+            && name.starts_with('/')
+            && !pattern.starts_with('/')
+        {
+            let cwd_with_slash = if cwd.ends_with('/') {
+                cwd.to_string()
+            } else {
+                format!("{}/", cwd)
+            };
+            if let Some(relative) = name.strip_prefix(&cwd_with_slash)
+                && self.matches_pattern(pattern, relative, args)
+            {
+                return true;
             }
         }
 
@@ -360,7 +1155,7 @@ impl Config {
     /// - "ls" - just the command name
     /// - "git status" - command with subcommand
     /// - "rm -rf" - command with specific flag
-    fn matches_pattern(&self, pattern: &str, name: &str, args: &[String]) -> bool {
+    pub fn matches_pattern(&self, pattern: &str, name: &str, args: &[String]) -> bool {
         let parts: Vec<&str> = pattern.split_whitespace().collect();
 
         if parts.is_empty() {
@@ -411,7 +1206,7 @@ impl Config {
     }
 
     /// Find all subcommands (positional args), skipping flags and their arguments
-    fn find_subcommands(&self, cmd_name: &str, args: &[String]) -> Vec<String> {
+    pub fn find_subcommands(&self, cmd_name: &str, args: &[String]) -> Vec<String> {
         // Flags that take an argument for common commands
         let flags_with_args: &[&str] = match cmd_name {
             "git" => &["-C", "-c", "--git-dir", "--work-tree", "--namespace"],
@@ -465,13 +1260,17 @@ impl Config {
     }
 
     /// Check if a flag is present in args
-    /// Handles combined flags like -rf matching -r and -f
+    /// Handles combined flags like -rf matching -r and -f, and flags given a
+    /// value (`--data=x`, `-XPOST`)
     fn has_flag(&self, args: &[String], flag: &str) -> bool {
         let flag_char = flag.trim_start_matches('-');
 
-        // Handle long flags (--force)
+        // Handle long flags (--force, --data=x)
         if flag.starts_with("--") {
-            return args.iter().any(|a| a == flag);
+            return args.iter().any(|a| {
+                a.strip_prefix(flag)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('='))
+            });
         }
 
         // Handle short flags (-f)
@@ -505,12 +1304,11 @@ impl Config {
                 continue;
             }
 
-            // If there's a pattern, check it
-            if let Some(ref pattern) = sugg.pattern {
-                // Simple glob matching on the pattern
-                if !glob_match(pattern, &full_cmd) {
-                    continue;
-                }
+            // If there's a pattern, it must match somewhere in the command line
+            if let Some(ref pattern) = sugg.pattern
+                && !pattern.is_match(&full_cmd)
+            {
+                continue;
             }
 
             return Some(sugg.message.clone());
@@ -521,12 +1319,7 @@ impl Config {
 
     /// Parse permission string to enum
     fn parse_permission(&self, s: &str) -> Permission {
-        match s.to_lowercase().as_str() {
-            "allow" => Permission::Allow,
-            "ask" => Permission::Ask,
-            "deny" => Permission::Deny,
-            _ => Permission::Passthrough,
-        }
+        Permission::parse(s).unwrap_or(Permission::Passthrough)
     }
 }
 
@@ -540,6 +1333,7 @@ impl Default for Config {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::temp_dir;
     use std::path::Path;
 
     fn test_config() -> Config {
@@ -709,4 +1503,446 @@ mod tests {
         );
         assert_eq!(result.permission, Permission::Ask);
     }
+
+    fn overlay(user: &str, project: &str) -> Config {
+        let mut config: Config = toml::from_str(user).unwrap();
+        let project: ProjectConfig = toml::from_str(project).unwrap();
+        config.merge_project(project);
+        config
+    }
+
+    #[test]
+    fn test_project_rules_checked_first() {
+        let config = overlay(
+            r#"
+            default = "passthrough"
+            [[rules]]
+            commands = ["make"]
+            permission = "ask"
+            "#,
+            r#"
+            [[rules]]
+            commands = ["make test", "./scripts/check.sh"]
+            permission = "allow"
+            reason = "project targets"
+            "#,
+        );
+        let result = config.check_command("make", &["test".into()]);
+        assert_eq!(result.permission, Permission::Allow);
+        let result = config.check_command("make", &["deploy".into()]);
+        assert_eq!(result.permission, Permission::Ask);
+        let result = config.check_command("./scripts/check.sh", &[]);
+        assert_eq!(result.permission, Permission::Allow);
+    }
+
+    #[test]
+    fn test_project_cannot_loosen_user_deny() {
+        let config = overlay(
+            r#"
+            [[rules]]
+            commands = ["dd"]
+            permission = "deny"
+            reason = "disk operations"
+            "#,
+            r#"
+            [[rules]]
+            commands = ["dd"]
+            permission = "allow"
+            "#,
+        );
+        let result = config.check_command("dd", &["if=/dev/zero".into()]);
+        assert_eq!(result.permission, Permission::Deny);
+        assert_eq!(result.reason, "disk operations");
+    }
+
+    #[test]
+    fn test_project_can_tighten() {
+        let config = overlay(
+            r#"
+            default = "passthrough"
+            [[rules]]
+            commands = ["git push"]
+            permission = "allow"
+            "#,
+            r#"
+            default = "ask"
+            [[rules]]
+            commands = ["git push"]
+            permission = "deny"
+            reason = "use the release script"
+            "#,
+        );
+        let result = config.check_command("git", &["push".into()]);
+        assert_eq!(result.permission, Permission::Deny);
+        let result = config.check_command("unknown", &[]);
+        assert_eq!(result.permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_project_default_cannot_loosen() {
+        let config = overlay(r#"default = "ask""#, r#"default = "allow""#);
+        let result = config.check_command("unknown", &[]);
+        assert_eq!(result.permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_project_wrappers_ignored_and_suggestions_appended() {
+        let config = overlay(
+            r#"
+            [[wrappers]]
+            command = "nice"
+            opts_with_args = ["-n"]
+            "#,
+            r#"
+            [[wrappers]]
+            command = "nice"
+            opts_with_args = []
+            [[wrappers]]
+            command = "with-env"
+            [[suggestions]]
+            command = "npm"
+            message = "Use pnpm in this repo"
+            "#,
+        );
+        assert_eq!(config.get_wrapper("nice").unwrap().opts_with_args, ["-n"]);
+        assert!(config.get_wrapper("with-env").is_none());
+        let result = config.check_command("npm", &["install".into()]);
+        assert_eq!(result.suggestion.as_deref(), Some("Use pnpm in this repo"));
+    }
+
+    #[test]
+    fn test_find_project_config_walks_up() {
+        let root = temp_dir("project");
+        let nested = root.join("a/b");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(root.join(PROJECT_CONFIG_FILE), "").unwrap();
+
+        assert_eq!(
+            find_project_config(&nested),
+            Some(root.join(PROJECT_CONFIG_FILE))
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    fn write_config(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_extends_builtin_default() {
+        let dir = temp_dir("extends");
+        let path = write_config(
+            &dir,
+            "config.toml",
+            r#"
+            extends = "builtin:default"
+            default = "ask"
+            [[rules]]
+            commands = ["ls"]
+            permission = "deny"
+            "#,
+        );
+        let config = Config::load(&path).unwrap();
+
+        // Own rules override the base
+        let result = config.check_command("ls", &[]);
+        assert_eq!(result.permission, Permission::Deny);
+        // Base rules still apply
+        let result = config.check_command("git", &["status".into()]);
+        assert_eq!(result.permission, Permission::Allow);
+        // Own scalar settings win
+        let result = config.check_command("unknown_cmd", &[]);
+        assert_eq!(result.permission, Permission::Ask);
+        assert!(config.get_wrapper("sudo").is_some());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_order() {
+        let dir = temp_dir("include");
+        write_config(
+            &dir,
+            "conf.d/10-team.toml",
+            r#"
+            default = "deny"
+            [[rules]]
+            commands = ["terraform apply"]
+            permission = "ask"
+            [[rules]]
+            commands = ["make"]
+            permission = "allow"
+            "#,
+        );
+        write_config(
+            &dir,
+            "conf.d/20-local.toml",
+            r#"
+            [[rules]]
+            commands = ["terraform"]
+            permission = "deny"
+            "#,
+        );
+        write_config(&dir, "conf.d/notes.txt", "not toml");
+        let path = write_config(
+            &dir,
+            "config.toml",
+            r#"
+            extends = "builtin:default"
+            include = ["conf.d/*.toml"]
+            [[rules]]
+            commands = ["make deploy"]
+            permission = "ask"
+            "#,
+        );
+        let config = Config::load(&path).unwrap();
+
+        // Later includes take precedence over earlier ones
+        let result = config.check_command("terraform", &["apply".into()]);
+        assert_eq!(result.permission, Permission::Deny);
+        // The file itself takes precedence over includes
+        let result = config.check_command("make", &["deploy".into()]);
+        assert_eq!(result.permission, Permission::Ask);
+        let result = config.check_command("make", &["build".into()]);
+        assert_eq!(result.permission, Permission::Allow);
+        // Includes override the extended base's scalar settings
+        let result = config.check_command("unknown_cmd", &[]);
+        assert_eq!(result.permission, Permission::Deny);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_cycle_detected() {
+        let dir = temp_dir("cycle");
+        write_config(&dir, "a.toml", r#"include = ["b.toml"]"#);
+        write_config(&dir, "b.toml", r#"include = ["a.toml"]"#);
+        let err = Config::load(&dir.join("a.toml")).unwrap_err();
+        assert!(err.contains("cycle"), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_twice_not_a_cycle() {
+        let dir = temp_dir("diamond");
+        write_config(
+            &dir,
+            "shared.toml",
+            r#"
+            [[rules]]
+            commands = ["make"]
+            permission = "allow"
+            "#,
+        );
+        write_config(&dir, "a.toml", r#"include = ["shared.toml"]"#);
+        let path = write_config(
+            &dir,
+            "config.toml",
+            r#"include = ["a.toml", "shared.toml", "builtin:default"]"#,
+        );
+        let config = Config::load(&path).unwrap();
+        assert_eq!(
+            config
+                .rules
+                .iter()
+                .filter(|r| r.commands == ["make"])
+                .count(),
+            1
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_args_matchers() {
+        let config: Config = toml::from_str(
+            r#"
+            [[rules]]
+            commands = ["kubectl"]
+            permission = "ask"
+            args_regex = "(^| )-n prod( |$)"
+
+            [[rules]]
+            commands = ["curl"]
+            permission = "allow"
+            args_glob = "-s https://api.internal/*"
+            not_flags = ["-X", "--data"]
+
+            [[rules]]
+            commands = ["kubectl", "curl"]
+            permission = "deny"
+            "#,
+        )
+        .unwrap();
+        let check = |cmd: &str, args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            config.check_command(cmd, &args).permission
+        };
+
+        assert_eq!(check("kubectl", &["get", "-n", "prod"]), Permission::Ask);
+        assert_eq!(
+            check("kubectl", &["get", "-n", "production"]),
+            Permission::Deny
+        );
+        assert_eq!(
+            check("curl", &["-s", "https://api.internal/users"]),
+            Permission::Allow
+        );
+        assert_eq!(
+            check("curl", &["-s", "https://example.com/"]),
+            Permission::Deny
+        );
+        assert_eq!(
+            check("curl", &["-s", "https://api.internal/users", "-X", "POST"]),
+            Permission::Deny
+        );
+        // Flags given a value, attached or after `=`
+        assert_eq!(
+            check(
+                "curl",
+                &["-s", "https://api.internal/users", "--data=@secret"]
+            ),
+            Permission::Deny
+        );
+        assert_eq!(
+            check("curl", &["-s", "https://api.internal/users", "-XPOST"]),
+            Permission::Deny
+        );
+        // Each glob takes one argument
+        assert_eq!(
+            check("curl", &["-s", "https://api.internal/x", "-T", "secret"]),
+            Permission::Deny
+        );
+        assert_eq!(check("curl", &["-s"]), Permission::Deny);
+    }
+
+    #[test]
+    fn test_not_flags_match_whole_flags() {
+        let config: Config = toml::from_str(
+            r#"
+            [[rules]]
+            commands = ["curl"]
+            permission = "allow"
+            not_flags = ["--data"]
+            "#,
+        )
+        .unwrap();
+        let check = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            config.check_command("curl", &args).permission
+        };
+        assert_eq!(check(&["--data-raw", "x"]), Permission::Allow);
+        assert_eq!(check(&["--data=x"]), Permission::Ask);
+    }
+
+    #[test]
+    fn test_invalid_args_matchers_ask() {
+        let config: Config = toml::from_str(
+            r#"
+            [[rules]]
+            commands = ["kubectl"]
+            permission = "deny"
+            args_regex = "(^| )-n prod( |$"
+
+            [[rules]]
+            commands = ["kubectl"]
+            permission = "allow"
+            "#,
+        )
+        .unwrap();
+        let result = config.check_command("kubectl", &["get".to_string()]);
+        assert_eq!(result.permission, Permission::Ask);
+        assert!(
+            result.reason.contains("invalid args_regex"),
+            "{}",
+            result.reason
+        );
+
+        let config: Config = toml::from_str(
+            r#"
+            [[rules]]
+            commands = ["curl"]
+            permission = "deny"
+            args_glob = "-s https://[a-z"
+            "#,
+        )
+        .unwrap();
+        let result = config.check_command("curl", &["-s".to_string()]);
+        assert_eq!(result.permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_suggestion_pattern_is_regex() {
+        let config: Config = toml::from_str(
+            r#"
+            [[suggestions]]
+            command = "git checkout"
+            message = "use git switch -c"
+            pattern = " -b "
+            "#,
+        )
+        .unwrap();
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let result = config.check_command("git", &args(&["checkout", "-b", "feature"]));
+        assert_eq!(result.suggestion.as_deref(), Some("use git switch -c"));
+        let result = config.check_command("git", &args(&["checkout", "main"]));
+        assert!(result.suggestion.is_none());
+    }
+
+    #[test]
+    fn test_missing_include_is_error() {
+        let dir = temp_dir("missing");
+        let path = write_config(&dir, "config.toml", r#"include = ["nope.toml"]"#);
+        assert!(Config::load(&path).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_git_policy_overrides() {
+        let toml = r#"
+            [git]
+            protected_branches = ["main", "release/*"]
+
+            [git.permissions]
+            force = "deny"
+
+            [[git.remotes]]
+            url = "github.com:me/*"
+            permission = "allow"
+
+            [[git.overrides]]
+            cwd = "/home/user/scratch/**"
+            protected_branches = []
+
+            [[git.overrides.remotes]]
+            url = "github.com:me/scratch"
+            permission = "ask"
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+
+        let policy = config.git_policy(Some("/home/user/project"));
+        assert_eq!(policy.protected_branches, ["main", "release/*"]);
+        assert_eq!(policy.permissions.force, "deny");
+        assert_eq!(policy.permissions.delete, "ask");
+        assert_eq!(policy.remotes.len(), 1);
+
+        // The override replaces what it sets and puts its remote rules first
+        let policy = config.git_policy(Some("/home/user/scratch/a"));
+        assert!(policy.protected_branches.is_empty());
+        assert_eq!(policy.permissions.force, "deny");
+        assert_eq!(policy.remotes[0].url, "github.com:me/scratch");
+        assert_eq!(policy.remotes.len(), 2);
+
+        // Unset tables keep the defaults
+        let config: Config = toml::from_str("").unwrap();
+        let policy = config.git_policy(None);
+        assert_eq!(policy.protected_branches, ["main", "master"]);
+        assert_eq!(policy.permissions.force_with_lease, "allow");
+    }
 }
@@ -0,0 +1,414 @@
+//! Working directory tracking
+//!
+//! Follows `cd`, `pushd` and `popd` through the control flow of a command line
+//! to find the directory each command runs in. `cd` inside a subshell, pipeline
+//! element or command substitution doesn't leak out, and branches or loops that
+//! may end in different directories make the directory unknown.
+
+use crate::analyzer::{AnalysisResult, Command, Segment};
+
+/// Directory state at a point of the command line
+/// Fields are None when they can't be known statically
+#[derive(Debug, Clone, PartialEq)]
+struct Dirs {
+    cwd: Option<String>,
+    oldpwd: Option<String>,
+    /// The `pushd` stack, top last
+    stack: Option<Vec<Option<String>>>,
+}
+
+/// None when the point can't be reached (after `exit`)
+type State = Option<Dirs>;
+
+/// States after a segment, depending on whether it succeeded
+/// (`&&` continues from `ok`, `||` from `failed`)
+struct Outcome {
+    ok: State,
+    failed: State,
+}
+
+impl Outcome {
+    fn both(state: State) -> Self {
+        Outcome {
+            ok: state.clone(),
+            failed: state,
+        }
+    }
+}
+
+/// Compute the working directory of every command in `analysis`, starting from `start`
+/// Returns one entry per command, None where the directory can't be known
+pub fn command_dirs(analysis: &AnalysisResult, start: Option<&str>) -> Vec<Option<String>> {
+    let mut tracker = Tracker {
+        commands: &analysis.commands,
+        dirs: vec![None; analysis.commands.len()],
+    };
+    let state = Dirs {
+        cwd: start.map(normalize),
+        oldpwd: None,
+        stack: Some(Vec::new()),
+    };
+    tracker.run(&analysis.flow, Some(state));
+
+    // Unreachable commands get no directory
+    tracker.dirs.into_iter().map(Option::flatten).collect()
+}
+
+struct Tracker<'a> {
+    commands: &'a [Command],
+    /// Directory seen by each command: None if not reached yet
+    dirs: Vec<Option<Option<String>>>,
+}
+
+impl Tracker<'_> {
+    fn run(&mut self, segment: &Segment, state: State) -> Outcome {
+        match segment {
+            Segment::Empty => Outcome::both(state),
+            Segment::Command(index) => {
+                let Some(dirs) = state else {
+                    return Outcome::both(None);
+                };
+                if let Some(slot) = self.dirs.get_mut(*index) {
+                    *slot = Some(match slot.take() {
+                        Some(seen) => same(seen, dirs.cwd.clone()),
+                        None => dirs.cwd.clone(),
+                    });
+                }
+                match self.commands.get(*index) {
+                    Some(cmd) => apply(cmd, dirs),
+                    None => Outcome::both(Some(dirs)),
+                }
+            }
+            Segment::Sequence(segments) => {
+                let mut outcome = Outcome::both(state);
+                for segment in segments {
+                    outcome = self.run(segment, merge(outcome.ok, outcome.failed));
+                }
+                outcome
+            }
+            Segment::And(left, right) => {
+                let left = self.run(left, state);
+                let right = self.run(right, left.ok);
+                Outcome {
+                    ok: right.ok,
+                    failed: merge(left.failed, right.failed),
+                }
+            }
+            Segment::Or(left, right) => {
+                let left = self.run(left, state);
+                let right = self.run(right, left.failed);
+                Outcome {
+                    ok: merge(left.ok, right.ok),
+                    failed: right.failed,
+                }
+            }
+            Segment::Conditional(branches) => {
+                let mut ok = None;
+                let mut failed = None;
+                for branch in branches {
+                    let outcome = self.run(branch, state.clone());
+                    ok = merge(ok, outcome.ok);
+                    failed = merge(failed, outcome.failed);
+                }
+                Outcome { ok, failed }
+            }
+            Segment::Loop(body) => {
+                // Run the body until the entry state stops changing; states only
+                // lose information when merged, so this ends after a few rounds
+                let mut entry = state;
+                loop {
+                    let outcome = self.run(body, entry.clone());
+                    let next = merge(entry.clone(), merge(outcome.ok, outcome.failed));
+                    if next == entry {
+                        return Outcome::both(entry);
+                    }
+                    entry = next;
+                }
+            }
+            Segment::Subshell(inner) => {
+                self.run(inner, state.clone());
+                Outcome::both(state)
+            }
+        }
+    }
+}
+
+/// Apply the directory change made by a command
+fn apply(cmd: &Command, dirs: Dirs) -> Outcome {
+    let operands: Vec<&str> = operands(&cmd.args);
+    let changed = match cmd.name.as_str() {
+        "cd" => {
+            let cwd = match operands.first() {
+                None => home(),
+                Some(&"-") => dirs.oldpwd.clone(),
+                Some(dir) => resolve(dir, dirs.cwd.as_deref()),
+            };
+            Dirs {
+                oldpwd: dirs.cwd.clone(),
+                cwd,
+                stack: dirs.stack.clone(),
+            }
+        }
+        "pushd" => pushd(&cmd.args, &operands, &dirs),
+        "popd" => popd(&operands, &dirs),
+        "exit" => return Outcome::both(None),
+        _ => return Outcome::both(Some(dirs)),
+    };
+
+    // A failed directory change leaves everything as it was
+    Outcome {
+        ok: Some(changed),
+        failed: Some(dirs),
+    }
+}
+
+/// `pushd DIR` saves the current directory and changes to DIR;
+/// without arguments it swaps the current directory with the top of the stack
+fn pushd(args: &[String], operands: &[&str], dirs: &Dirs) -> Dirs {
+    let rotates = operands.iter().any(|o| is_stack_index(o));
+    if args.iter().any(|a| a == "-n") || rotates {
+        let cwd = if rotates { None } else { dirs.cwd.clone() };
+        return Dirs {
+            oldpwd: dirs.cwd.clone(),
+            cwd,
+            stack: None,
+        };
+    }
+
+    let mut stack = dirs.stack.clone();
+    let cwd = match operands.first() {
+        Some(dir) => {
+            if let Some(stack) = stack.as_mut() {
+                stack.push(dirs.cwd.clone());
+            }
+            resolve(dir, dirs.cwd.as_deref())
+        }
+        None => match stack.as_mut().map(|s| s.pop()) {
+            Some(Some(top)) => {
+                if let Some(stack) = stack.as_mut() {
+                    stack.push(dirs.cwd.clone());
+                }
+                top
+            }
+            // An empty stack is an error; leave the directory alone
+            Some(None) => return dirs.clone(),
+            None => None,
+        },
+    };
+
+    Dirs {
+        oldpwd: dirs.cwd.clone(),
+        cwd,
+        stack,
+    }
+}
+
+/// `popd` changes to the top of the stack and removes it
+fn popd(operands: &[&str], dirs: &Dirs) -> Dirs {
+    if operands.iter().any(|o| is_stack_index(o)) {
+        // Removes an entry without changing directory (except `+0`, which we don't model)
+        return Dirs {
+            stack: None,
+            ..dirs.clone()
+        };
+    }
+
+    let mut stack = dirs.stack.clone();
+    let cwd = match stack.as_mut().map(|s| s.pop()) {
+        Some(Some(top)) => top,
+        Some(None) => return dirs.clone(),
+        None => None,
+    };
+
+    Dirs {
+        oldpwd: dirs.cwd.clone(),
+        cwd,
+        stack,
+    }
+}
+
+/// Non-option arguments (`cd -L dir`, `cd -- -dir`); a lone `-` is an operand
+fn operands(args: &[String]) -> Vec<&str> {
+    let mut operands = Vec::new();
+    let mut options_done = false;
+    for arg in args {
+        if !options_done && arg == "--" {
+            options_done = true;
+        } else if options_done || arg == "-" || !arg.starts_with('-') || is_stack_index(arg) {
+            operands.push(arg.as_str());
+        }
+    }
+    operands
+}
+
+/// `+N` or `-N` stack positions taken by pushd and popd
+fn is_stack_index(arg: &str) -> bool {
+    arg.strip_prefix(['+', '-'])
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Resolve a `cd` target against the current directory
+/// Returns None for targets that depend on expansions or an unknown directory
+fn resolve(target: &str, cwd: Option<&str>) -> Option<String> {
+    if target.is_empty() || target.contains(['$', '`', '*', '?', '[']) {
+        return None;
+    }
+
+    if target == "~" {
+        return home();
+    }
+    if let Some(rest) = target.strip_prefix("~/") {
+        return Some(normalize(&format!("{}/{}", home()?, rest)));
+    }
+    if target.starts_with('~') {
+        // ~user - can't resolve reliably
+        return None;
+    }
+    if target.starts_with('/') {
+        return Some(normalize(target));
+    }
+    Some(normalize(&format!("{}/{}", cwd?, target)))
+}
+
+fn home() -> Option<String> {
+    std::env::var("HOME").ok().map(|h| normalize(&h))
+}
+
+/// Lexically normalize an absolute path, resolving `.` and `..` like `cd` does
+fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// Combine the states of two paths that may both have been taken
+fn merge(a: State, b: State) -> State {
+    match (a, b) {
+        (None, state) | (state, None) => state,
+        (Some(a), Some(b)) => Some(Dirs {
+            cwd: same(a.cwd, b.cwd),
+            oldpwd: same(a.oldpwd, b.oldpwd),
+            stack: same(a.stack, b.stack),
+        }),
+    }
+}
+
+/// Keep a value only if both sides agree on it
+fn same<T: PartialEq>(a: Option<T>, b: Option<T>) -> Option<T> {
+    if a == b { a } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::analyze;
+
+    /// Directory of each command named `name`
+    fn dirs_of(command: &str, name: &str) -> Vec<Option<String>> {
+        let analysis = analyze(command);
+        let dirs = command_dirs(&analysis, Some("/work"));
+        analysis
+            .commands
+            .iter()
+            .zip(dirs)
+            .filter(|(cmd, _)| cmd.name == name)
+            .map(|(_, dir)| dir)
+            .collect()
+    }
+
+    fn dir(path: &str) -> Option<String> {
+        Some(path.to_string())
+    }
+
+    #[test]
+    fn test_sequential_cd() {
+        assert_eq!(
+            dirs_of("cd /tmp/claude && tar -xf x", "tar"),
+            [dir("/tmp/claude")]
+        );
+        assert_eq!(dirs_of("ls; tar -xf x", "tar"), [dir("/work")]);
+        assert_eq!(dirs_of("cd sub/../src && make", "make"), [dir("/work/src")]);
+        assert_eq!(dirs_of("cd a && cd b && make", "make"), [dir("/work/a/b")]);
+    }
+
+    #[test]
+    fn test_arguments_are_not_control_flow() {
+        assert_eq!(
+            dirs_of("echo \"wait for it\"; cd /tmp/claude && tar -xf x", "tar"),
+            [dir("/tmp/claude")]
+        );
+    }
+
+    #[test]
+    fn test_subshell_cd_does_not_leak() {
+        assert_eq!(dirs_of("(cd /etc && ls); rm x", "rm"), [dir("/work")]);
+        assert_eq!(dirs_of("(cd /etc && ls); rm x", "ls"), [dir("/etc")]);
+        assert_eq!(dirs_of("cd /etc | cat; rm x", "rm"), [dir("/work")]);
+        assert_eq!(dirs_of("echo $(cd /etc); rm x", "rm"), [dir("/work")]);
+    }
+
+    #[test]
+    fn test_conditional_cd_is_unknown() {
+        assert_eq!(dirs_of("if x; then cd /etc; fi; rm y", "rm"), [None]);
+        assert_eq!(dirs_of("if x; then cd /etc; fi; rm y", "x"), [dir("/work")]);
+        assert_eq!(dirs_of("case $a in b) cd /etc;; esac; rm y", "rm"), [None]);
+        assert_eq!(dirs_of("for f in a; do cd $f; done; rm y", "rm"), [None]);
+        assert_eq!(dirs_of("while x; do cd ..; rm y; done", "rm"), [None]);
+        // Every branch ends in the same place
+        assert_eq!(
+            dirs_of(
+                "if x; then cd /tmp || exit; else cd /tmp || exit; fi; rm y",
+                "rm"
+            ),
+            [dir("/tmp")]
+        );
+        assert_eq!(
+            dirs_of("for f in a b; do ls; done; rm y", "rm"),
+            [dir("/work")]
+        );
+    }
+
+    #[test]
+    fn test_and_or_lists() {
+        assert_eq!(dirs_of("cd /a || rm y", "rm"), [dir("/work")]);
+        assert_eq!(dirs_of("cd /a || exit 1; rm y", "rm"), [dir("/a")]);
+        assert_eq!(dirs_of("cd /a; rm y", "rm"), [None]);
+        assert_eq!(dirs_of("cd /a || cd /b; rm y", "rm"), [None]);
+        assert_eq!(dirs_of("true && cd /a && rm y", "rm"), [dir("/a")]);
+    }
+
+    #[test]
+    fn test_home_and_oldpwd() {
+        let home = home();
+        assert_eq!(dirs_of("cd && rm y", "rm")[0], home);
+        assert_eq!(dirs_of("cd ~ && rm y", "rm")[0], home);
+        assert_eq!(dirs_of("cd /a && cd /b && cd - && rm y", "rm"), [dir("/a")]);
+        assert_eq!(dirs_of("cd - && rm y", "rm"), [None]);
+        assert_eq!(dirs_of("cd \"$DIR\" && rm y", "rm"), [None]);
+    }
+
+    #[test]
+    fn test_pushd_popd() {
+        assert_eq!(dirs_of("pushd /a && rm y", "rm"), [dir("/a")]);
+        assert_eq!(dirs_of("pushd /a && popd && rm y", "rm"), [dir("/work")]);
+        assert_eq!(
+            dirs_of("pushd /a && pushd /b && pushd && rm y", "rm"),
+            [dir("/a")]
+        );
+        assert_eq!(dirs_of("popd && rm y", "rm"), [dir("/work")]);
+        assert_eq!(dirs_of("pushd +1 && rm y", "rm"), [None]);
+    }
+
+    #[test]
+    fn test_function_body_cd() {
+        assert_eq!(dirs_of("f() { cd /etc; }; f && rm y", "rm"), [dir("/etc")]);
+    }
+}
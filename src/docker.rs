@@ -37,14 +37,14 @@ fn has_rw_bind_mount(args: &[&str]) -> bool {
         let arg = args[i];
 
         // Handle -v/--volume
-        if arg == "-v" || arg == "--volume" {
-            if let Some(volume) = args.get(i + 1) {
-                if is_rw_bind_mount(volume) {
-                    return true;
-                }
-                i += 2;
-                continue;
+        if (arg == "-v" || arg == "--volume")
+            && let Some(volume) = args.get(i + 1)
+        {
+            if is_rw_bind_mount(volume) {
+                return true;
             }
+            i += 2;
+            continue;
         }
 
         // Handle -v=value or --volume=value
@@ -60,14 +60,14 @@ fn has_rw_bind_mount(args: &[&str]) -> bool {
         }
 
         // Handle --mount
-        if arg == "--mount" {
-            if let Some(mount) = args.get(i + 1) {
-                if is_rw_mount(mount) {
-                    return true;
-                }
-                i += 2;
-                continue;
+        if arg == "--mount"
+            && let Some(mount) = args.get(i + 1)
+        {
+            if is_rw_mount(mount) {
+                return true;
             }
+            i += 2;
+            continue;
         }
 
         // Handle --mount=value
@@ -142,6 +142,7 @@ mod tests {
             name: "docker".to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("docker {}", args.join(" ")),
+            ..Default::default()
         }
     }
 
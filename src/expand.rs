@@ -0,0 +1,503 @@
+//! Shell word expansion for path checks
+//!
+//! Expands an argument the way bash would before the command sees it: braces,
+//! tilde, environment variables and filename globs against the directory the
+//! command runs in. Anything that can't be resolved statically (command
+//! substitutions, unset variables, `~user`, globs with an unknown cwd, ...)
+//! makes the word unknown, which path checkers treat as unsafe.
+
+use std::path::PathBuf;
+
+/// Upper bound on the words a single argument may expand to
+const MAX_WORDS: usize = 1_000;
+
+/// A character of an expanded word, and whether it's an unquoted glob character
+type Char = (char, bool);
+
+/// Expand a word as written in the source (quotes included) into the words
+/// the command receives. `cwd` is the directory the command runs in, None if unknown,
+/// and `assigned` the variables the command line sets, whose values aren't the
+/// environment's.
+/// Returns None if the expansion can't be known before the command runs.
+pub fn expand_word(word: &str, cwd: Option<&str>, assigned: &[String]) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    for word in expand_braces(word)? {
+        let chars = expand_quotes_and_vars(&word, cwd, assigned)?;
+        if chars.iter().any(|&(_, glob)| glob) {
+            words.extend(expand_glob(&chars, cwd)?);
+        } else {
+            words.push(chars.iter().map(|&(c, _)| c).collect());
+        }
+        if words.len() > MAX_WORDS {
+            return None;
+        }
+    }
+    Some(words)
+}
+
+/// Which characters of a word are outside quotes and not escaped
+fn unquoted(chars: &[char]) -> Vec<bool> {
+    let mut active = Vec::with_capacity(chars.len());
+    let (mut single, mut double, mut escaped) = (false, false, false);
+    for &c in chars {
+        let is_active = !single && !double && !escaped;
+        active.push(is_active);
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && !single {
+            escaped = true;
+        } else if c == '\'' && !double {
+            single = !single;
+        } else if c == '"' && !single {
+            double = !double;
+        }
+    }
+    active
+}
+
+/// Brace expansion: `a{b,c}d` -> `abd acd`, `x{1..3}` -> `x1 x2 x3`
+/// Words are returned still quoted; an unmatched or plain `{...}` is left alone
+fn expand_braces(word: &str) -> Option<Vec<String>> {
+    let chars: Vec<char> = word.chars().collect();
+    let active = unquoted(&chars);
+
+    let mut start = 0;
+    while let Some(open) = (start..chars.len())
+        .find(|&i| chars[i] == '{' && active[i] && !(i > 0 && chars[i - 1] == '$' && active[i - 1]))
+    {
+        let mut depth = 0;
+        let mut commas = Vec::new();
+        let mut close = None;
+        for i in open + 1..chars.len() {
+            if !active[i] {
+                continue;
+            }
+            match chars[i] {
+                '{' => depth += 1,
+                '}' if depth == 0 => {
+                    close = Some(i);
+                    break;
+                }
+                '}' => depth -= 1,
+                ',' if depth == 0 => commas.push(i),
+                _ => {}
+            }
+        }
+        let Some(close) = close else {
+            break;
+        };
+
+        let prefix: String = chars[..open].iter().collect();
+        let suffix: String = chars[close + 1..].iter().collect();
+        let alternatives: Vec<String> = if commas.is_empty() {
+            let body: String = chars[open + 1..close].iter().collect();
+            match sequence(&body) {
+                Some(items) => items,
+                None => {
+                    start = open + 1;
+                    continue;
+                }
+            }
+        } else {
+            let mut bounds = vec![open];
+            bounds.extend(&commas);
+            bounds.push(close);
+            bounds
+                .windows(2)
+                .map(|w| chars[w[0] + 1..w[1]].iter().collect())
+                .collect()
+        };
+
+        let mut words = Vec::new();
+        for alternative in alternatives {
+            words.extend(expand_braces(&format!(
+                "{}{}{}",
+                prefix, alternative, suffix
+            ))?);
+            if words.len() > MAX_WORDS {
+                return None;
+            }
+        }
+        return Some(words);
+    }
+
+    Some(vec![word.to_string()])
+}
+
+/// A `{a..b}` sequence of integers or single letters
+fn sequence(body: &str) -> Option<Vec<String>> {
+    let (from, to) = body.split_once("..")?;
+    if let (Ok(from), Ok(to)) = (from.parse::<i64>(), to.parse::<i64>()) {
+        // Past MAX_WORDS the caller gives up on the word
+        let items: Vec<i64> = if from <= to {
+            (from..=to).take(MAX_WORDS + 1).collect()
+        } else {
+            (to..=from).rev().take(MAX_WORDS + 1).collect()
+        };
+        return Some(items.iter().map(|n| n.to_string()).collect());
+    }
+
+    let (mut from_chars, mut to_chars) = (from.chars(), to.chars());
+    match (
+        from_chars.next(),
+        from_chars.next(),
+        to_chars.next(),
+        to_chars.next(),
+    ) {
+        (Some(from), None, Some(to), None)
+            if from.is_ascii_alphabetic() && to.is_ascii_alphabetic() =>
+        {
+            let items: Vec<char> = if from <= to {
+                (from..=to).collect()
+            } else {
+                (to..=from).rev().collect()
+            };
+            Some(items.iter().map(|c| c.to_string()).collect())
+        }
+        _ => None,
+    }
+}
+
+/// Remove quotes and expand tilde and variables, marking unquoted glob characters
+fn expand_quotes_and_vars(word: &str, cwd: Option<&str>, assigned: &[String]) -> Option<Vec<Char>> {
+    let chars: Vec<char> = word.chars().collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    // Tilde prefix, up to the first slash
+    if chars.first() == Some(&'~') {
+        let end = chars.iter().position(|&c| c == '/').unwrap_or(chars.len());
+        let prefix: String = chars[1..end].iter().collect();
+        let dir = match prefix.as_str() {
+            "" if !assigned.iter().any(|v| v == "HOME") => std::env::var("HOME").ok()?,
+            "+" => cwd?.to_string(),
+            // ~user and ~- can't be resolved reliably
+            _ => return None,
+        };
+        out.extend(dir.chars().map(|c| (c, false)));
+        i = end;
+    }
+
+    let mut double = false;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if i + 1 < chars.len() => {
+                let next = chars[i + 1];
+                if double && !matches!(next, '$' | '`' | '"' | '\\' | '\n') {
+                    out.push(('\\', false));
+                }
+                out.push((next, false));
+                i += 2;
+            }
+            '\'' if !double => {
+                let end = chars[i + 1..].iter().position(|&c| c == '\'')? + i + 1;
+                out.extend(chars[i + 1..end].iter().map(|&c| (c, false)));
+                i = end + 1;
+            }
+            '"' => {
+                double = !double;
+                i += 1;
+            }
+            '`' => return None,
+            '$' => {
+                let (value, len) = expand_variable(&chars[i + 1..], cwd, assigned)?;
+                // Unquoted values are split into words and globbed
+                if !double && value.chars().any(char::is_whitespace) {
+                    return None;
+                }
+                out.extend(value.chars().map(|c| (c, !double && is_glob_char(c))));
+                i += 1 + len;
+            }
+            _ => {
+                out.push((c, !double && is_glob_char(c)));
+                i += 1;
+            }
+        }
+    }
+
+    Some(out)
+}
+
+/// Expand the variable after a `$`, returning its value and the characters consumed
+/// Returns None for unset or `assigned` variables and expansions that can't be evaluated
+fn expand_variable(
+    rest: &[char],
+    cwd: Option<&str>,
+    assigned: &[String],
+) -> Option<(String, usize)> {
+    let (name, len) = match rest.first() {
+        Some('{') => {
+            let end = rest.iter().position(|&c| c == '}')?;
+            let name: String = rest[1..end].iter().collect();
+            (name, end + 1)
+        }
+        Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+            let len = rest
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                .count();
+            (rest[..len].iter().collect(), len)
+        }
+        // `$(...)`, `$((...))`, `$'...'`, positional and special parameters
+        Some('(' | '\'' | '"' | '@' | '*' | '#' | '?' | '-' | '$' | '!') => return None,
+        Some(c) if c.is_ascii_digit() => return None,
+        // A lone `$` is literal
+        _ => return Some(("$".to_string(), 0)),
+    };
+
+    // ${VAR:-x}, ${#VAR}, ${VAR/a/b}, ... aren't evaluated
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    if assigned.contains(&name) {
+        return None;
+    }
+    let value = match name.as_str() {
+        "PWD" => cwd?.to_string(),
+        _ => std::env::var(&name).ok()?,
+    };
+    Some((value, len))
+}
+
+fn is_glob_char(c: char) -> bool {
+    matches!(c, '*' | '?' | '[')
+}
+
+/// Expand a glob against the filesystem
+/// Like bash without `nullglob`, a pattern that matches nothing is kept as written
+fn expand_glob(chars: &[Char], cwd: Option<&str>) -> Option<Vec<String>> {
+    let absolute = chars.first().is_some_and(|&(c, _)| c == '/');
+    let base = if absolute { "/" } else { cwd? };
+
+    // (word so far, directory it names)
+    let mut candidates = vec![(
+        if absolute {
+            "/".to_string()
+        } else {
+            String::new()
+        },
+        PathBuf::from(base),
+    )];
+    let components: Vec<&[Char]> = chars.split(|&(c, _)| c == '/').collect();
+    let last = components.len() - 1;
+
+    for (index, component) in components.into_iter().enumerate() {
+        if component.is_empty() {
+            if index == last && index > 0 {
+                // Trailing slash
+                for (word, _) in &mut candidates {
+                    if !word.ends_with('/') {
+                        word.push('/');
+                    }
+                }
+            }
+            continue;
+        }
+
+        let mut next = Vec::new();
+        if component.iter().any(|&(_, glob)| glob) {
+            for (word, dir) in &candidates {
+                let Ok(entries) = std::fs::read_dir(dir) else {
+                    continue;
+                };
+                let mut names: Vec<String> = entries
+                    .filter_map(|e| e.ok()?.file_name().into_string().ok())
+                    .filter(|name| glob_matches(component, name))
+                    .collect();
+                names.sort();
+                for name in names {
+                    next.push((join(word, &name), dir.join(&name)));
+                }
+            }
+        } else {
+            let name: String = component.iter().map(|&(c, _)| c).collect();
+            for (word, dir) in &candidates {
+                next.push((join(word, &name), dir.join(&name)));
+            }
+        }
+
+        if next.len() > MAX_WORDS {
+            return None;
+        }
+        candidates = next;
+    }
+
+    if candidates.is_empty() {
+        return Some(vec![chars.iter().map(|&(c, _)| c).collect()]);
+    }
+    Some(candidates.into_iter().map(|(word, _)| word).collect())
+}
+
+fn join(word: &str, name: &str) -> String {
+    if word.is_empty() || word.ends_with('/') {
+        format!("{}{}", word, name)
+    } else {
+        format!("{}/{}", word, name)
+    }
+}
+
+/// Match a file name against one glob component
+/// A leading `.` has to be matched explicitly, as in bash
+fn glob_matches(pattern: &[Char], name: &str) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    if name.first() == Some(&'.') && pattern.first() != Some(&('.', false)) {
+        return false;
+    }
+    match_from(pattern, &name)
+}
+
+fn match_from(pattern: &[Char], name: &[char]) -> bool {
+    let Some((&(p, glob), rest)) = pattern.split_first() else {
+        return name.is_empty();
+    };
+
+    match (p, glob) {
+        ('*', true) => (0..=name.len()).any(|skip| match_from(rest, &name[skip..])),
+        ('?', true) => !name.is_empty() && match_from(rest, &name[1..]),
+        ('[', true) => match bracket(rest) {
+            Some((matches, len)) => {
+                !name.is_empty() && matches(name[0]) && match_from(&rest[len..], &name[1..])
+            }
+            // No closing bracket: a literal `[`
+            None => name.first() == Some(&'[') && match_from(rest, &name[1..]),
+        },
+        _ => name.first() == Some(&p) && match_from(rest, &name[1..]),
+    }
+}
+
+/// Parse a bracket expression after `[`, returning its matcher and length including `]`
+fn bracket(pattern: &[Char]) -> Option<(impl Fn(char) -> bool, usize)> {
+    let mut i = 0;
+    let negated = matches!(pattern.first(), Some(('!' | '^', _)));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let &(c, _) = pattern.get(i)?;
+        if c == ']' && !first {
+            break;
+        }
+        first = false;
+        if let (Some(('-', _)), Some(&(end, _))) = (pattern.get(i + 1), pattern.get(i + 2))
+            && end != ']'
+        {
+            ranges.push((c, end));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+
+    let matcher = move |c: char| ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != negated;
+    Some((matcher, i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    fn expand(word: &str) -> Option<Vec<String>> {
+        expand_word(word, Some("/work"), &[])
+    }
+
+    #[test]
+    fn test_plain_and_quoted_words() {
+        assert_eq!(expand("a.txt").unwrap(), ["a.txt"]);
+        assert_eq!(expand("'a b'").unwrap(), ["a b"]);
+        assert_eq!(expand("\"a\\\"b\"").unwrap(), ["a\"b"]);
+        assert_eq!(expand("a\\ b").unwrap(), ["a b"]);
+    }
+
+    #[test]
+    fn test_tilde() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(expand("~").unwrap()[0], home);
+        assert_eq!(
+            expand("~/Documents").unwrap(),
+            [format!("{}/Documents", home)]
+        );
+        assert_eq!(expand("~+/x").unwrap(), ["/work/x"]);
+        // Quoted tilde is a literal file name
+        assert_eq!(expand("'~'/x").unwrap(), ["~/x"]);
+        assert!(expand("~root/x").is_none());
+    }
+
+    #[test]
+    fn test_variables() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(expand("$HOME/x").unwrap(), [format!("{}/x", home)]);
+        assert_eq!(expand("\"${HOME}\"/x").unwrap(), [format!("{}/x", home)]);
+        assert_eq!(expand("$PWD/x").unwrap(), ["/work/x"]);
+        assert_eq!(expand("'$HOME'").unwrap(), ["$HOME"]);
+        assert!(expand("$CBH_SURELY_UNSET_VARIABLE/x").is_none());
+        assert!(expand("${HOME:-/tmp}").is_none());
+        assert!(expand("$(pwd)/x").is_none());
+        assert!(expand("`pwd`/x").is_none());
+        assert!(expand("$1").is_none());
+    }
+
+    #[test]
+    fn test_assigned_variables_unknown() {
+        let home = std::env::var("HOME").unwrap();
+        let assigned = ["HOME".to_string(), "PWD".to_string()];
+        let expand = |word: &str| expand_word(word, Some("/work"), &assigned);
+        assert!(expand("$HOME/x").is_none());
+        assert!(expand("\"${HOME}\"").is_none());
+        assert!(expand("~/x").is_none());
+        assert!(expand("$PWD/x").is_none());
+        assert_eq!(expand("'$HOME'").unwrap(), ["$HOME"]);
+        let assigned = ["X".to_string()];
+        assert_eq!(expand_word("$HOME", None, &assigned).unwrap(), [home]);
+    }
+
+    #[test]
+    fn test_braces() {
+        assert_eq!(
+            expand("/tmp/{a,b}.txt").unwrap(),
+            ["/tmp/a.txt", "/tmp/b.txt"]
+        );
+        assert_eq!(expand("x{1..3}").unwrap(), ["x1", "x2", "x3"]);
+        assert_eq!(expand("{a,b{c,d}}").unwrap(), ["a", "bc", "bd"]);
+        assert_eq!(expand("'{a,b}'").unwrap(), ["{a,b}"]);
+        assert_eq!(expand("{a}").unwrap(), ["{a}"]);
+        assert!(expand("x{1..100000}").is_none());
+    }
+
+    #[test]
+    fn test_globs() {
+        let dir = temp_dir("expand");
+        for name in ["a.log", "b.log", "c.txt", ".hidden.log"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub/d.log"), "").unwrap();
+        let cwd = dir.to_str();
+        let root = dir.display().to_string();
+
+        assert_eq!(expand_word("*.log", cwd, &[]).unwrap(), ["a.log", "b.log"]);
+        assert_eq!(
+            expand_word("[ab].log", cwd, &[]).unwrap(),
+            ["a.log", "b.log"]
+        );
+        assert_eq!(expand_word("[!a]*.log", cwd, &[]).unwrap(), ["b.log"]);
+        assert_eq!(expand_word("?.txt", cwd, &[]).unwrap(), ["c.txt"]);
+        assert_eq!(expand_word("*/*.log", cwd, &[]).unwrap(), ["sub/d.log"]);
+        assert_eq!(
+            expand_word(&format!("{}/s*/", root), None, &[]).unwrap(),
+            [format!("{}/sub/", root)]
+        );
+        // Quoted globs and unmatched patterns are kept as written
+        assert_eq!(expand_word("'*.log'", cwd, &[]).unwrap(), ["*.log"]);
+        assert_eq!(expand_word("*.none", cwd, &[]).unwrap(), ["*.none"]);
+        // A relative glob needs the cwd
+        assert!(expand_word("*.log", None, &[]).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,707 @@
+//! Fish command analyzer
+//!
+//! There is no fish grammar among the parsers used here, so this is a small
+//! hand-written parser covering what permission checks need: commands with
+//! their arguments, assignments and redirects, separated by `;`, newlines,
+//! pipes, `&&`, `||` and the `and`/`or`/`not` keywords, inside `begin`, `if`,
+//! `while`, `for`, `switch` and `function` blocks, plus command substitutions.
+//! Control flow isn't tracked: every command found is checked.
+
+use crate::analyzer::{Assignment, Command, Input, Redirect, Stdin};
+use crate::quote;
+
+/// Deepest nesting of command substitutions parsed
+const MAX_NESTING: usize = 32;
+
+/// Result of analyzing a fish command
+#[derive(Debug)]
+pub struct FishAnalysisResult {
+    /// All commands found, including those in blocks and command substitutions
+    pub commands: Vec<Command>,
+    /// Whether parsing succeeded
+    pub success: bool,
+    /// Error message if parsing failed
+    pub error: Option<String>,
+}
+
+/// Analyze a fish command string and extract all commands
+pub fn analyze(cmd: &str) -> FishAnalysisResult {
+    let mut commands = Vec::new();
+    match parse(cmd, 0, &mut commands) {
+        Ok(()) => FishAnalysisResult {
+            commands,
+            success: true,
+            error: None,
+        },
+        Err(error) => FishAnalysisResult {
+            commands: vec![],
+            success: false,
+            error: Some(error),
+        },
+    }
+}
+
+/// A word with fish's quoting removed
+#[derive(Debug, Default)]
+struct Word {
+    /// The word as written
+    raw: String,
+    /// The value with quotes and escapes removed, expansions left as written
+    value: String,
+    /// Whether the word contains a variable or command substitution
+    dynamic: bool,
+    /// Whether any part of the word is quoted or escaped
+    quoted: bool,
+}
+
+impl Word {
+    /// The word written for bash, so wrappers that pass it on (`sudo rm 'a b'`)
+    /// keep its quoting; bare globs, braces and `~` keep their meaning
+    /// Dynamic words stay in fish syntax, for fish wrappers only: a bash wrapper
+    /// given one runs a command only known at runtime
+    fn bash(&self) -> String {
+        let bare = !self.quoted
+            && self
+                .value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "/._-+=:@,%*?[]{}~".contains(c));
+        if self.dynamic || bare {
+            self.raw.clone()
+        } else {
+            quote::quote(&self.value)
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    Word(Word),
+    /// A redirection, with the target when it's part of the operator (`2>&1`)
+    Redirect {
+        fd: Option<String>,
+        operator: String,
+        target: Option<String>,
+    },
+    /// `;`, a newline or `&`
+    End,
+    /// `|`, `&|` or `2>|`
+    Pipe,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+}
+
+/// Splits fish source into tokens, parsing command substitutions as it goes
+struct Lexer<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+    commands: &'a mut Vec<Command>,
+}
+
+impl Lexer<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, String> {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r') => self.pos += 1,
+                Some('\\') if self.peek_at(1) == Some('\n') => self.pos += 2,
+                Some('#') => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        let Some(c) = self.peek() else {
+            return Ok(None);
+        };
+        let token = match c {
+            '\n' | ';' => {
+                self.pos += 1;
+                Token::End
+            }
+            '|' => {
+                self.pos += 1;
+                if self.peek() == Some('|') {
+                    self.pos += 1;
+                    Token::Or
+                } else {
+                    Token::Pipe
+                }
+            }
+            '&' => match self.peek_at(1) {
+                Some('&') => {
+                    self.pos += 2;
+                    Token::And
+                }
+                Some('|') => {
+                    self.pos += 2;
+                    Token::Pipe
+                }
+                Some('>') => self.redirect(None),
+                _ => {
+                    self.pos += 1;
+                    Token::End
+                }
+            },
+            '>' | '<' => self.redirect(None),
+            ')' => return Err("unexpected ')'".to_string()),
+            _ => self.word()?,
+        };
+        Ok(Some(token))
+    }
+
+    /// A word, or a redirection with a file descriptor (`2>`)
+    fn word(&mut self) -> Result<Token, String> {
+        let mut word = Word::default();
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' | '\n' | ';' | '|' | '&' | ')' => break,
+                '>' | '<' => {
+                    if !word.quoted
+                        && !word.raw.is_empty()
+                        && word.raw.chars().all(|c| c.is_ascii_digit())
+                    {
+                        return Ok(self.redirect(Some(word.raw)));
+                    }
+                    break;
+                }
+                '\'' => self.single_quoted(&mut word)?,
+                '"' => self.double_quoted(&mut word)?,
+                '\\' => self.escape(&mut word),
+                '$' => self.variable(&mut word)?,
+                '(' => self.substitution(&mut word)?,
+                _ => {
+                    self.pos += 1;
+                    word.raw.push(c);
+                    word.value.push(c);
+                }
+            }
+        }
+        Ok(Token::Word(word))
+    }
+
+    /// `>`, `>>`, `<`, `&>`, `>?` (no clobber), `2>&1`, `>&-`, or `2>|` (a pipe)
+    fn redirect(&mut self, fd: Option<String>) -> Token {
+        let mut operator = String::new();
+        if self.peek() == Some('&') {
+            self.pos += 1;
+            operator.push('&');
+        }
+        let direction = self.bump().unwrap_or('>');
+        operator.push(direction);
+        if direction == '>' && self.peek() == Some('>') {
+            self.pos += 1;
+            operator.push('>');
+        }
+        if self.peek() == Some('?') {
+            self.pos += 1;
+        }
+        if direction == '>' && self.peek() == Some('|') {
+            self.pos += 1;
+            return Token::Pipe;
+        }
+
+        let mut target = None;
+        if self.peek() == Some('&') && !operator.starts_with('&') {
+            self.pos += 1;
+            operator = format!("{}&", direction);
+            let mut fd = String::new();
+            while let Some(c) = self.peek().filter(|c| c.is_ascii_digit() || *c == '-') {
+                self.pos += 1;
+                fd.push(c);
+            }
+            target = Some(fd);
+        }
+        Token::Redirect {
+            fd,
+            operator,
+            target,
+        }
+    }
+
+    /// `'...'`, where only `\'` and `\\` are escapes
+    fn single_quoted(&mut self, word: &mut Word) -> Result<(), String> {
+        word.quoted = true;
+        word.raw.push(self.bump().unwrap_or('\''));
+        loop {
+            let c = self.bump().ok_or("unterminated single quote")?;
+            word.raw.push(c);
+            match c {
+                '\'' => return Ok(()),
+                '\\' if matches!(self.peek(), Some('\'' | '\\')) => {
+                    let escaped = self.bump().unwrap_or('\\');
+                    word.raw.push(escaped);
+                    word.value.push(escaped);
+                }
+                _ => word.value.push(c),
+            }
+        }
+    }
+
+    /// `"..."`, which expands variables and `$(...)` but not `(...)`
+    fn double_quoted(&mut self, word: &mut Word) -> Result<(), String> {
+        word.quoted = true;
+        word.raw.push(self.bump().unwrap_or('"'));
+        loop {
+            let c = self.peek().ok_or("unterminated double quote")?;
+            match c {
+                '"' => {
+                    self.pos += 1;
+                    word.raw.push(c);
+                    return Ok(());
+                }
+                '\\' => {
+                    self.pos += 1;
+                    word.raw.push(c);
+                    match self.bump() {
+                        Some('\n') => word.raw.push('\n'),
+                        Some(escaped @ ('"' | '$' | '\\')) => {
+                            word.raw.push(escaped);
+                            word.value.push(escaped);
+                        }
+                        Some(other) => {
+                            word.raw.push(other);
+                            word.value.push('\\');
+                            word.value.push(other);
+                        }
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+                '$' => self.variable(word)?,
+                _ => {
+                    self.pos += 1;
+                    word.raw.push(c);
+                    word.value.push(c);
+                }
+            }
+        }
+    }
+
+    /// A backslash escape outside quotes (`\ `, `\n`, `\x41`, `é`)
+    fn escape(&mut self, word: &mut Word) {
+        word.quoted = true;
+        word.raw.push(self.bump().unwrap_or('\\'));
+        let Some(c) = self.bump() else {
+            return;
+        };
+        word.raw.push(c);
+        let decoded = match c {
+            '\n' => return,
+            'a' => '\x07',
+            'b' => '\x08',
+            'e' => '\x1b',
+            'f' => '\x0c',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'v' => '\x0b',
+            'x' | 'X' => self.code(word, 16, 2),
+            'u' => self.code(word, 16, 4),
+            'U' => self.code(word, 16, 8),
+            '0'..='7' => {
+                self.pos -= 1;
+                word.raw.pop();
+                self.code(word, 8, 3)
+            }
+            'c' => match self.bump() {
+                Some(control) => {
+                    word.raw.push(control);
+                    char::from_u32(control as u32 & 0x1f).unwrap_or(control)
+                }
+                None => c,
+            },
+            _ => c,
+        };
+        word.value.push(decoded);
+    }
+
+    /// The character coded by up to `max` digits in `radix`
+    fn code(&mut self, word: &mut Word, radix: u32, max: usize) -> char {
+        let mut code = 0;
+        let mut digits = 0;
+        while digits < max
+            && let Some(c) = self.peek()
+            && let Some(digit) = c.to_digit(radix)
+        {
+            self.pos += 1;
+            word.raw.push(c);
+            code = code * radix + digit;
+            digits += 1;
+        }
+        char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+
+    /// `$name`, `$name[1]` or `$(...)`, kept as written
+    fn variable(&mut self, word: &mut Word) -> Result<(), String> {
+        word.dynamic = true;
+        if self.peek_at(1) == Some('(') {
+            self.pos += 1;
+            word.raw.push('$');
+            word.value.push('$');
+            return self.substitution(word);
+        }
+        let start = self.pos;
+        self.pos += 1;
+        while self.peek() == Some('$') {
+            self.pos += 1;
+        }
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        word.raw.push_str(&text);
+        word.value.push_str(&text);
+        Ok(())
+    }
+
+    /// `(...)`: the commands inside are parsed, the word keeps the text as written
+    fn substitution(&mut self, word: &mut Word) -> Result<(), String> {
+        word.dynamic = true;
+        self.pos += 1;
+        let start = self.pos;
+        let mut depth = 1;
+        let mut quote = None;
+        while depth > 0 {
+            let c = self.bump().ok_or("unterminated command substitution")?;
+            match (quote, c) {
+                (_, '\\') => self.pos += 1,
+                (None, '\'' | '"') => quote = Some(c),
+                (Some(q), _) if c == q => quote = None,
+                (None, '(') => depth += 1,
+                (None, ')') => depth -= 1,
+                _ => {}
+            }
+        }
+        let inner: String = self.chars[start..self.pos - 1].iter().collect();
+        parse(&inner, self.depth + 1, self.commands)?;
+        let text = format!("({})", inner);
+        word.raw.push_str(&text);
+        word.value.push_str(&text);
+        Ok(())
+    }
+}
+
+/// Words and redirections of one statement, up to a separator
+#[derive(Default)]
+struct Statement {
+    words: Vec<Word>,
+    redirects: Vec<(Option<String>, Redirect)>,
+}
+
+/// Parse `source`, appending the commands found to `commands`
+fn parse(source: &str, depth: usize, commands: &mut Vec<Command>) -> Result<(), String> {
+    if depth > MAX_NESTING {
+        return Err(format!(
+            "command substitutions nested more than {} deep",
+            MAX_NESTING
+        ));
+    }
+    let mut lexer = Lexer {
+        chars: source.chars().collect(),
+        pos: 0,
+        depth,
+        commands,
+    };
+    let mut blocks = Vec::new();
+    let mut statement = Statement::default();
+    loop {
+        match lexer.next_token()? {
+            Some(Token::Word(word)) => statement.words.push(word),
+            Some(Token::Redirect {
+                fd,
+                operator,
+                target,
+            }) => {
+                let (target, raw_target) = match target {
+                    Some(target) => (target.clone(), target),
+                    None => match lexer.next_token()? {
+                        Some(Token::Word(word)) => (word.value.clone(), word.bash()),
+                        _ => return Err(format!("missing target for '{}'", operator)),
+                    },
+                };
+                statement.redirects.push((
+                    fd,
+                    Redirect {
+                        operator,
+                        target,
+                        raw_target,
+                    },
+                ));
+            }
+            token => {
+                let statement = std::mem::take(&mut statement);
+                finish(statement, &mut blocks, lexer.commands)?;
+                if token.is_none() {
+                    break;
+                }
+            }
+        }
+    }
+    match blocks.last() {
+        Some(block) => Err(format!("missing 'end' for '{}'", block)),
+        None => Ok(()),
+    }
+}
+
+/// Turn a statement into a command, handling the keywords that open and close blocks
+fn finish(
+    statement: Statement,
+    blocks: &mut Vec<String>,
+    commands: &mut Vec<Command>,
+) -> Result<(), String> {
+    let Statement {
+        mut words,
+        redirects,
+    } = statement;
+
+    let mut start = 0;
+    while let Some(word) = words.get(start).filter(|w| !w.quoted && !w.dynamic) {
+        let has_operand = words
+            .get(start + 1)
+            .is_some_and(|w| !w.value.starts_with('-'));
+        match word.value.as_str() {
+            "and" | "or" | "not" | "!" | "time" => start += 1,
+            "command" | "builtin" | "exec" if has_operand => start += 1,
+            "begin" | "if" | "while" => {
+                blocks.push(word.value.clone());
+                start += 1;
+            }
+            "else" => {
+                if blocks.last().is_none_or(|b| b != "if") {
+                    return Err("'else' outside of 'if'".to_string());
+                }
+                start += 1;
+                // `else if` continues the same block
+                if words.get(start).is_some_and(|w| w.raw == "if") {
+                    start += 1;
+                }
+            }
+            "end" => {
+                blocks.pop().ok_or("'end' outside of a block")?;
+                // `begin ...; end > log` redirects what the block wrote
+                if let Some(last) = commands.last_mut() {
+                    last.redirects.extend(redirects.into_iter().map(|(_, r)| r));
+                }
+                return Ok(());
+            }
+            // The remaining words are values (substitutions were already parsed)
+            "for" | "switch" | "function" => {
+                blocks.push(word.value.clone());
+                return Ok(());
+            }
+            "case" => {
+                if blocks.last().is_none_or(|b| b != "switch") {
+                    return Err("'case' outside of 'switch'".to_string());
+                }
+                return Ok(());
+            }
+            _ => break,
+        }
+    }
+    let mut words = words.split_off(start);
+    if words.is_empty() {
+        return Ok(());
+    }
+
+    let text = words
+        .iter()
+        .map(|w| w.raw.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // `VAR=value command` sets the variable for the command
+    let mut assignments = Vec::new();
+    while let Some(word) = words.first()
+        && !word.quoted
+        && let Some((name, value)) = word.value.split_once('=')
+        && is_variable_name(name)
+    {
+        assignments.push(Assignment {
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+        words.remove(0);
+    }
+
+    let mut stdin = None;
+    let mut file_redirects = Vec::new();
+    for (fd, redirect) in redirects {
+        if redirect.operator == "<" && fd.as_deref().is_none_or(|fd| fd == "0") {
+            stdin = Some(Stdin {
+                input: Some(Input::File(redirect.target.clone())),
+                ..Default::default()
+            });
+        }
+        file_redirects.push(redirect);
+    }
+
+    let Some(name) = words.first() else {
+        commands.push(Command {
+            text,
+            redirects: file_redirects,
+            assignments,
+            ..Default::default()
+        });
+        return Ok(());
+    };
+    let args = &words[1..];
+    let mut command = Command {
+        name: name.value.clone(),
+        args: args.iter().map(|w| w.value.clone()).collect(),
+        raw_args: args.iter().map(Word::bash).collect(),
+        text,
+        redirects: file_redirects,
+        dynamic_name: name.dynamic,
+        dynamic_args: args.iter().map(|w| w.dynamic).collect(),
+        assignments,
+        stdin,
+    };
+    if command.name == "set" {
+        command.assignments.extend(set_assignment(&command.args));
+    }
+    commands.push(command);
+    Ok(())
+}
+
+/// The variable set by `set [options] NAME VALUE...`
+fn set_assignment(args: &[String]) -> Option<Assignment> {
+    let mut args = args.iter();
+    let name = loop {
+        let arg = args.next()?;
+        match arg.as_str() {
+            "-e" | "--erase" | "-q" | "--query" | "-n" | "--names" | "-S" | "--show" => {
+                return None;
+            }
+            _ if arg.starts_with('-') => {}
+            _ => break arg,
+        }
+    };
+    // `set PATH[1] /x` sets one element
+    let name = name.split('[').next().unwrap_or(name);
+    is_variable_name(name).then(|| Assignment {
+        name: name.to_string(),
+        value: args.map(String::as_str).collect::<Vec<_>>().join(" "),
+    })
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(cmd: &str) -> Vec<String> {
+        let result = analyze(cmd);
+        assert!(result.success, "{:?}", result.error);
+        result.commands.into_iter().map(|c| c.name).collect()
+    }
+
+    #[test]
+    fn test_lists_and_pipes() {
+        assert_eq!(
+            names("ls -la; and git status | grep x && echo ok || rm y &"),
+            vec!["ls", "git", "grep", "echo", "rm"]
+        );
+        assert_eq!(names("not test -f x\nor touch x"), vec!["test", "touch"]);
+        assert_eq!(names("command rm x; command -v rm"), vec!["rm", "command"]);
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(
+            names("if test -d x; rm -r x; else if true; ls; else; pwd; end"),
+            vec!["test", "rm", "true", "ls", "pwd"]
+        );
+        assert_eq!(
+            names("for f in (find . -name '*.log'); rm $f; end"),
+            vec!["find", "rm"]
+        );
+        assert_eq!(
+            names("switch $x; case a; ls; case '*'; pwd; end"),
+            vec!["ls", "pwd"]
+        );
+        assert_eq!(names("begin; ls; end; while true; sleep 1; end").len(), 3);
+        assert_eq!(names("function f; rm -rf /; end"), vec!["rm"]);
+    }
+
+    #[test]
+    fn test_quoting() {
+        let result = analyze(r#"rm 'a b' "c $d" \e\ f 'it\'s' "x\"y" \x72m"#);
+        let rm = &result.commands[0];
+        assert_eq!(rm.args, vec!["a b", "c $d", "\x1b f", "it's", "x\"y", "rm"]);
+        assert_eq!(rm.raw_args[0], "'a b'");
+        assert_eq!(rm.raw_args[1], r#""c $d""#);
+
+        let result = analyze("r''m *.log ~/x {a,b}");
+        let rm = &result.commands[0];
+        assert_eq!(rm.name, "rm");
+        assert_eq!(rm.raw_args, vec!["*.log", "~/x", "{a,b}"]);
+    }
+
+    #[test]
+    fn test_substitutions() {
+        assert_eq!(
+            names("echo (rm -rf x) \"$(mkfs /dev/sda)\" \"(not run)\""),
+            vec!["rm", "mkfs", "echo"]
+        );
+        let result = analyze("(which rm) -rf x");
+        assert!(result.commands.last().unwrap().dynamic_name);
+        assert!(!analyze(&format!("{}ls{}", "(".repeat(40), ")".repeat(40))).success);
+    }
+
+    #[test]
+    fn test_redirects_and_assignments() {
+        let result = analyze("echo x > out.txt 2>&1; cat < in.txt; ls &>> log");
+        let redirects: Vec<_> = result.commands.iter().map(|c| &c.redirects).collect();
+        assert_eq!(redirects[0][0].operator, ">");
+        assert_eq!(redirects[0][0].target, "out.txt");
+        assert!(redirects[0][1].is_fd_duplication());
+        assert_eq!(
+            result.commands[1].input(),
+            Some(&Input::File("in.txt".to_string()))
+        );
+        assert_eq!(redirects[2][0].operator, "&>>");
+
+        let result = analyze("set -gx LD_PRELOAD /tmp/x.so; PATH=/tmp ls");
+        assert_eq!(result.commands[0].assignments[0].name, "LD_PRELOAD");
+        assert_eq!(result.commands[1].name, "ls");
+        assert_eq!(result.commands[1].assignments[0].name, "PATH");
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        for source in [
+            "echo 'x",
+            "if true; ls",
+            "end",
+            "ls )",
+            "echo (ls",
+            "echo >",
+        ] {
+            assert!(!analyze(source).success, "{}", source);
+        }
+    }
+}
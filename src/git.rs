@@ -1,20 +1,29 @@
 //! Git command special handling
 
 use crate::analyzer::Command;
-use crate::config::{Permission, PermissionResult};
+use crate::config::{Config, Permission, PermissionResult};
+use crate::expand;
+use glob_match::glob_match;
 use std::process::Command as ProcessCommand;
 
-/// Protected branch names
-const PROTECTED_BRANCHES: &[&str] = &["master", "main"];
+/// Whether a git command goes to one of the handlers here: `push`,
+/// `checkout` or a destructive command ([`check_git_destructive`])
+pub fn is_handled(cmd: &Command) -> bool {
+    subcommand(cmd).is_some_and(|(subcommand, first_arg)| {
+        matches!(subcommand, "push" | "checkout")
+            || Destruction::parse(subcommand, &cmd.args[first_arg..]).is_some()
+    })
+}
 
 /// Check if a git checkout should be allowed
 pub fn check_git_checkout(cmd: &Command) -> Option<PermissionResult> {
     // Only handle git checkout
-    if cmd.name != "git" || cmd.args.first().map(|s| s.as_str()) != Some("checkout") {
+    let (subcommand, first_arg) = subcommand(cmd)?;
+    if subcommand != "checkout" {
         return None;
     }
 
-    let args: Vec<&str> = cmd.args.iter().skip(1).map(|s| s.as_str()).collect();
+    let args: Vec<&str> = cmd.args[first_arg..].iter().map(|s| s.as_str()).collect();
 
     // git checkout -b <branch> - creating a branch is safe
     if args
@@ -39,103 +48,1378 @@ pub fn check_git_checkout(cmd: &Command) -> Option<PermissionResult> {
     })
 }
 
-/// Check if a git push should be allowed
-pub fn check_git_push(cmd: &Command) -> Option<PermissionResult> {
-    // Only handle git push
-    if cmd.name != "git" || cmd.args.first().map(|s| s.as_str()) != Some("push") {
-        return None;
+/// Check a git command that destroys local state (`reset --hard`, `clean -f`,
+/// `branch -D`, `stash drop`, ...) by what it would lose in the repository it
+/// runs in: nothing is allowed, anything else gets the `recoverable` or
+/// `unrecoverable` permission from `[git.permissions]`
+/// Pushes that delete remote refs are checked by [`check_git_push`]
+pub fn check_git_destructive(
+    cmd: &Command,
+    cwd: Option<&str>,
+    assigned: &[String],
+    config: &Config,
+) -> Option<PermissionResult> {
+    let (subcommand, first_arg) = subcommand(cmd)?;
+    let destruction = Destruction::parse(subcommand, &cmd.args[first_arg..])?;
+
+    let dir = command_dir(cmd, first_arg, cwd, assigned);
+    let permissions = config.git_policy(dir.as_deref()).permissions;
+    let repo = dir.and_then(|dir| Repo::open(cmd, first_arg, dir, assigned));
+    let permission = |p: &str| Permission::parse(p).unwrap_or(Permission::Ask);
+    let (permission, reason) = match destruction.loss(repo.as_ref()) {
+        Loss::Nothing(reason) => (Permission::Allow, reason),
+        Loss::Recoverable(reason) => (permission(&permissions.recoverable), reason),
+        Loss::Unrecoverable(reason) => (permission(&permissions.unrecoverable), reason),
+    };
+    Some(PermissionResult {
+        permission,
+        reason,
+        suggestion: None,
+    })
+}
+
+/// A git command that destroys local state
+#[derive(Debug, PartialEq)]
+enum Destruction {
+    /// `reset --hard [commit]`
+    ResetHard(Option<String>),
+    /// `clean`, with the `clean --dry-run ...` arguments listing what it deletes
+    /// (None for options that aren't understood)
+    Clean(Option<Vec<String>>),
+    /// `restore` of the working tree, from `source` or the index (HEAD with `--staged`)
+    Restore {
+        source: Option<String>,
+        staged: bool,
+        paths: Vec<String>,
+    },
+    /// `rm -f`, which removes files with uncommitted changes
+    ForceRemove(Vec<String>),
+    /// `switch --discard-changes`, `checkout -f`: the command as written in reasons
+    ForceCheckout(&'static str),
+    /// `branch -D`, `branch -d --force`
+    DeleteBranches(Vec<String>),
+    /// `branch -M`, `branch -C`, `branch -m --force`: the command as written in
+    /// reasons, and its branch arguments (`[old] new`)
+    OverwriteBranch(&'static str, Vec<String>),
+    /// `stash drop [stash]`
+    DropStash(String),
+    /// `stash clear`
+    ClearStash,
+    /// `tag -d`
+    DeleteTags(Vec<String>),
+    /// `update-ref -d`
+    DeleteRef(String),
+    /// `reflog delete`, `reflog expire --expire...`
+    ExpireReflog,
+    /// `gc --prune=now`, `prune`
+    Prune,
+    /// `filter-branch`; `--force` replaces the backup of an earlier rewrite
+    FilterBranch { force: bool },
+}
+
+/// What a destructive git command would lose
+#[derive(Debug, PartialEq)]
+enum Loss {
+    /// Nothing that isn't kept elsewhere
+    Nothing(String),
+    /// Commits or objects that stay reachable through the reflog or by hash
+    /// until they expire and are pruned
+    Recoverable(String),
+    /// Uncommitted changes, untracked files, or the means of recovery itself
+    Unrecoverable(String),
+}
+
+impl Loss {
+    fn rank(&self) -> u8 {
+        match self {
+            Loss::Nothing(_) => 0,
+            Loss::Recoverable(_) => 1,
+            Loss::Unrecoverable(_) => 2,
+        }
     }
+}
 
-    // Check for dangerous force push (not --force-with-lease which is safer)
-    let has_dangerous_force = cmd.args.iter().any(|a| a == "-f" || a == "--force");
-    let has_force_with_lease = cmd.args.iter().any(|a| a == "--force-with-lease");
+impl Destruction {
+    /// The destruction a git subcommand does, None if it only reads or can't lose work
+    fn parse(subcommand: &str, args: &[String]) -> Option<Self> {
+        let flag = |short, long: &[&str]| has_option(args, short, long);
+        let first = args.first().map(String::as_str);
+        match subcommand {
+            "reset" if flag(None, &["hard"]) => Some(Destruction::ResetHard(
+                positional(args, &[]).first().cloned(),
+            )),
+            "clean" => match clean_listing(args) {
+                CleanArgs::DryRun => None,
+                CleanArgs::Listing(listing) => Some(Destruction::Clean(Some(listing))),
+                CleanArgs::Unknown => Some(Destruction::Clean(None)),
+            },
+            "restore" => {
+                let staged = flag(Some('S'), &["staged"]);
+                if staged && !flag(Some('W'), &["worktree"]) {
+                    return None;
+                }
+                let source = args
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, arg)| match arg.as_str() {
+                        "-s" | "--source" => args.get(i + 1).cloned(),
+                        _ => arg.strip_prefix("--source=").map(String::from),
+                    });
+                Some(Destruction::Restore {
+                    source,
+                    staged,
+                    paths: positional(args, &["-s", "--source"]),
+                })
+            }
+            "switch" if flag(Some('f'), &["force", "discard-changes"]) => {
+                Some(Destruction::ForceCheckout("git switch --discard-changes"))
+            }
+            "checkout" if flag(Some('f'), &["force"]) => {
+                Some(Destruction::ForceCheckout("git checkout -f"))
+            }
+            "rm" if flag(Some('f'), &["force"])
+                && !flag(None, &["cached"])
+                && !flag(Some('n'), &["dry-run"]) =>
+            {
+                Some(Destruction::ForceRemove(positional(args, &[])))
+            }
+            "branch"
+                if !flag(Some('r'), &["remotes"])
+                    && (flag(Some('D'), &[])
+                        || (flag(Some('d'), &["delete"]) && flag(Some('f'), &["force"]))) =>
+            {
+                Some(Destruction::DeleteBranches(positional(args, &[])))
+            }
+            "branch" if flag(Some('M'), &[]) => Some(Destruction::OverwriteBranch(
+                "git branch -M",
+                positional(args, &[]),
+            )),
+            "branch" if flag(Some('C'), &[]) => Some(Destruction::OverwriteBranch(
+                "git branch -C",
+                positional(args, &[]),
+            )),
+            "branch" if flag(Some('f'), &["force"]) && flag(Some('m'), &["move"]) => Some(
+                Destruction::OverwriteBranch("git branch -m --force", positional(args, &[])),
+            ),
+            "branch" if flag(Some('f'), &["force"]) && flag(Some('c'), &["copy"]) => Some(
+                Destruction::OverwriteBranch("git branch -c --force", positional(args, &[])),
+            ),
+            "stash" => match first? {
+                "drop" => {
+                    // `git stash drop 2` is stash@{2}
+                    let stash = match positional(&args[1..], &[]).into_iter().next() {
+                        Some(n) if n.chars().all(|c| c.is_ascii_digit()) => {
+                            format!("stash@{{{}}}", n)
+                        }
+                        Some(stash) => stash,
+                        None => "stash@{0}".to_string(),
+                    };
+                    Some(Destruction::DropStash(stash))
+                }
+                "clear" => Some(Destruction::ClearStash),
+                _ => None,
+            },
+            "tag" if flag(Some('d'), &["delete"]) => {
+                Some(Destruction::DeleteTags(positional(args, &[])))
+            }
+            "update-ref" if flag(Some('d'), &[]) => Some(Destruction::DeleteRef(
+                positional(args, &[]).first()?.clone(),
+            )),
+            "reflog" => match first? {
+                "delete" => Some(Destruction::ExpireReflog),
+                // Without an expiry time, only entries older than gc's would go
+                "expire" if flag(None, &["expire", "expire-unreachable"]) => {
+                    Some(Destruction::ExpireReflog)
+                }
+                _ => None,
+            },
+            "gc" if args
+                .iter()
+                .any(|a| matches!(a.as_str(), "--prune=now" | "--prune=all")) =>
+            {
+                Some(Destruction::Prune)
+            }
+            // Without --expire, every unreachable object goes
+            "prune" if !flag(Some('n'), &["dry-run"]) => Some(Destruction::Prune),
+            "filter-branch" => Some(Destruction::FilterBranch {
+                force: flag(Some('f'), &["force"]),
+            }),
+            _ => None,
+        }
+    }
 
-    if has_dangerous_force {
-        return Some(PermissionResult {
-            permission: Permission::Ask,
-            reason: "force push".to_string(),
-            suggestion: Some("Consider using --force-with-lease for safer force push".to_string()),
-        });
+    /// What the command would lose in `repo` (None when it can't be read)
+    fn loss(&self, repo: Option<&Repo>) -> Loss {
+        let unknown =
+            |what: &str| Loss::Unrecoverable(format!("{} (can't inspect the repository)", what));
+        match self {
+            Destruction::ResetHard(target) => {
+                let Some(repo) = repo else {
+                    return unknown("git reset --hard discards uncommitted changes");
+                };
+                let changed = uncommitted_files(repo);
+                if !changed.is_empty() {
+                    return Loss::Unrecoverable(format!(
+                        "git reset --hard discards uncommitted changes to {}",
+                        summarize("file", &changed)
+                    ));
+                }
+                let Some(target) = target else {
+                    return Loss::Nothing("git reset --hard: no uncommitted changes".to_string());
+                };
+                match repo.inspect(&["rev-list", "--count", &format!("{}..HEAD", target)]) {
+                    Some(count) if count.trim() == "0" => Loss::Nothing(format!(
+                        "git reset --hard {}: no uncommitted changes or commits left behind",
+                        target
+                    )),
+                    Some(count) => Loss::Recoverable(format!(
+                        "git reset --hard {} leaves {} behind (recoverable from the reflog)",
+                        target,
+                        commits(count.trim())
+                    )),
+                    None => Loss::Recoverable(format!(
+                        "git reset --hard {} may leave commits behind",
+                        target
+                    )),
+                }
+            }
+            Destruction::Clean(listing) => {
+                let (Some(repo), Some(listing)) = (repo, listing) else {
+                    return unknown("git clean deletes untracked files");
+                };
+                let listing: Vec<&str> = listing.iter().map(String::as_str).collect();
+                let Some(output) = repo.inspect(&listing) else {
+                    return Loss::Unrecoverable(
+                        "git clean: can't list the files it would delete".to_string(),
+                    );
+                };
+                let removed: Vec<String> = output
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("Would remove "))
+                    .map(String::from)
+                    .collect();
+                if removed.is_empty() {
+                    Loss::Nothing("git clean: nothing to delete".to_string())
+                } else {
+                    Loss::Unrecoverable(format!(
+                        "git clean deletes {}",
+                        summarize("untracked file", &removed)
+                    ))
+                }
+            }
+            Destruction::ForceCheckout(command) => {
+                let Some(repo) = repo else {
+                    return unknown(&format!("{} discards uncommitted changes", command));
+                };
+                let changed = uncommitted_files(repo);
+                if changed.is_empty() {
+                    Loss::Nothing(format!("{}: no uncommitted changes", command))
+                } else {
+                    Loss::Unrecoverable(format!(
+                        "{} discards uncommitted changes to {}",
+                        command,
+                        summarize("file", &changed)
+                    ))
+                }
+            }
+            Destruction::Restore {
+                source,
+                staged,
+                paths,
+            } => {
+                let Some(repo) = repo else {
+                    return unknown("git restore discards uncommitted changes");
+                };
+                // From the index, only unstaged changes go; otherwise staged ones too
+                let base = match source {
+                    Some(_) => Some("HEAD"),
+                    None if *staged => Some("HEAD"),
+                    None => None,
+                };
+                let changed = diff_files(repo, base, paths);
+                if changed.is_empty() {
+                    Loss::Nothing("git restore: no uncommitted changes to discard".to_string())
+                } else {
+                    Loss::Unrecoverable(format!(
+                        "git restore discards uncommitted changes to {}",
+                        summarize("file", &changed)
+                    ))
+                }
+            }
+            Destruction::ForceRemove(paths) => {
+                let Some(repo) = repo else {
+                    return unknown("git rm -f removes files with uncommitted changes");
+                };
+                let changed = diff_files(repo, Some("HEAD"), paths);
+                if changed.is_empty() {
+                    Loss::Nothing("git rm -f: the files match the last commit".to_string())
+                } else {
+                    Loss::Unrecoverable(format!(
+                        "git rm -f discards uncommitted changes to {}",
+                        summarize("file", &changed)
+                    ))
+                }
+            }
+            Destruction::DeleteBranches(branches) => {
+                let Some(repo) = repo else {
+                    return Loss::Recoverable(format!(
+                        "git branch -D deletes {} (can't inspect the repository)",
+                        quoted(branches)
+                    ));
+                };
+                let losses = branches.iter().map(|branch| {
+                    match unmerged_commits(repo, branch).as_deref() {
+                        None => Loss::Nothing(format!("no branch '{}'", branch)),
+                        Some("0") => Loss::Nothing(format!("'{}' is merged", branch)),
+                        Some(count) => Loss::Recoverable(format!(
+                            "'{}' has {} on no other branch",
+                            branch,
+                            commits(count)
+                        )),
+                    }
+                });
+                combine(
+                    "git branch -D",
+                    losses,
+                    "(recoverable by hash until gc prunes them)",
+                )
+            }
+            Destruction::OverwriteBranch(command, names) => {
+                let Some(new) = names.last() else {
+                    return Loss::Nothing(format!("{}: no branch named", command));
+                };
+                let Some(repo) = repo else {
+                    return Loss::Recoverable(format!(
+                        "{} may overwrite '{}' (can't inspect the repository)",
+                        command, new
+                    ));
+                };
+                // Renaming or copying a branch onto itself replaces nothing
+                let old = match names.as_slice() {
+                    [old, _] => Some(old),
+                    _ => repo.head.as_ref(),
+                };
+                if old == Some(new) {
+                    return Loss::Nothing(format!("{}: '{}' is the branch itself", command, new));
+                }
+                match unmerged_commits(repo, new).as_deref() {
+                    None => Loss::Nothing(format!("{}: no branch '{}' to overwrite", command, new)),
+                    Some("0") => Loss::Nothing(format!("{}: '{}' is merged", command, new)),
+                    Some(count) => Loss::Recoverable(format!(
+                        "{} overwrites '{}', which has {} on no other branch (recoverable by hash until gc prunes them)",
+                        command,
+                        new,
+                        commits(count)
+                    )),
+                }
+            }
+            Destruction::DropStash(stash) => {
+                let subject = repo.and_then(|repo| {
+                    repo.inspect(&["log", "-1", "--format=%gs", "-g", stash])
+                        .or_else(|| repo.inspect(&["log", "-1", "--format=%s", stash]))
+                });
+                match (repo, subject) {
+                    (Some(_), None) => Loss::Nothing(format!("git stash drop: no {}", stash)),
+                    (_, subject) => Loss::Recoverable(format!(
+                        "git stash drop drops {}{} (recoverable by hash until gc prunes it)",
+                        stash,
+                        subject
+                            .map(|s| format!(" \"{}\"", s.trim()))
+                            .unwrap_or_default()
+                    )),
+                }
+            }
+            Destruction::ClearStash => {
+                let stashes = repo.and_then(|repo| repo.inspect(&["stash", "list"]));
+                match stashes.as_deref().map(str::lines).map(Iterator::count) {
+                    Some(0) => Loss::Nothing("git stash clear: no stashes".to_string()),
+                    Some(count) => Loss::Recoverable(format!(
+                        "git stash clear drops {} stashes (recoverable by hash until gc prunes them)",
+                        count
+                    )),
+                    None => Loss::Recoverable(
+                        "git stash clear drops every stash (can't inspect the repository)"
+                            .to_string(),
+                    ),
+                }
+            }
+            Destruction::DeleteTags(tags) => {
+                let targets: Vec<String> = tags
+                    .iter()
+                    .map(|tag| {
+                        let commit = repo.and_then(|repo| {
+                            repo.inspect(&[
+                                "rev-parse",
+                                "--short",
+                                &format!("refs/tags/{}^{{}}", tag),
+                            ])
+                        });
+                        match commit {
+                            Some(commit) => format!("'{}' ({})", tag, commit.trim()),
+                            None => format!("'{}'", tag),
+                        }
+                    })
+                    .collect();
+                Loss::Recoverable(format!(
+                    "git tag -d deletes {} (recreate from the commit)",
+                    targets.join(", ")
+                ))
+            }
+            Destruction::DeleteRef(name) => {
+                let target = repo.and_then(|repo| repo.inspect(&["rev-parse", "--short", name]));
+                Loss::Recoverable(match target {
+                    Some(target) => format!(
+                        "git update-ref -d deletes {} ({}; recreate from the commit)",
+                        name,
+                        target.trim()
+                    ),
+                    None => format!("git update-ref -d deletes {}", name),
+                })
+            }
+            Destruction::ExpireReflog => Loss::Unrecoverable(
+                "git reflog drops entries, which are how reset and deleted commits are recovered"
+                    .to_string(),
+            ),
+            Destruction::Prune => Loss::Unrecoverable(
+                "prunes unreachable objects now: dropped stashes and commits of deleted branches or resets are gone for good"
+                    .to_string(),
+            ),
+            Destruction::FilterBranch { force } => {
+                let backup = repo.and_then(|repo| {
+                    repo.inspect(&["for-each-ref", "--count=1", "refs/original"])
+                });
+                match backup {
+                    Some(refs) if *force && !refs.is_empty() => Loss::Unrecoverable(
+                        "git filter-branch -f overwrites the refs/original backup of an earlier rewrite"
+                            .to_string(),
+                    ),
+                    _ => Loss::Recoverable(
+                        "git filter-branch rewrites history (the originals are kept under refs/original)"
+                            .to_string(),
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// Whether `args` (before any `--`) contain a short option, alone or in a
+/// cluster (`-fdx`), or one of the long options
+fn has_option(args: &[String], short: Option<char>, long: &[&str]) -> bool {
+    args.iter().take_while(|a| *a != "--").any(|arg| {
+        if let Some(name) = arg.strip_prefix("--") {
+            long.contains(&name.split('=').next().unwrap_or(name))
+        } else if let Some(cluster) = arg.strip_prefix('-') {
+            short.is_some_and(|c| cluster.contains(c))
+        } else {
+            false
+        }
+    })
+}
+
+/// What a `git clean` command line does
+enum CleanArgs {
+    /// `-n`/`--dry-run`: only lists
+    DryRun,
+    /// Deletes files; `clean --dry-run` arguments that list them, with the
+    /// user's paths after `--` so none can be taken as an option
+    Listing(Vec<String>),
+    /// An option that isn't understood
+    Unknown,
+}
+
+fn clean_listing(args: &[String]) -> CleanArgs {
+    let mut options = vec!["clean".to_string(), "--dry-run".to_string()];
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            paths.extend(args.cloned());
+            break;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            match long.split_once('=') {
+                Some(("exclude", pattern)) => options.push(format!("--exclude={}", pattern)),
+                None if long == "exclude" => match args.next() {
+                    Some(pattern) => options.push(format!("--exclude={}", pattern)),
+                    None => return CleanArgs::Unknown,
+                },
+                None if long == "dry-run" => return CleanArgs::DryRun,
+                None if long == "force" => options.push("-f".to_string()),
+                None if matches!(long, "quiet" | "interactive") => {}
+                _ => return CleanArgs::Unknown,
+            }
+        } else if let Some(cluster) = arg.strip_prefix('-').filter(|c| !c.is_empty()) {
+            for (i, c) in cluster.char_indices() {
+                match c {
+                    'n' => return CleanArgs::DryRun,
+                    'd' | 'f' | 'x' | 'X' => options.push(format!("-{}", c)),
+                    'q' | 'i' => {}
+                    // `-e pattern` or `-epattern`
+                    'e' => {
+                        let pattern = match &cluster[i + 1..] {
+                            "" => match args.next() {
+                                Some(pattern) => pattern.as_str(),
+                                None => return CleanArgs::Unknown,
+                            },
+                            attached => attached,
+                        };
+                        options.push(format!("--exclude={}", pattern));
+                        break;
+                    }
+                    _ => return CleanArgs::Unknown,
+                }
+            }
+        } else {
+            paths.push(arg.clone());
+        }
     }
+    options.push("--".to_string());
+    options.extend(paths);
+    CleanArgs::Listing(options)
+}
 
-    // Try to determine the target branch
-    let target_branch = get_push_target_branch(cmd);
+/// Arguments that aren't options, skipping the values of `with_value` options
+fn positional(args: &[String], with_value: &[&str]) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            result.extend(args.cloned());
+            break;
+        }
+        if with_value.contains(&arg.as_str()) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            result.push(arg.clone());
+        }
+    }
+    result
+}
+
+/// How many commits of `branch` are on no other branch, tag or remote-tracking
+/// branch, None when there's no such branch
+fn unmerged_commits(repo: &Repo, branch: &str) -> Option<String> {
+    repo.inspect(&[
+        "rev-list",
+        "--count",
+        &format!("refs/heads/{}", branch),
+        "--not",
+        &format!("--exclude=refs/heads/{}", branch),
+        "--all",
+    ])
+    .map(|count| count.trim().to_string())
+}
+
+/// Tracked files with uncommitted changes, staged or not
+fn uncommitted_files(repo: &Repo) -> Vec<String> {
+    repo.inspect(&["status", "--porcelain", "-uno", "--ignore-submodules"])
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.get(3..))
+        .map(String::from)
+        .collect()
+}
+
+/// Files whose working tree differs from `base` (the index when None) under `paths`
+fn diff_files(repo: &Repo, base: Option<&str>, paths: &[String]) -> Vec<String> {
+    let mut args = vec![
+        "diff",
+        "--name-only",
+        "--no-renames",
+        "--no-ext-diff",
+        "--no-textconv",
+        "--ignore-submodules",
+    ];
+    args.extend(base);
+    args.push("--");
+    args.extend(paths.iter().map(String::as_str));
+    repo.inspect(&args)
+        .unwrap_or_default()
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+/// `3 files: a, b, c`, listing at most five
+fn summarize(noun: &str, files: &[String]) -> String {
+    let mut list = files.iter().take(5).cloned().collect::<Vec<_>>().join(", ");
+    if files.len() > 5 {
+        list.push_str(", ...");
+    }
+    let plural = if files.len() == 1 { "" } else { "s" };
+    format!("{} {}{}: {}", files.len(), noun, plural, list)
+}
+
+/// `1 commit`, `3 commits`
+fn commits(count: &str) -> String {
+    let plural = if count == "1" { "" } else { "s" };
+    format!("{} commit{}", count, plural)
+}
+
+/// `'a', 'b'`
+fn quoted(names: &[String]) -> String {
+    let names: Vec<String> = names.iter().map(|n| format!("'{}'", n)).collect();
+    names.join(", ")
+}
+
+/// The worst of several losses, with every loss described
+fn combine(command: &str, losses: impl Iterator<Item = Loss>, recovery: &str) -> Loss {
+    let losses: Vec<Loss> = losses.collect();
+    let worst = losses.iter().map(Loss::rank).max().unwrap_or(0);
+    let details: Vec<&str> = losses
+        .iter()
+        .filter(|loss| loss.rank() == worst)
+        .map(|loss| match loss {
+            Loss::Nothing(s) | Loss::Recoverable(s) | Loss::Unrecoverable(s) => s.as_str(),
+        })
+        .collect();
+    let reason = format!("{}: {}", command, details.join(", "));
+    match worst {
+        0 => Loss::Nothing(reason),
+        1 => Loss::Recoverable(format!("{} {}", reason, recovery)),
+        _ => Loss::Unrecoverable(reason),
+    }
+}
+
+/// Long options of `git push`, which git accepts abbreviated to any unique prefix
+/// Options taking a separate argument are marked with a trailing `=`
+const PUSH_LONG_OPTIONS: &[&str] = &[
+    "all",
+    "branches",
+    "mirror",
+    "delete",
+    "tags",
+    "dry-run",
+    "porcelain",
+    "force",
+    "force-with-lease",
+    "force-if-includes",
+    "repo=",
+    "set-upstream",
+    "thin",
+    "receive-pack=",
+    "exec=",
+    "quiet",
+    "verbose",
+    "progress",
+    "prune",
+    "recurse-submodules=",
+    "verify",
+    "follow-tags",
+    "signed",
+    "atomic",
+    "push-option=",
+    "ipv4",
+    "ipv6",
+];
+
+/// How a push changes a remote ref
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RefChange {
+    /// Fast-forward only
+    Update,
+    /// Overwritten even if it isn't a fast-forward, guarded by `--force-with-lease`
+    LeasedForce,
+    /// Overwritten even if it isn't a fast-forward
+    Force,
+    Delete,
+}
 
-    if let Some(branch) = &target_branch {
-        if PROTECTED_BRANCHES.contains(&branch.as_str()) {
-            let reason = if has_force_with_lease {
-                format!("force push to protected branch '{}'", branch)
+/// A remote ref a push changes
+#[derive(Debug, Clone, PartialEq)]
+struct RefUpdate {
+    /// Destination ref as written, `refs/heads/` stripped (may be a pattern, `*`)
+    dst: String,
+    change: RefChange,
+}
+
+/// Options of a `git push` that decide which refs change, and how
+#[derive(Debug, Default)]
+struct PushOptions {
+    force: bool,
+    force_with_lease: bool,
+    delete: bool,
+    all: bool,
+    mirror: bool,
+    tags: bool,
+    prune: bool,
+    dry_run: bool,
+    /// `--repo=<remote>`
+    repo: Option<String>,
+    /// Repository and refspecs
+    positional: Vec<String>,
+}
+
+impl PushOptions {
+    /// Parse the arguments after `push`; Err holds an ambiguous or unknown option
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = PushOptions::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                options.positional.extend(args.by_ref().cloned());
+            } else if let Some(long) = arg.strip_prefix("--") {
+                let (name, value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (long, None),
+                };
+                let (negated, name) = match name.strip_prefix("no-") {
+                    Some(name) => (true, name),
+                    None => (false, name),
+                };
+                let option = resolve_long_option(name)?;
+                if option.ends_with('=') && value.is_none() {
+                    let value = args.next().cloned();
+                    if option == "repo=" {
+                        options.repo = value;
+                    }
+                    continue;
+                }
+                options.set(option.trim_end_matches('='), !negated, value);
+            } else if let Some(short) = arg.strip_prefix('-').filter(|s| !s.is_empty()) {
+                for (i, flag) in short.char_indices() {
+                    match flag {
+                        'f' => options.force = true,
+                        'd' => options.delete = true,
+                        'n' => options.dry_run = true,
+                        // `-o <option>`, or the rest of the word
+                        'o' => {
+                            if i + 1 == short.len() {
+                                args.next();
+                            }
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
             } else {
-                format!("push to protected branch '{}'", branch)
-            };
+                options.positional.push(arg.clone());
+            }
+        }
+        Ok(options)
+    }
+
+    fn set(&mut self, option: &str, on: bool, value: Option<&str>) {
+        match option {
+            "force" => self.force = on,
+            "force-with-lease" => self.force_with_lease = on,
+            "delete" => self.delete = on,
+            "all" | "branches" => self.all = on,
+            "mirror" => self.mirror = on,
+            "tags" => self.tags = on,
+            "prune" => self.prune = on,
+            "dry-run" => self.dry_run = on,
+            "repo" => self.repo = value.map(String::from),
+            _ => {}
+        }
+    }
+
+    /// How refs given without `+` change
+    fn default_change(&self) -> RefChange {
+        if self.force {
+            RefChange::Force
+        } else if self.force_with_lease {
+            RefChange::LeasedForce
+        } else {
+            RefChange::Update
+        }
+    }
+}
+
+/// The option a long option name (possibly abbreviated, without `no-`) stands for
+fn resolve_long_option(name: &str) -> Result<&'static str, String> {
+    if let Some(exact) = PUSH_LONG_OPTIONS
+        .iter()
+        .find(|o| o.trim_end_matches('=') == name)
+    {
+        return Ok(exact);
+    }
+    let candidates: Vec<&str> = PUSH_LONG_OPTIONS
+        .iter()
+        .copied()
+        .filter(|o| !name.is_empty() && o.starts_with(name))
+        .collect();
+    match candidates.as_slice() {
+        [option] => Ok(option),
+        [] => Err(format!("unknown option --{}", name)),
+        _ => Err(format!("ambiguous option --{}", name)),
+    }
+}
+
+/// Check if a git push should be allowed, under the `[git]` settings for the
+/// directory it runs in
+/// `cwd` is the command's directory, used to read the repository's branch,
+/// remote and push configuration; `assigned` the variables the command line sets
+pub fn check_git_push(
+    cmd: &Command,
+    cwd: Option<&str>,
+    assigned: &[String],
+    config: &Config,
+) -> Option<PermissionResult> {
+    let (subcommand, first_arg) = subcommand(cmd)?;
+    if subcommand != "push" {
+        return None;
+    }
+
+    let options = match PushOptions::parse(&cmd.args[first_arg..]) {
+        Ok(options) => options,
+        Err(reason) => {
             return Some(PermissionResult {
                 permission: Permission::Ask,
                 reason,
                 suggestion: None,
             });
         }
+    };
+    if options.dry_run {
+        return Some(PermissionResult {
+            permission: Permission::Allow,
+            reason: "git push --dry-run".to_string(),
+            suggestion: None,
+        });
     }
 
-    // Allow push (including --force-with-lease) to non-protected branches
-    Some(PermissionResult {
+    let dir = command_dir(cmd, first_arg, cwd, assigned);
+    let policy = config.git_policy(dir.as_deref());
+    let repo = dir.and_then(|dir| Repo::open(cmd, first_arg, dir, assigned));
+    let remote = options
+        .positional
+        .first()
+        .or(options.repo.as_ref())
+        .cloned()
+        .or_else(|| repo.as_ref().map(Repo::default_push_remote));
+
+    // Remote rules decide every push to the remote
+    if let Some(url) = remote.as_deref().and_then(|r| remote_url(r, repo.as_ref()))
+        && let Some(rule) = policy.remotes.iter().find(|r| glob_match(&r.url, &url))
+    {
+        return Some(PermissionResult {
+            permission: Permission::parse(&rule.permission).unwrap_or(Permission::Ask),
+            reason: format!("push to {} (git remote rule \"{}\")", url, rule.url),
+            suggestion: None,
+        });
+    }
+
+    let permissions = policy.permissions;
+    let permission = |p: &str| Permission::parse(p).unwrap_or(Permission::Ask);
+    let lease_suggestion =
+        || Some("Consider using --force-with-lease for safer force push".to_string());
+    let mut results = Vec::new();
+
+    // Check for dangerous force push (not --force-with-lease which is safer)
+    if options.force {
+        results.push(PermissionResult {
+            permission: permission(&permissions.force),
+            reason: "force push".to_string(),
+            suggestion: lease_suggestion(),
+        });
+    }
+    let mirror = options.mirror
+        || remote
+            .as_ref()
+            .zip(repo.as_ref())
+            .is_some_and(|(remote, repo)| {
+                repo.get(&format!("remote.{}.mirror", remote)) == Some("true")
+            });
+    if mirror {
+        results.push(PermissionResult {
+            permission: permission(&permissions.mirror),
+            reason: "mirror push overwrites and deletes remote refs".to_string(),
+            suggestion: None,
+        });
+    }
+    if options.prune {
+        results.push(PermissionResult {
+            permission: permission(&permissions.prune),
+            reason: "push --prune deletes remote branches".to_string(),
+            suggestion: None,
+        });
+    }
+
+    if !mirror {
+        let updates = match push_updates(&options, remote.as_deref(), repo.as_ref()) {
+            Ok(updates) => updates,
+            Err(reason) => {
+                results.push(PermissionResult {
+                    permission: Permission::Ask,
+                    reason: format!("can't resolve push target: {}", reason),
+                    suggestion: None,
+                });
+                Vec::new()
+            }
+        };
+        for update in updates {
+            let dst = &update.dst;
+            let (kind, reason, suggestion) = match update.change {
+                RefChange::Update => (&permissions.push, "git push".to_string(), None),
+                RefChange::LeasedForce => (
+                    &permissions.force_with_lease,
+                    format!("force push to '{}'", dst),
+                    None,
+                ),
+                RefChange::Force => (
+                    &permissions.force,
+                    format!("force push to '{}'", dst),
+                    lease_suggestion(),
+                ),
+                RefChange::Delete => (
+                    &permissions.delete,
+                    format!("delete remote ref '{}'", dst),
+                    None,
+                ),
+            };
+            let mut result = PermissionResult {
+                permission: permission(kind),
+                reason,
+                suggestion,
+            };
+            if is_protected(dst, policy.protected_branches)
+                && permission(&permissions.protected) > result.permission
+            {
+                result = PermissionResult {
+                    permission: permission(&permissions.protected),
+                    reason: match update.change {
+                        RefChange::Update => format!("push to protected branch '{}'", dst),
+                        RefChange::Delete => format!("delete protected branch '{}'", dst),
+                        _ => format!("force push to protected branch '{}'", dst),
+                    },
+                    suggestion: None,
+                };
+            }
+            results.push(result);
+        }
+    }
+
+    // The most restrictive kind of change decides; a push of nothing is allowed
+    let mut decision = PermissionResult {
         permission: Permission::Allow,
         reason: "git push".to_string(),
         suggestion: None,
-    })
+    };
+    for (i, result) in results.into_iter().enumerate() {
+        if i == 0 || result.permission > decision.permission {
+            decision = result;
+        }
+    }
+    Some(decision)
+}
+
+/// Whether a destination (a branch name or pattern) may update a protected branch
+fn is_protected(dst: &str, protected: &[String]) -> bool {
+    protected
+        .iter()
+        .any(|pattern| glob_match(pattern, dst) || (dst.contains('*') && glob_match(dst, pattern)))
 }
 
-/// Try to determine the target branch for a git push
-fn get_push_target_branch(cmd: &Command) -> Option<String> {
-    let args: Vec<&str> = cmd.args.iter().skip(1).map(|s| s.as_str()).collect();
+/// The URL a remote pushes to, as `host:path` without `.git`
+/// `remote` is a configured remote name, or a URL or path given directly
+fn remote_url(remote: &str, repo: Option<&Repo>) -> Option<String> {
+    let url = if remote.contains([':', '/']) {
+        remote.to_string()
+    } else {
+        let repo = repo?;
+        repo.get(&format!("remote.{}.pushurl", remote))
+            .or_else(|| repo.get(&format!("remote.{}.url", remote)))?
+            .to_string()
+    };
+    Some(normalize_url(&url))
+}
 
-    // Skip flags and their arguments
-    let mut positional: Vec<&str> = Vec::new();
-    let mut skip_next = false;
+/// `https://user@host/path.git`, `ssh://host:22/path` and `user@host:path` as `host:path`
+fn normalize_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let Some((_, rest)) = url.split_once("://") else {
+        // scp-like `user@host:path`, or a local path
+        return match url.split_once(':') {
+            Some((host, path)) if !host.contains('/') => {
+                let host = host.rsplit('@').next().unwrap_or(host);
+                format!("{}:{}", host, path)
+            }
+            _ => url.to_string(),
+        };
+    };
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let host = authority.rsplit('@').next().unwrap_or(authority);
+    let host = host.split(':').next().unwrap_or(host);
+    if host.is_empty() {
+        // file:///path
+        return format!("/{}", path);
+    }
+    format!("{}:{}", host, path)
+}
 
-    for arg in &args {
-        if skip_next {
-            skip_next = false;
-            continue;
+/// The remote refs a push changes
+fn push_updates(
+    options: &PushOptions,
+    remote: Option<&str>,
+    repo: Option<&Repo>,
+) -> Result<Vec<RefUpdate>, String> {
+    let refspecs: Vec<&String> = options.positional.iter().skip(1).collect();
+
+    let default_change = options.default_change();
+    let mut updates = Vec::new();
+    if options.tags {
+        // Tags that exist remotely are only replaced by a forced push
+        updates.push(RefUpdate {
+            dst: "refs/tags/*".to_string(),
+            change: default_change,
+        });
+    }
+    if options.all {
+        let repo = repo.ok_or("not in a readable repository")?;
+        updates.extend(repo.branches()?.into_iter().map(|dst| RefUpdate {
+            dst,
+            change: default_change,
+        }));
+    }
+
+    if options.delete {
+        if refspecs.is_empty() {
+            return Err("--delete without a ref".to_string());
         }
-        if *arg == "-u" || *arg == "--set-upstream" || *arg == "-o" || *arg == "--push-option" {
-            skip_next = true;
-            continue;
+        updates.extend(refspecs.iter().map(|dst| RefUpdate {
+            dst: short_ref(dst).to_string(),
+            change: RefChange::Delete,
+        }));
+        return Ok(updates);
+    }
+    for refspec in &refspecs {
+        updates.push(parse_refspec(refspec, default_change, repo)?);
+    }
+    if !refspecs.is_empty() || options.all || options.tags {
+        return Ok(updates);
+    }
+
+    // No refspec: the remote's configured push refspecs, or `push.default`
+    let repo = repo.ok_or("not in a readable repository")?;
+    let head = repo.head.as_deref();
+    let remote = remote.ok_or("no remote")?;
+    let configured = repo.get_all(&format!("remote.{}.push", remote));
+    if !configured.is_empty() {
+        for refspec in configured {
+            updates.push(parse_refspec(refspec, default_change, Some(repo))?);
+        }
+        return Ok(updates);
+    }
+
+    let branch = head.ok_or("HEAD is detached")?;
+    let dst = match repo.get("push.default").unwrap_or("simple") {
+        "nothing" => return Ok(updates),
+        "matching" => {
+            updates.extend(repo.branches()?.into_iter().map(|dst| RefUpdate {
+                dst,
+                change: default_change,
+            }));
+            return Ok(updates);
+        }
+        "upstream" | "tracking" => repo
+            .get(&format!("branch.{}.merge", branch))
+            .map(|merge| short_ref(merge).to_string())
+            .ok_or_else(|| format!("branch '{}' has no upstream", branch))?,
+        // `simple` refuses an upstream with another name, `current` uses the same name
+        _ => branch.to_string(),
+    };
+    updates.push(RefUpdate {
+        dst,
+        change: default_change,
+    });
+    Ok(updates)
+}
+
+/// Parse `[+]<src>[:<dst>]`
+fn parse_refspec(
+    refspec: &str,
+    default_change: RefChange,
+    repo: Option<&Repo>,
+) -> Result<RefUpdate, String> {
+    let (forced, refspec) = match refspec.strip_prefix('+') {
+        Some(refspec) => (true, refspec),
+        None => (false, refspec),
+    };
+    let change = if forced {
+        RefChange::Force
+    } else {
+        default_change
+    };
+    let (src, dst) = refspec.split_once(':').unwrap_or((refspec, ""));
+    match (src, dst) {
+        // `:` pushes matching branches
+        ("", "") => Ok(RefUpdate {
+            dst: "*".to_string(),
+            change,
+        }),
+        // `:branch` deletes it
+        ("", dst) => Ok(RefUpdate {
+            dst: short_ref(dst).to_string(),
+            change: RefChange::Delete,
+        }),
+        (_, dst) if !dst.is_empty() => Ok(RefUpdate {
+            dst: short_ref(dst).to_string(),
+            change,
+        }),
+        // `src` or `src:` pushes to the same name; HEAD is the current branch
+        (src, _) => {
+            let dst = if matches!(src, "HEAD" | "@") {
+                repo.and_then(|r| r.head.clone())
+                    .ok_or("HEAD is detached or the repository is unreadable")?
+            } else {
+                short_ref(src).to_string()
+            };
+            Ok(RefUpdate { dst, change })
         }
-        if arg.starts_with('-') {
-            continue;
+    }
+}
+
+/// A ref name without `refs/heads/`
+fn short_ref(name: &str) -> &str {
+    name.strip_prefix("refs/heads/").unwrap_or(name)
+}
+
+/// The subcommand of a git invocation and the index of its first argument,
+/// skipping global options (`git -C dir -c k=v push`)
+pub fn subcommand(cmd: &Command) -> Option<(&str, usize)> {
+    if cmd.name != "git" {
+        return None;
+    }
+    let mut i = 0;
+    while let Some(arg) = cmd.args.get(i) {
+        match arg.as_str() {
+            "-C" | "-c" | "--git-dir" | "--work-tree" | "--namespace" | "--config-env" => i += 2,
+            _ if arg.starts_with('-') => i += 1,
+            _ => return Some((arg, i + 1)),
         }
-        positional.push(arg);
     }
+    None
+}
 
-    // git push [remote] [branch]
-    // If branch is specified, use it
-    if positional.len() >= 2 {
-        let branch = positional[1];
-        // Handle refspec like HEAD:main or feature:main
-        if let Some(colon_pos) = branch.find(':') {
-            return Some(branch[colon_pos + 1..].to_string());
+/// The directory git runs in: `cwd` followed by each `-C` before the subcommand
+/// None if unknown (no cwd, or a `-C` that can't be expanded)
+fn command_dir(
+    cmd: &Command,
+    first_arg: usize,
+    cwd: Option<&str>,
+    assigned: &[String],
+) -> Option<String> {
+    let mut dir = cwd?.to_string();
+    let mut i = 0;
+    while i + 1 < first_arg {
+        match cmd.args[i].as_str() {
+            "-C" => {
+                // Expanded the way the shell would (`-C ~/repo`)
+                let mut words = expand::expand_word(cmd.raw_arg(i + 1), Some(&dir), assigned)?;
+                if words.len() != 1 {
+                    return None;
+                }
+                let path = words.pop()?;
+                dir = if path.starts_with('/') {
+                    path
+                } else {
+                    format!("{}/{}", dir.trim_end_matches('/'), path)
+                };
+                i += 2;
+            }
+            "-c" | "--git-dir" | "--work-tree" | "--namespace" | "--config-env" => i += 2,
+            _ => i += 1,
         }
-        return Some(branch.to_string());
     }
+    Some(dir)
+}
 
-    // If only remote or no args, check current branch
-    get_current_branch()
+/// Repository state read by running git where the command would
+struct Repo {
+    /// Directory git runs in
+    dir: String,
+    /// Global options locating the repository (`--git-dir`, `--work-tree`, `-c`)
+    globals: Vec<String>,
+    /// `git config --list` entries, keys lowercased except for subsections
+    config: Vec<(String, String)>,
+    /// The current branch, None when HEAD is detached
+    head: Option<String>,
 }
 
-/// Get the current git branch
-fn get_current_branch() -> Option<String> {
-    let output = ProcessCommand::new("git")
-        .args(["rev-parse", "--abbrev-ref", "HEAD"])
-        .output()
-        .ok()?;
+impl Repo {
+    /// Read the repository a git command (subcommand at `first_arg - 1`) runs in
+    /// Returns None when the directory is unknown or isn't a repository
+    /// `dir` is where git runs, after any `-C` (see [`command_dir`])
+    fn open(cmd: &Command, first_arg: usize, dir: String, assigned: &[String]) -> Option<Repo> {
+        let cwd = Some(dir.as_str());
+        let mut globals = Vec::new();
+        let mut i = 0;
+        while i + 1 < first_arg {
+            let arg = cmd.args[i].as_str();
+            match arg {
+                "-C" => i += 2,
+                "--git-dir" | "--work-tree" => {
+                    // Expanded the way the shell would (`-C ~/repo`)
+                    let mut words = expand::expand_word(cmd.raw_arg(i + 1), cwd, assigned)?;
+                    if words.len() != 1 {
+                        return None;
+                    }
+                    globals.push(arg.to_string());
+                    globals.push(words.pop()?);
+                    i += 2;
+                }
+                "-c" => {
+                    globals.extend([arg.to_string(), cmd.args.get(i + 1)?.clone()]);
+                    i += 2;
+                }
+                "--namespace" | "--config-env" => i += 2,
+                _ if arg.starts_with("--git-dir=") || arg.starts_with("--work-tree=") => {
+                    globals.push(arg.to_string());
+                    i += 1;
+                }
+                _ => i += 1,
+            }
+        }
 
-    if output.status.success() {
-        let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if !branch.is_empty() && branch != "HEAD" {
-            return Some(branch);
+        let mut repo = Repo {
+            dir,
+            globals,
+            config: Vec::new(),
+            head: None,
+        };
+        let config = repo.git(&["config", "-z", "--list"])?;
+        repo.config = config
+            .split('\0')
+            .filter_map(|entry| {
+                let (key, value) = entry.split_once('\n').unwrap_or((entry, "true"));
+                (!key.is_empty()).then(|| (key.to_string(), value.to_string()))
+            })
+            .collect();
+        repo.head = repo
+            .git(&["symbolic-ref", "-q", "--short", "HEAD"])
+            .map(|head| head.trim().to_string());
+        Some(repo)
+    }
+
+    /// Settings overriding the repository's configured programs (fsmonitor,
+    /// hooks, pager, filters), so that reading it runs nothing it chose
+    /// None when a filter can't be named on the command line
+    fn no_programs(&self) -> Option<Vec<String>> {
+        let mut settings = vec![
+            "core.fsmonitor=false".to_string(),
+            "core.hooksPath=/dev/null".to_string(),
+            "core.pager=cat".to_string(),
+            "log.showSignature=false".to_string(),
+        ];
+        for (key, _) in &self.config {
+            let Some(filter) = key
+                .strip_prefix("filter.")
+                .and_then(|rest| rest.rsplit_once('.'))
+                .filter(|(_, setting)| matches!(*setting, "clean" | "smudge" | "process"))
+                .map(|(filter, _)| filter)
+            else {
+                continue;
+            };
+            // `-c` splits at the first `=`
+            if filter.contains('=') {
+                return None;
+            }
+            settings.push(format!("{}=", key));
+            settings.push(format!("filter.{}.required=false", filter));
         }
+        Some(
+            settings
+                .into_iter()
+                .flat_map(|setting| ["-c".to_string(), setting])
+                .chain(["--no-pager".to_string()])
+                .collect(),
+        )
+    }
+
+    /// Run git in the repository and return its output, None if it fails
+    fn git(&self, args: &[&str]) -> Option<String> {
+        let output = ProcessCommand::new("git")
+            .current_dir(&self.dir)
+            .args(&self.globals)
+            .args(self.no_programs()?)
+            .args(args)
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Run a read-only git command to see what another would change, without
+    /// the command's own `-c` settings (which can name programs git runs)
+    fn inspect(&self, args: &[&str]) -> Option<String> {
+        let mut globals = Vec::new();
+        let mut i = 0;
+        while let Some(arg) = self.globals.get(i) {
+            if arg == "-c" {
+                i += 2;
+                continue;
+            }
+            globals.push(arg.as_str());
+            i += 1;
+        }
+        let output = ProcessCommand::new("git")
+            .current_dir(&self.dir)
+            .arg("--no-optional-locks")
+            .args(globals)
+            .args(self.no_programs()?)
+            .args(args)
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// The last value of a config key (`branch.<name>.merge`; section and
+    /// variable names are matched case-insensitively)
+    fn get(&self, key: &str) -> Option<&str> {
+        self.get_all(key).pop()
+    }
+
+    fn get_all(&self, key: &str) -> Vec<&str> {
+        let key = normalize_key(key);
+        self.config
+            .iter()
+            .filter(|(k, _)| normalize_key(k) == key)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    /// The remote `git push` without a repository pushes to
+    fn default_push_remote(&self) -> String {
+        let branch = |var: &str| {
+            self.head
+                .as_ref()
+                .and_then(|head| self.get(&format!("branch.{}.{}", head, var)))
+        };
+        branch("pushRemote")
+            .or_else(|| self.get("remote.pushDefault"))
+            .or_else(|| branch("remote"))
+            .unwrap_or("origin")
+            .to_string()
+    }
+
+    /// Local branch names
+    fn branches(&self) -> Result<Vec<String>, String> {
+        let output = self
+            .git(&["for-each-ref", "--format=%(refname:short)", "refs/heads"])
+            .ok_or("can't list branches")?;
+        Ok(output.lines().map(String::from).collect())
+    }
+}
+
+/// Lowercase the section and variable of a config key, keeping the subsection
+fn normalize_key(key: &str) -> String {
+    match (key.find('.'), key.rfind('.')) {
+        (Some(first), Some(last)) if first != last => format!(
+            "{}{}{}",
+            key[..first].to_lowercase(),
+            &key[first..last],
+            key[last..].to_lowercase()
+        ),
+        _ => key.to_lowercase(),
     }
-    None
 }
 
 #[cfg(test)]
@@ -147,13 +1431,14 @@ mod tests {
             name: "git".to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("git {}", args.join(" ")),
+            ..Default::default()
         }
     }
 
     #[test]
     fn test_force_push_asks() {
         let cmd = make_cmd(&["push", "-f"]);
-        let result = check_git_push(&cmd).unwrap();
+        let result = check_git_push(&cmd, None, &[], &Config::default()).unwrap();
         assert_eq!(result.permission, Permission::Ask);
         assert!(result.suggestion.is_some()); // suggests --force-with-lease
     }
@@ -161,52 +1446,559 @@ mod tests {
     #[test]
     fn test_force_with_lease_to_feature_allows() {
         let cmd = make_cmd(&["push", "--force-with-lease", "origin", "feature-branch"]);
-        let result = check_git_push(&cmd).unwrap();
+        let result = check_git_push(&cmd, None, &[], &Config::default()).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
 
     #[test]
     fn test_force_with_lease_to_main_asks() {
         let cmd = make_cmd(&["push", "--force-with-lease", "origin", "main"]);
-        let result = check_git_push(&cmd).unwrap();
+        let result = check_git_push(&cmd, None, &[], &Config::default()).unwrap();
         assert_eq!(result.permission, Permission::Ask);
     }
 
     #[test]
     fn test_push_to_master_asks() {
         let cmd = make_cmd(&["push", "origin", "master"]);
-        let result = check_git_push(&cmd).unwrap();
+        let result = check_git_push(&cmd, None, &[], &Config::default()).unwrap();
         assert_eq!(result.permission, Permission::Ask);
     }
 
     #[test]
     fn test_push_to_main_asks() {
         let cmd = make_cmd(&["push", "origin", "main"]);
-        let result = check_git_push(&cmd).unwrap();
+        let result = check_git_push(&cmd, None, &[], &Config::default()).unwrap();
         assert_eq!(result.permission, Permission::Ask);
     }
 
     #[test]
     fn test_push_to_feature_allows() {
         let cmd = make_cmd(&["push", "origin", "feature-branch"]);
-        let result = check_git_push(&cmd).unwrap();
+        let result = check_git_push(&cmd, None, &[], &Config::default()).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
 
     #[test]
     fn test_push_refspec_to_main_asks() {
         let cmd = make_cmd(&["push", "origin", "HEAD:main"]);
-        let result = check_git_push(&cmd).unwrap();
+        let result = check_git_push(&cmd, None, &[], &Config::default()).unwrap();
         assert_eq!(result.permission, Permission::Ask);
     }
 
     #[test]
     fn test_non_push_returns_none() {
         let cmd = make_cmd(&["status"]);
-        let result = check_git_push(&cmd);
+        let result = check_git_push(&cmd, None, &[], &Config::default());
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_plus_refspec_is_force() {
+        let result = check_git_push(
+            &make_cmd(&["push", "origin", "+feature"]),
+            None,
+            &[],
+            &Config::default(),
+        )
+        .unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+        assert_eq!(result.reason, "force push to 'feature'");
+        let cmd = make_cmd(&["push", "--force-with-lease", "origin", "+HEAD:feature"]);
+        assert_eq!(
+            check_git_push(&cmd, None, &[], &Config::default())
+                .unwrap()
+                .permission,
+            Permission::Ask
+        );
+    }
+
+    #[test]
+    fn test_push_deletions_and_bulk_pushes_ask() {
+        for args in [
+            &["push", "origin", ":feature"][..],
+            &["push", "--delete", "origin", "feature"],
+            &["push", "-d", "origin", "feature"],
+            &["push", "--mirror", "backup"],
+            &["push", "--prune", "origin", "feature"],
+            &["push", "origin", "refs/heads/*:refs/heads/*"],
+            &["push", "origin", ":"],
+        ] {
+            let result = check_git_push(&make_cmd(args), None, &[], &Config::default()).unwrap();
+            assert_eq!(result.permission, Permission::Ask, "{:?}", args);
+        }
+        let tags = make_cmd(&["push", "--tags", "origin"]);
+        assert_eq!(
+            check_git_push(&tags, None, &[], &Config::default())
+                .unwrap()
+                .permission,
+            Permission::Allow
+        );
+    }
+
+    #[test]
+    fn test_abbreviated_options() {
+        let cmd = make_cmd(&["push", "--forc", "origin", "feature"]);
+        let result = check_git_push(&cmd, None, &[], &Config::default()).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+        assert_eq!(result.reason, "ambiguous option --forc");
+        for option in ["--mirr", "--del", "--pru"] {
+            let cmd = make_cmd(&["push", option, "origin", "feature"]);
+            let result = check_git_push(&cmd, None, &[], &Config::default()).unwrap();
+            assert_eq!(result.permission, Permission::Ask, "{}", option);
+        }
+        let cmd = make_cmd(&["push", "--dry", "-f", "origin", "main"]);
+        assert_eq!(
+            check_git_push(&cmd, None, &[], &Config::default())
+                .unwrap()
+                .permission,
+            Permission::Allow
+        );
+        let cmd = make_cmd(&["push", "--no-verify", "--set-up", "origin", "feature"]);
+        assert_eq!(
+            check_git_push(&cmd, None, &[], &Config::default())
+                .unwrap()
+                .permission,
+            Permission::Allow
+        );
+    }
+
+    /// A repository on branch `branch` with `config` entries set
+    fn repo(name: &str, branch: &str, config: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = crate::test_util::temp_dir(&format!("git-{}", name));
+        let git = |args: &[&str]| {
+            let status = ProcessCommand::new("git").args(args).status().expect("git");
+            assert!(status.success());
+        };
+        git(&["init", "-q", "-b", branch, dir.to_str().unwrap()]);
+        for (key, value) in config {
+            git(&["-C", dir.to_str().unwrap(), "config", key, value]);
+        }
+        dir
+    }
+
+    #[test]
+    fn test_push_target_from_repo_config() {
+        let decide = |dir: &std::path::Path, args: &[&str]| {
+            check_git_push(&make_cmd(args), dir.to_str(), &[], &Config::default())
+                .unwrap()
+                .permission
+        };
+
+        // The current branch, read in the directory the command runs in
+        let dir = repo("current", "main", &[]);
+        assert_eq!(decide(&dir, &["push"]), Permission::Ask);
+        assert_eq!(decide(&dir, &["push", "origin", "HEAD"]), Permission::Ask);
+        assert_eq!(
+            decide(&dir, &["push", "origin", "HEAD:feature"]),
+            Permission::Allow
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let dir = repo("feature", "feature", &[]);
+        assert_eq!(decide(&dir, &["push"]), Permission::Allow);
+        assert_eq!(decide(&dir, &["push", "--all"]), Permission::Allow);
+        // `git -C` is resolved against the command's directory
+        let parent = dir.parent().unwrap();
+        let name = dir.file_name().unwrap().to_str().unwrap();
+        assert_eq!(decide(parent, &["-C", name, "push"]), Permission::Allow);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // push.default=upstream pushes to the tracked branch
+        let dir = repo(
+            "upstream",
+            "feature",
+            &[
+                ("push.default", "upstream"),
+                ("branch.feature.remote", "origin"),
+                ("branch.feature.merge", "refs/heads/main"),
+            ],
+        );
+        assert_eq!(decide(&dir, &["push"]), Permission::Ask);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Configured push refspecs and mirror remotes
+        let dir = repo(
+            "refspecs",
+            "feature",
+            &[
+                (
+                    "remote.origin.push",
+                    "+refs/heads/feature:refs/heads/feature",
+                ),
+                ("remote.backup.mirror", "true"),
+            ],
+        );
+        assert_eq!(decide(&dir, &["push"]), Permission::Ask);
+        assert_eq!(decide(&dir, &["push", "backup"]), Permission::Ask);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Without a known directory, only explicit refspecs can be resolved
+        let cmd = make_cmd(&["push"]);
+        assert_eq!(
+            check_git_push(&cmd, None, &[], &Config::default())
+                .unwrap()
+                .permission,
+            Permission::Ask
+        );
+    }
+
+    #[test]
+    fn test_configured_policy() {
+        let config: Config = toml::from_str(
+            r#"
+            [git]
+            protected_branches = ["main", "release/*"]
+
+            [git.permissions]
+            force_with_lease = "ask"
+            delete = "deny"
+
+            [[git.remotes]]
+            url = "github.com:me/*"
+            permission = "allow"
+            "#,
+        )
+        .unwrap();
+        let decide = |args: &[&str], cwd: Option<&str>| {
+            check_git_push(&make_cmd(args), cwd, &[], &config)
+                .unwrap()
+                .permission
+        };
+
+        assert_eq!(
+            decide(&["push", "origin", "release/1.0"], None),
+            Permission::Ask
+        );
+        assert_eq!(
+            decide(&["push", "origin", "master"], None),
+            Permission::Allow
+        );
+        assert_eq!(
+            decide(&["push", "--force-with-lease", "origin", "feature"], None),
+            Permission::Ask
+        );
+        assert_eq!(
+            decide(&["push", "origin", ":feature"], None),
+            Permission::Deny
+        );
+
+        // Remote rules match the URL, however it's spelled
+        for url in [
+            "git@github.com:me/app.git",
+            "https://github.com/me/app",
+            "ssh://git@github.com:22/me/app.git",
+        ] {
+            assert_eq!(
+                decide(&["push", "-f", url, "main"], None),
+                Permission::Allow,
+                "{}",
+                url
+            );
+        }
+        let dir = repo(
+            "remotes",
+            "main",
+            &[("remote.origin.url", "git@github.com:me/fork.git")],
+        );
+        assert_eq!(decide(&["push", "-f"], dir.to_str()), Permission::Allow);
+        let dir_str = dir.to_str().unwrap();
+        let result = check_git_push(
+            &make_cmd(&["push", "--delete", "origin", "x"]),
+            Some(dir_str),
+            &[],
+            &config,
+        )
+        .unwrap();
+        assert_eq!(
+            result.reason,
+            "push to github.com:me/fork (git remote rule \"github.com:me/*\")"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_subcommand_after_global_options() {
+        let cmd = make_cmd(&["-C", "/x", "-c", "a=b", "--no-pager", "push", "origin"]);
+        assert_eq!(subcommand(&cmd), Some(("push", 6)));
+        let cmd = make_cmd(&["-C", "/x", "push", "origin", "main"]);
+        assert_eq!(
+            check_git_push(&cmd, None, &[], &Config::default())
+                .unwrap()
+                .permission,
+            Permission::Ask
+        );
+        assert_eq!(subcommand(&make_cmd(&["--version"])), None);
+    }
+
+    #[test]
+    fn test_destruction_parse() {
+        let parse = |args: &[&str]| {
+            let (subcommand, args) = args.split_first().unwrap();
+            let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+            Destruction::parse(subcommand, &args)
+        };
+        assert_eq!(
+            parse(&["reset", "--hard", "HEAD~2"]),
+            Some(Destruction::ResetHard(Some("HEAD~2".to_string())))
+        );
+        assert_eq!(parse(&["reset", "--soft", "HEAD~2"]), None);
+        assert!(matches!(
+            parse(&["clean", "-fdx"]),
+            Some(Destruction::Clean(_))
+        ));
+        assert_eq!(parse(&["clean", "-fdn"]), None);
+        assert_eq!(parse(&["clean", "-f", "--dry-run"]), None);
+        // Paths stay after `--`, where git can't take them as options
+        assert_eq!(
+            parse(&["clean", "-fdx", "-en", "--", "-x", "."]),
+            Some(Destruction::Clean(Some(
+                [
+                    "clean",
+                    "--dry-run",
+                    "-f",
+                    "-d",
+                    "-x",
+                    "--exclude=n",
+                    "--",
+                    "-x",
+                    "."
+                ]
+                .map(String::from)
+                .to_vec()
+            )))
+        );
+        assert_eq!(
+            parse(&["clean", "-f", "--unknown"]),
+            Some(Destruction::Clean(None))
+        );
+        assert_eq!(parse(&["restore", "--staged", "a"]), None);
+        assert!(matches!(
+            parse(&["restore", "-SW", "a"]),
+            Some(Destruction::Restore { staged: true, .. })
+        ));
+        assert_eq!(parse(&["rm", "a"]), None);
+        assert_eq!(parse(&["rm", "-rf", "--cached", "a"]), None);
+        assert_eq!(
+            parse(&["branch", "-D", "x", "y"]),
+            Some(Destruction::DeleteBranches(vec![
+                "x".to_string(),
+                "y".to_string()
+            ]))
+        );
+        assert!(parse(&["branch", "--delete", "--force", "x"]).is_some());
+        assert_eq!(parse(&["branch", "-d", "x"]), None);
+        assert_eq!(parse(&["branch", "-rD", "origin/x"]), None);
+        assert!(parse(&["branch", "-M", "x"]).is_some());
+        assert!(parse(&["branch", "-c", "-f", "x", "y"]).is_some());
+        assert_eq!(parse(&["branch", "-m", "x", "y"]), None);
+        assert!(parse(&["switch", "--discard-changes", "x"]).is_some());
+        assert!(parse(&["checkout", "--force", "x"]).is_some());
+        assert_eq!(parse(&["switch", "x"]), None);
+        assert_eq!(
+            parse(&["stash", "drop", "1"]),
+            Some(Destruction::DropStash("stash@{1}".to_string()))
+        );
+        assert_eq!(parse(&["stash", "clear"]), Some(Destruction::ClearStash));
+        assert_eq!(parse(&["stash", "list"]), None);
+        assert!(parse(&["tag", "-d", "v1"]).is_some());
+        assert_eq!(parse(&["tag", "v1"]), None);
+        assert!(parse(&["update-ref", "-d", "refs/x"]).is_some());
+        assert_eq!(parse(&["reflog", "expire"]), None);
+        assert!(parse(&["reflog", "expire", "--expire=now", "--all"]).is_some());
+        assert_eq!(parse(&["gc"]), None);
+        assert_eq!(parse(&["gc", "--prune=now"]), Some(Destruction::Prune));
+        assert_eq!(parse(&["prune"]), Some(Destruction::Prune));
+        assert_eq!(
+            parse(&["filter-branch", "-f", "--tree-filter", "x"]),
+            Some(Destruction::FilterBranch { force: true })
+        );
+    }
+
+    #[test]
+    fn test_destructive_commands_report_losses() {
+        let dir = repo("destructive", "main", &[]);
+        let git = |args: &[&str]| {
+            let output = ProcessCommand::new("git")
+                .args(["-c", "user.name=t", "-c", "user.email=t@t", "-C"])
+                .arg(&dir)
+                .args(args)
+                .output()
+                .expect("git");
+            assert!(output.status.success(), "{:?}", args);
+        };
+        let write = |file: &str, content: &str| std::fs::write(dir.join(file), content).unwrap();
+        let config = Config::default();
+        let check =
+            |args: &[&str]| check_git_destructive(&make_cmd(args), dir.to_str(), &[], &config);
+
+        write("a.txt", "1");
+        git(&["add", "a.txt"]);
+        git(&["commit", "-qm", "one"]);
+
+        // Nothing to lose
+        assert_eq!(
+            check(&["reset", "--hard"]).unwrap().permission,
+            Permission::Allow
+        );
+        assert_eq!(
+            check(&["clean", "-fd"]).unwrap().permission,
+            Permission::Allow
+        );
+        assert_eq!(
+            check(&["restore", "."]).unwrap().permission,
+            Permission::Allow
+        );
+        assert_eq!(
+            check(&["stash", "clear"]).unwrap().permission,
+            Permission::Allow
+        );
+        assert_eq!(
+            check(&["switch", "-f", "main"]).unwrap().permission,
+            Permission::Allow
+        );
+        assert!(check(&["status"]).is_none());
+
+        // Uncommitted and untracked work
+        write("a.txt", "2");
+        write("new.txt", "x");
+        let result = check(&["reset", "--hard"]).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+        assert_eq!(
+            result.reason,
+            "git reset --hard discards uncommitted changes to 1 file: a.txt"
+        );
+        let result = check(&["clean", "-fd"]).unwrap();
+        assert_eq!(result.reason, "git clean deletes 1 untracked file: new.txt");
+        // A `--` pathspec must not turn the listing into a real clean
+        let result = check(&["clean", "-fdx", "--", "."]).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+        assert_eq!(result.reason, "git clean deletes 1 untracked file: new.txt");
+        assert!(dir.join("new.txt").exists());
+        let result = check(&["restore", "a.txt"]).unwrap();
+        assert_eq!(
+            result.reason,
+            "git restore discards uncommitted changes to 1 file: a.txt"
+        );
+        assert_eq!(
+            check(&["restore", "new.txt"]).unwrap().permission,
+            Permission::Allow
+        );
+        let result = check(&["switch", "--discard-changes", "main"]).unwrap();
+        assert_eq!(
+            result.reason,
+            "git switch --discard-changes discards uncommitted changes to 1 file: a.txt"
+        );
+        let result = check(&["checkout", "-f", "main"]).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+
+        // Commits only on a branch, recoverable until gc
+        git(&["stash", "-q"]);
+        git(&["switch", "-qc", "feature"]);
+        write("b.txt", "1");
+        git(&["add", "b.txt"]);
+        git(&["commit", "-qm", "two"]);
+        git(&["switch", "-q", "main"]);
+        let result = check(&["branch", "-D", "feature"]).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+        assert_eq!(
+            result.reason,
+            "git branch -D: 'feature' has 1 commit on no other branch (recoverable by hash until gc prunes them)"
+        );
+        git(&["branch", "merged"]);
+        assert_eq!(
+            check(&["branch", "-D", "merged"]).unwrap().permission,
+            Permission::Allow
+        );
+        let result = check(&["branch", "-M", "main", "feature"]).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+        assert_eq!(
+            result.reason,
+            "git branch -M overwrites 'feature', which has 1 commit on no other branch (recoverable by hash until gc prunes them)"
+        );
+        assert_eq!(
+            check(&["branch", "-C", "merged"]).unwrap().permission,
+            Permission::Allow
+        );
+        assert_eq!(
+            check(&["branch", "-m", "--force", "main"])
+                .unwrap()
+                .permission,
+            Permission::Allow
+        );
+        assert_eq!(
+            check(&["branch", "-M", "renamed"]).unwrap().permission,
+            Permission::Allow
+        );
+        let result = check(&["stash", "drop"]).unwrap();
+        assert!(result.reason.contains("WIP on main"), "{}", result.reason);
+        assert_eq!(
+            check(&["stash", "drop", "3"]).unwrap().permission,
+            Permission::Allow
+        );
+
+        // Permissions by recoverability
+        let config: Config = toml::from_str(
+            r#"
+            [git.permissions]
+            recoverable = "allow"
+            unrecoverable = "deny"
+            "#,
+        )
+        .unwrap();
+        let check = |args: &[&str]| {
+            check_git_destructive(&make_cmd(args), dir.to_str(), &[], &config)
+                .unwrap()
+                .permission
+        };
+        assert_eq!(check(&["tag", "-d", "v1"]), Permission::Allow);
+        assert_eq!(check(&["gc", "--prune=now"]), Permission::Deny);
+        git(&["switch", "-q", "feature"]);
+        assert_eq!(check(&["reset", "--hard", "main"]), Permission::Allow);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Unknown repository: the worst case
+        let result =
+            check_git_destructive(&make_cmd(&["clean", "-f"]), None, &[], &Config::default());
+        assert_eq!(result.unwrap().permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_inspection_runs_no_configured_programs() {
+        let dir = repo("programs", "main", &[]);
+        let git = |args: &[&str]| {
+            let output = ProcessCommand::new("git")
+                .args(["-c", "user.name=t", "-c", "user.email=t@t", "-C"])
+                .arg(&dir)
+                .args(args)
+                .output()
+                .expect("git");
+            assert!(output.status.success(), "{:?}", args);
+        };
+        std::fs::write(dir.join(".gitattributes"), "a.txt filter=evil\n").unwrap();
+        std::fs::write(dir.join("a.txt"), "1").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-qm", "one"]);
+
+        let marker = dir.join("ran");
+        let program = format!("touch {}; cat", marker.display());
+        git(&["config", "core.fsmonitor", &program]);
+        git(&["config", "filter.evil.clean", &program]);
+        git(&["config", "filter.evil.required", "true"]);
+        git(&["config", "core.pager", &program]);
+        std::fs::write(dir.join("a.txt"), "2").unwrap();
+
+        let result = check_git_destructive(
+            &make_cmd(&["reset", "--hard"]),
+            dir.to_str(),
+            &[],
+            &Config::default(),
+        )
+        .unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+        assert!(!marker.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     // Git checkout tests
 
     #[test]
@@ -0,0 +1,267 @@
+//! Rule proposals from the audit log
+//!
+//! Commands that keep ending up as `ask` or `passthrough` are usually ones
+//! that get approved by hand every time. This groups them by command name and
+//! subcommand path (`gh pr list`, not just `gh pr`) and proposes `[[rules]]`
+//! snippets with how often each was seen.
+
+use crate::Handler;
+use crate::analyzer::Command;
+use crate::audit::{AuditCommand, AuditEntry};
+use crate::config::Config;
+use std::collections::BTreeMap;
+
+/// A proposed rule for one command name
+#[derive(Debug, PartialEq)]
+pub struct RuleProposal {
+    pub name: String,
+    /// Patterns with how often they were seen
+    pub patterns: Vec<(String, usize)>,
+    /// Times the command was seen in total
+    pub total: usize,
+}
+
+impl RuleProposal {
+    /// Render as a commented `[[rules]]` TOML snippet
+    pub fn to_toml(&self) -> String {
+        let mut out = format!("# {}: seen {} times\n", self.name, self.total);
+        for (pattern, count) in &self.patterns {
+            out.push_str(&format!("#   {} x{}\n", pattern, count));
+        }
+        let commands: Vec<String> = self
+            .patterns
+            .iter()
+            .map(|(pattern, _)| toml::Value::String(pattern.clone()).to_string())
+            .collect();
+        out.push_str("[[rules]]\n");
+        out.push_str(&format!("commands = [{}]\n", commands.join(", ")));
+        out.push_str("permission = \"allow\"\n");
+        out.push_str("reason = \"approved by hand (from audit log)\"\n");
+        out
+    }
+}
+
+/// Per-name counts while grouping
+#[derive(Default)]
+struct Group {
+    total: usize,
+    subcommands: BTreeMap<String, usize>,
+    /// Whether any entry had a subcommand path, so the bare name would cover
+    /// subcommands that were never seen
+    has_subcommands: bool,
+}
+
+/// Propose rules for commands seen as ask/passthrough at least `min_count` times
+pub fn propose_rules(
+    config: &Config,
+    entries: &[AuditEntry],
+    min_count: usize,
+) -> Vec<RuleProposal> {
+    let mut groups: BTreeMap<String, Group> = BTreeMap::new();
+
+    for command in entries.iter().flat_map(|e| &e.commands) {
+        if !matches!(command.decision.as_str(), "ask" | "passthrough")
+            || !decided_by_rule(&command.handler)
+            || config.get_wrapper(&command.name).is_some()
+            || is_special_handler(command)
+        {
+            continue;
+        }
+
+        let name = command.name.strip_prefix("./").unwrap_or(&command.name);
+        if name.is_empty() || name.contains(['$', '`', '"', '\'']) {
+            continue;
+        }
+
+        let group = groups.entry(name.to_string()).or_default();
+        group.total += 1;
+        // A rule for `gh pr` would also allow `gh pr merge`, so key on the whole path
+        let path: Vec<String> = config
+            .find_subcommands(&command.name, &command.args)
+            .into_iter()
+            .take_while(|s| is_subcommand_like(s))
+            .collect();
+        if !path.is_empty() {
+            group.has_subcommands = true;
+            *group.subcommands.entry(path.join(" ")).or_default() += 1;
+        }
+    }
+
+    let mut proposals: Vec<RuleProposal> = groups
+        .into_iter()
+        .filter(|(_, group)| group.total >= min_count)
+        .map(|(name, group)| {
+            // Prefer specific subcommands that recur; fall back to the bare name
+            // only for commands that never took a subcommand
+            let mut patterns: Vec<(String, usize)> = group
+                .subcommands
+                .into_iter()
+                .filter(|(_, count)| *count >= min_count)
+                .map(|(sub, count)| (format!("{} {}", name, sub), count))
+                .collect();
+            patterns.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            if patterns.is_empty() && !group.has_subcommands {
+                patterns.push((name.clone(), group.total));
+            }
+            RuleProposal {
+                name,
+                patterns,
+                total: group.total,
+            }
+        })
+        .filter(|proposal| !proposal.patterns.is_empty())
+        .collect();
+
+    proposals.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.name.cmp(&b.name)));
+    proposals
+}
+
+/// Whether a command is decided by a special handler, so a rule would not apply
+fn is_special_handler(command: &AuditCommand) -> bool {
+    let cmd = Command {
+        name: command.name.clone(),
+        args: command.args.clone(),
+        ..Default::default()
+    };
+    Handler::of(&cmd).is_some()
+}
+
+/// Whether a decision came from a config rule or the default, not a special handler
+fn decided_by_rule(handler: &str) -> bool {
+    handler == "default" || handler.starts_with("rule #") || handler.starts_with("project rule #")
+}
+
+/// Whether a positional looks like a subcommand rather than a file or value
+fn is_subcommand_like(arg: &str) -> bool {
+    arg.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(commands: &[(&str, &str, &str)]) -> AuditEntry {
+        AuditEntry {
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            cwd: None,
+            permission_mode: None,
+            tool: "Bash".to_string(),
+            command: String::new(),
+            decision: "ask".to_string(),
+            reason: String::new(),
+            handler: None,
+            commands: commands
+                .iter()
+                .map(|(text, decision, handler)| {
+                    let mut words = text.split_whitespace();
+                    AuditCommand {
+                        text: text.to_string(),
+                        name: words.next().unwrap().to_string(),
+                        args: words.map(String::from).collect(),
+                        decision: decision.to_string(),
+                        handler: handler.to_string(),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    fn repeat(text: &str, times: usize) -> Vec<AuditEntry> {
+        (0..times)
+            .map(|_| entry(&[(text, "passthrough", "default")]))
+            .collect()
+    }
+
+    #[test]
+    fn test_groups_by_subcommand() {
+        let config = Config::default();
+        let mut entries = repeat("cargo build --release", 4);
+        entries.extend(repeat("cargo bench", 3));
+        entries.extend(repeat("cargo fix", 1));
+
+        let proposals = propose_rules(&config, &entries, 3);
+        assert_eq!(
+            proposals,
+            [RuleProposal {
+                name: "cargo".to_string(),
+                patterns: vec![
+                    ("cargo build".to_string(), 4),
+                    ("cargo bench".to_string(), 3)
+                ],
+                total: 8,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_groups_by_full_subcommand_path() {
+        let config = Config::default();
+        let mut entries = repeat("gh pr list", 3);
+        entries.extend(repeat("gh pr merge 12", 1));
+
+        let proposals = propose_rules(&config, &entries, 3);
+        assert_eq!(proposals[0].patterns, [("gh pr list".to_string(), 3)]);
+    }
+
+    #[test]
+    fn test_falls_back_to_command_name() {
+        let config = Config::default();
+        let entries: Vec<AuditEntry> = ["a.txt", "b.txt", "c.txt"]
+            .iter()
+            .map(|f| entry(&[(&format!("bat {}", f), "passthrough", "default")]))
+            .collect();
+
+        let proposals = propose_rules(&config, &entries, 3);
+        assert_eq!(proposals.len(), 1);
+        assert_eq!(proposals[0].patterns, [("bat".to_string(), 3)]);
+    }
+
+    #[test]
+    fn test_no_fallback_for_scattered_subcommands() {
+        let config = Config::default();
+        let mut entries = repeat("kubectl get pods", 1);
+        entries.extend(repeat("kubectl describe pod web", 1));
+        entries.extend(repeat("kubectl delete pod web", 1));
+
+        assert!(propose_rules(&config, &entries, 3).is_empty());
+    }
+
+    #[test]
+    fn test_skips_special_handlers_and_allowed() {
+        let config = Config::default();
+        let mut entries = repeat("rm -rf build", 5);
+        entries.extend(repeat("git push origin main", 5));
+        entries.extend(repeat("git reset --hard HEAD~1", 5));
+        entries.extend(repeat("git branch -M main", 5));
+        entries.extend(repeat("git branch -m --force old new", 5));
+        entries.extend(repeat("git checkout -f main", 5));
+        entries.extend(repeat("git stash drop", 5));
+        entries.extend(repeat("sed -i s/a/b/ f.txt", 5));
+        entries.extend(repeat("docker run alpine", 5));
+        entries.extend(repeat("sudo apt update", 5));
+        entries.extend(repeat("mysql-prod -e 'DELETE FROM t'", 5));
+        entries.extend(repeat("mariadb db", 5));
+        entries
+            .extend((0..5).map(|_| entry(&[("git push --force", "ask", "git::check_git_push")])));
+        entries.extend((0..5).map(|_| entry(&[("ls", "allow", "rule #1 \"ls\"")])));
+
+        assert!(propose_rules(&config, &entries, 3).is_empty());
+    }
+
+    #[test]
+    fn test_to_toml() {
+        let proposal = RuleProposal {
+            name: "make".to_string(),
+            patterns: vec![("make test".to_string(), 5)],
+            total: 6,
+        };
+        let toml_text = proposal.to_toml();
+        assert!(toml_text.starts_with("# make: seen 6 times\n#   make test x5\n"));
+
+        let config: Config = toml::from_str(&toml_text).unwrap();
+        assert_eq!(config.rules[0].commands, ["make test"]);
+    }
+}
@@ -0,0 +1,699 @@
+//! Config linting
+//!
+//! Finds rules that can never take effect or don't mean what they say:
+//! unknown permission values (in rules and the `[git]` table), host rules on
+//! rules that don't check hosts, duplicate and shadowed patterns, invalid cwd
+//! globs, invalid argument matchers and suggestions whose pattern can never
+//! match. Rules are first-match-wins, so a broad rule like `"git"` silently
+//! disables every later `"git ..."` rule.
+//!
+//! The hook runs the checks for problems that change decisions (unknown
+//! permissions, invalid globs and regexes) whenever it loads its config and
+//! prints them as warnings; the `lint` subcommand runs all of them.
+
+use crate::config::{Config, ConfigRegex, GitPermissions, GitRemoteRule, Permission, Rule};
+
+/// A problem found in a config
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintIssue {
+    /// Where the problem is, e.g. `rule #3 "git push"`
+    pub location: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Check a config for problems
+pub fn lint(config: &Config) -> Vec<LintIssue> {
+    check(config, true)
+}
+
+/// Check a config for problems that change decisions: unknown permissions
+/// (treated as passthrough) and invalid globs and regexes
+pub fn validate(config: &Config) -> Vec<LintIssue> {
+    check(config, false)
+}
+
+/// Run the checks, all of them or only those for problems that change decisions
+fn check(config: &Config, all: bool) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    if Permission::parse(&config.default).is_none() {
+        issues.push(LintIssue {
+            location: "default".to_string(),
+            message: format!("unknown permission '{}'", config.default),
+        });
+    }
+
+    if Permission::parse(&config.dynamic_commands).is_none() {
+        issues.push(LintIssue {
+            location: "dynamic_commands".to_string(),
+            message: format!("unknown permission '{}'", config.dynamic_commands),
+        });
+    }
+
+    if Permission::parse(&config.env.permission).is_none() {
+        issues.push(LintIssue {
+            location: "env".to_string(),
+            message: format!("unknown permission '{}'", config.env.permission),
+        });
+    }
+
+    let git = &config.git;
+    lint_git("git", Some(&git.permissions), &git.remotes, &mut issues);
+    for over in &git.overrides {
+        let location = format!("git override \"{}\"", over.cwd);
+        lint_git(
+            &location,
+            over.permissions.as_ref(),
+            &over.remotes,
+            &mut issues,
+        );
+    }
+
+    for (index, rule) in config.rules.iter().enumerate() {
+        lint_rule(config, index, rule, all, &mut issues);
+    }
+    if !all {
+        return issues;
+    }
+
+    // Project rules are meant to override user rules, so shadowing is only
+    // checked within each group
+    let (project, user) = config.rules.split_at(config.project_rule_count());
+    lint_shadowing(config, 0, project, &mut issues);
+    lint_shadowing(config, project.len(), user, &mut issues);
+
+    for (index, suggestion) in config.suggestions.iter().enumerate() {
+        if let Some(ref pattern) = suggestion.pattern
+            && let Some(problem) = suggestion_pattern_problem(&suggestion.command, pattern)
+        {
+            issues.push(LintIssue {
+                location: format!("suggestion #{} \"{}\"", index + 1, suggestion.command),
+                message: problem,
+            });
+        }
+    }
+
+    issues
+}
+
+/// Check the permissions of a `[git]` table or override
+fn lint_git(
+    location: &str,
+    permissions: Option<&GitPermissions>,
+    remotes: &[GitRemoteRule],
+    issues: &mut Vec<LintIssue>,
+) {
+    for (name, permission) in permissions.iter().flat_map(|p| p.named()) {
+        if Permission::parse(permission).is_none() {
+            issues.push(LintIssue {
+                location: format!("{}.permissions.{}", location, name),
+                message: format!("unknown permission '{}'", permission),
+            });
+        }
+    }
+    for remote in remotes {
+        if Permission::parse(&remote.permission).is_none() {
+            issues.push(LintIssue {
+                location: format!("{} remote \"{}\"", location, remote.url),
+                message: format!("unknown permission '{}'", remote.permission),
+            });
+        }
+    }
+}
+
+/// Check a rule's own fields (with `all` false, only those that change decisions)
+fn lint_rule(config: &Config, index: usize, rule: &Rule, all: bool, issues: &mut Vec<LintIssue>) {
+    let location = rule_location(config, index, rule);
+    let is_check_host = rule.permission == "check_host";
+
+    if !is_check_host && Permission::parse(&rule.permission).is_none() {
+        issues.push(LintIssue {
+            location: location.clone(),
+            message: format!(
+                "unknown permission '{}' (treated as passthrough)",
+                rule.permission
+            ),
+        });
+    }
+
+    if all && !is_check_host && !rule.host_rules.is_empty() {
+        issues.push(LintIssue {
+            location: location.clone(),
+            message: "host_rules are ignored unless permission is \"check_host\"".to_string(),
+        });
+    }
+
+    for host_rule in &rule.host_rules {
+        if Permission::parse(&host_rule.permission).is_none() {
+            issues.push(LintIssue {
+                location: location.clone(),
+                message: format!(
+                    "unknown permission '{}' for host '{}'",
+                    host_rule.permission, host_rule.pattern
+                ),
+            });
+        }
+    }
+
+    if all
+        && rule
+            .commands
+            .iter()
+            .any(|c| c.split_whitespace().next().is_none())
+    {
+        issues.push(LintIssue {
+            location: location.clone(),
+            message: "empty pattern never matches".to_string(),
+        });
+    }
+
+    if let Some(ref cwd) = rule.cwd
+        && let Some(problem) = cwd_glob_problem(cwd)
+    {
+        issues.push(LintIssue {
+            location: location.clone(),
+            message: format!("invalid cwd glob '{}': {}", cwd, problem),
+        });
+    }
+
+    if let Some(ref pattern) = rule.args_glob
+        && let Some(problem) = glob_syntax_problem(pattern)
+    {
+        issues.push(LintIssue {
+            location: location.clone(),
+            message: format!("invalid args_glob '{}': {}", pattern, problem),
+        });
+    }
+
+    if let Some(ref pattern) = rule.args_regex
+        && let Some(err) = pattern.error()
+    {
+        issues.push(LintIssue {
+            location,
+            message: format!(
+                "invalid args_regex '{}': {}",
+                pattern.as_str(),
+                regex_error_summary(err)
+            ),
+        });
+    }
+}
+
+/// Report duplicate patterns and rules whose every pattern is matched first
+/// by an earlier rule
+fn lint_shadowing(config: &Config, offset: usize, rules: &[Rule], issues: &mut Vec<LintIssue>) {
+    for (i, rule) in rules.iter().enumerate() {
+        let index = offset + i;
+        let mut shadowed_by = Vec::new();
+
+        for pattern in &rule.commands {
+            let earlier = rules[..i]
+                .iter()
+                .enumerate()
+                .filter(|(_, earlier)| earlier.cwd.is_none() || earlier.cwd == rule.cwd)
+                // Argument matchers make a rule too narrow to reason about
+                .filter(|(_, earlier)| !earlier.has_args_constraints())
+                .flat_map(|(j, earlier)| earlier.commands.iter().map(move |p| (j, p)));
+
+            let mut covering = None;
+            for (j, earlier_pattern) in earlier {
+                if normalize(earlier_pattern) == normalize(pattern) {
+                    covering = Some((j, earlier_pattern, true));
+                    break;
+                }
+                if covering.is_none() && subsumes(earlier_pattern, pattern) {
+                    covering = Some((j, earlier_pattern, false));
+                }
+            }
+
+            match covering {
+                Some((j, earlier_pattern, true)) => {
+                    issues.push(LintIssue {
+                        location: config.rule_match(index, pattern.clone()).to_string(),
+                        message: format!(
+                            "duplicate of {}",
+                            config.rule_match(offset + j, earlier_pattern.clone())
+                        ),
+                    });
+                    shadowed_by.push(None);
+                }
+                Some((j, earlier_pattern, false)) => {
+                    shadowed_by.push(Some(config.rule_match(offset + j, earlier_pattern.clone())))
+                }
+                None => break,
+            }
+        }
+
+        // Only report shadowing when it isn't already explained by duplicates
+        if shadowed_by.len() == rule.commands.len()
+            && let Some(Some(first)) = shadowed_by.iter().find(|s| s.is_some())
+        {
+            issues.push(LintIssue {
+                location: rule_location(config, index, rule),
+                message: format!("never matches: shadowed by earlier {}", first),
+            });
+        }
+    }
+}
+
+/// Location of a rule, named by its first pattern
+fn rule_location(config: &Config, index: usize, rule: &Rule) -> String {
+    let pattern = rule.commands.first().cloned().unwrap_or_default();
+    config.rule_match(index, pattern).to_string()
+}
+
+/// Normalize a pattern for comparison
+fn normalize(pattern: &str) -> Vec<&str> {
+    let mut parts: Vec<&str> = pattern.split_whitespace().collect();
+    if let Some(first) = parts.first_mut() {
+        *first = first.strip_prefix("./").unwrap_or(first);
+    }
+    parts
+}
+
+/// Whether every command matched by `later` is also matched by `earlier`
+///
+/// Mirrors `Config::matches_pattern`: the command name must match, every flag
+/// of `earlier` must be implied by a flag of `later`, and the subcommands of
+/// `earlier` must be a prefix of those of `later`.
+fn subsumes(earlier: &str, later: &str) -> bool {
+    let earlier = normalize(earlier);
+    let later = normalize(later);
+    let (Some((earlier_cmd, earlier_rest)), Some((later_cmd, later_rest))) =
+        (earlier.split_first(), later.split_first())
+    else {
+        return false;
+    };
+
+    // "ls" matches "/bin/ls" by basename, but not the other way around
+    let later_basename = later_cmd.rsplit('/').next().unwrap_or(later_cmd);
+    if earlier_cmd != later_cmd && (earlier_cmd.contains('/') || *earlier_cmd != later_basename) {
+        return false;
+    }
+
+    let (earlier_flags, earlier_subs): (Vec<&str>, Vec<&str>) =
+        earlier_rest.iter().partition(|p| p.starts_with('-'));
+    let (later_flags, later_subs): (Vec<&str>, Vec<&str>) =
+        later_rest.iter().partition(|p| p.starts_with('-'));
+
+    earlier_subs.len() <= later_subs.len()
+        && earlier_subs.iter().zip(&later_subs).all(|(a, b)| a == b)
+        && earlier_flags
+            .iter()
+            .all(|flag| later_flags.iter().any(|l| flag_implies(l, flag)))
+}
+
+/// Whether a command containing flag `present` always satisfies `required`
+fn flag_implies(present: &str, required: &str) -> bool {
+    if present == required {
+        return true;
+    }
+    // -rf contains -r, as in Config::has_flag
+    let chars = required.trim_start_matches('-');
+    !required.starts_with("--")
+        && chars.len() == 1
+        && present.starts_with('-')
+        && !present.starts_with("--")
+        && present.contains(chars)
+}
+
+/// Describe why a cwd glob can never match, if it can't
+fn cwd_glob_problem(pattern: &str) -> Option<String> {
+    if let Some(problem) = glob_syntax_problem(pattern) {
+        return Some(problem);
+    }
+    // The cwd is always compared as an absolute path
+    if !pattern.starts_with(['/', '*', '{']) && !pattern.starts_with("~/") {
+        return Some("relative patterns never match the absolute cwd".to_string());
+    }
+    None
+}
+
+/// Describe why a suggestion pattern can never match, if it can't
+fn suggestion_pattern_problem(command: &str, pattern: &ConfigRegex) -> Option<String> {
+    if let Some(err) = pattern.error() {
+        return Some(format!(
+            "invalid pattern '{}': {}",
+            pattern.as_str(),
+            regex_error_summary(err)
+        ));
+    }
+    let pattern = pattern.as_str();
+
+    // Suggestions only apply to command lines starting with `command`, so the
+    // literal prefix of an anchored pattern has to agree with it
+    let anchored = pattern.strip_prefix('^')?;
+    let literal_end = anchored
+        .find(|c: char| "\\.+*?()|[]{}^$".contains(c))
+        .unwrap_or(anchored.len());
+    let mut literal = &anchored[..literal_end];
+    // A quantifier applies to the last literal character
+    if anchored[literal_end..].starts_with(['*', '?', '{'])
+        && let Some((i, _)) = literal.char_indices().last()
+    {
+        literal = &literal[..i];
+    }
+    if !literal.starts_with(command) && !command.starts_with(literal) {
+        return Some(format!(
+            "pattern '{}' never matches commands starting with '{}'",
+            pattern, command
+        ));
+    }
+    None
+}
+
+/// Last line of a regex error, which holds the actual message
+fn regex_error_summary(err: &regex::Error) -> String {
+    let text = err.to_string();
+    text.lines().last().unwrap_or(&text).trim().to_string()
+}
+
+/// Check glob syntax: balanced brackets and braces, no trailing escape
+pub fn glob_syntax_problem(pattern: &str) -> Option<String> {
+    let mut chars = pattern.chars();
+    let mut in_class = false;
+    let mut brace_depth = 0usize;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.next().is_none() => {
+                return Some("trailing backslash".to_string());
+            }
+            '[' if !in_class => in_class = true,
+            ']' if in_class => in_class = false,
+            '{' if !in_class => brace_depth += 1,
+            '}' if !in_class && brace_depth == 0 => {
+                return Some("unmatched '}'".to_string());
+            }
+            '}' if !in_class => brace_depth -= 1,
+            _ => {}
+        }
+    }
+
+    if in_class {
+        Some("unclosed '['".to_string())
+    } else if brace_depth > 0 {
+        Some("unclosed '{'".to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint_toml(content: &str) -> Vec<String> {
+        let config: Config = toml::from_str(content).unwrap();
+        lint(&config).iter().map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn test_default_config_is_clean() {
+        let issues: Vec<String> = lint(&Config::default())
+            .iter()
+            .map(|i| i.to_string())
+            .collect();
+        assert!(issues.is_empty(), "{:#?}", issues);
+    }
+
+    #[test]
+    fn test_unknown_permission() {
+        let issues = lint_toml(
+            r#"
+            default = "alow"
+
+            [env]
+            permission = "denny"
+
+            [[rules]]
+            commands = ["ls"]
+            permission = "alow"
+            "#,
+        );
+        assert_eq!(
+            issues,
+            [
+                "default: unknown permission 'alow'",
+                "env: unknown permission 'denny'",
+                "rule #1 \"ls\": unknown permission 'alow' (treated as passthrough)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_git_permission() {
+        let issues = lint_toml(
+            r#"
+            [git.permissions]
+            force = "nope"
+
+            [[git.remotes]]
+            url = "github.com:me/*"
+            permission = "alow"
+
+            [[git.overrides]]
+            cwd = "~/work/**"
+            permissions = { delete = "dney" }
+            "#,
+        );
+        assert_eq!(
+            issues,
+            [
+                "git.permissions.force: unknown permission 'nope'",
+                "git remote \"github.com:me/*\": unknown permission 'alow'",
+                "git override \"~/work/**\".permissions.delete: unknown permission 'dney'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_host_rules_without_check_host() {
+        let issues = lint_toml(
+            r#"
+            [[rules]]
+            commands = ["ssh"]
+            permission = "allow"
+            host_rules = [{ pattern = "prod-*", permission = "deny" }]
+            "#,
+        );
+        assert_eq!(
+            issues,
+            ["rule #1 \"ssh\": host_rules are ignored unless permission is \"check_host\""]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_pattern() {
+        let issues = lint_toml(
+            r#"
+            [[rules]]
+            commands = ["ls", "cat"]
+            permission = "allow"
+
+            [[rules]]
+            commands = ["cat"]
+            permission = "ask"
+            "#,
+        );
+        assert_eq!(issues, ["rule #2 \"cat\": duplicate of rule #1 \"cat\""]);
+    }
+
+    #[test]
+    fn test_shadowed_rule() {
+        let issues = lint_toml(
+            r#"
+            [[rules]]
+            commands = ["git"]
+            permission = "allow"
+
+            [[rules]]
+            commands = ["git push --force", "git push -f"]
+            permission = "deny"
+            "#,
+        );
+        assert_eq!(
+            issues,
+            ["rule #2 \"git push --force\": never matches: shadowed by earlier rule #1 \"git\""]
+        );
+    }
+
+    #[test]
+    fn test_partially_shadowed_rule_not_reported() {
+        let issues = lint_toml(
+            r#"
+            [[rules]]
+            commands = ["rm -r"]
+            permission = "ask"
+
+            [[rules]]
+            commands = ["rm -rf", "rm"]
+            permission = "allow"
+            "#,
+        );
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_cwd_restricted_rule_does_not_shadow() {
+        let issues = lint_toml(
+            r#"
+            [[rules]]
+            commands = ["make"]
+            permission = "allow"
+            cwd = "/home/me/project"
+
+            [[rules]]
+            commands = ["make install"]
+            permission = "ask"
+            "#,
+        );
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_subsumes() {
+        assert!(subsumes("git push", "git push --force origin"));
+        assert!(subsumes("rm -r", "rm -rf"));
+        assert!(subsumes("ls", "/bin/ls"));
+        assert!(!subsumes("/bin/ls", "ls"));
+        assert!(!subsumes("git push", "git pull"));
+        assert!(!subsumes("rm -rf", "rm -r"));
+        assert!(!subsumes("git --force", "git -f"));
+    }
+
+    #[test]
+    fn test_invalid_cwd_glob() {
+        let issues = lint_toml(
+            r#"
+            [[rules]]
+            commands = ["make"]
+            permission = "allow"
+            cwd = "/home/me/{a,b"
+
+            [[rules]]
+            commands = ["cargo"]
+            permission = "allow"
+            cwd = "Projects/*"
+            "#,
+        );
+        assert_eq!(
+            issues,
+            [
+                "rule #1 \"make\": invalid cwd glob '/home/me/{a,b': unclosed '{'",
+                "rule #2 \"cargo\": invalid cwd glob 'Projects/*': relative patterns never match the absolute cwd"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_suggestion_pattern_never_matches() {
+        let issues = lint_toml(
+            r#"
+            [[suggestions]]
+            command = "git checkout"
+            message = "use git switch"
+            pattern = "^git switch"
+
+            [[suggestions]]
+            command = "git checkout"
+            message = "use git restore"
+            pattern = "^git checkout -- "
+
+            [[suggestions]]
+            command = "git checkout"
+            message = "use git switch -c"
+            pattern = " -b "
+            "#,
+        );
+        assert_eq!(
+            issues,
+            [
+                "suggestion #1 \"git checkout\": pattern '^git switch' never matches commands starting with 'git checkout'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_args_matchers() {
+        let issues = lint_toml(
+            r#"
+            [[rules]]
+            commands = ["kubectl"]
+            permission = "ask"
+            args_regex = "-n (prod"
+
+            [[rules]]
+            commands = ["curl"]
+            permission = "allow"
+            args_glob = "-s https://api.internal/[a-z"
+
+            [[suggestions]]
+            command = "git checkout"
+            message = "use git switch"
+            pattern = "checkout ("
+            "#,
+        );
+        assert_eq!(issues.len(), 3, "{:#?}", issues);
+        assert!(issues[0].starts_with("rule #1 \"kubectl\": invalid args_regex '-n (prod': "));
+        assert_eq!(
+            issues[1],
+            "rule #2 \"curl\": invalid args_glob '-s https://api.internal/[a-z': unclosed '['"
+        );
+        assert!(issues[2].starts_with("suggestion #1 \"git checkout\": invalid pattern"));
+    }
+
+    #[test]
+    fn test_args_constrained_rule_does_not_shadow() {
+        let issues = lint_toml(
+            r#"
+            [[rules]]
+            commands = ["kubectl"]
+            permission = "ask"
+            args_regex = "(^| )-n prod( |$)"
+
+            [[rules]]
+            commands = ["kubectl get"]
+            permission = "allow"
+            "#,
+        );
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_validate_reports_only_decision_problems() {
+        let config: Config = toml::from_str(
+            r#"
+            [[rules]]
+            commands = ["git"]
+            permission = "alow"
+
+            [[rules]]
+            commands = ["git push"]
+            permission = "ask"
+            host_rules = [{ pattern = "*", permission = "allow" }]
+
+            [[rules]]
+            commands = ["kubectl"]
+            permission = "deny"
+            args_regex = "(^| )-n prod( |$"
+            "#,
+        )
+        .unwrap();
+        let issues: Vec<String> = validate(&config).iter().map(|i| i.to_string()).collect();
+        assert_eq!(issues.len(), 2, "{:#?}", issues);
+        assert_eq!(
+            issues[0],
+            "rule #1 \"git\": unknown permission 'alow' (treated as passthrough)"
+        );
+        assert!(issues[1].starts_with("rule #3 \"kubectl\": invalid args_regex"));
+        // Shadowing and ignored host rules are left to `lint`
+        assert!(lint(&config).len() > issues.len());
+    }
+}
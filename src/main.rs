@@ -4,19 +4,39 @@
 
 mod advice;
 mod analyzer;
+mod assign;
+mod audit;
+mod cli;
 mod config;
+mod cwd;
 mod docker;
+mod expand;
+mod fish;
 mod git;
+mod learn;
+mod lint;
 mod nushell;
+mod paths;
+mod policy;
+mod quote;
+mod redirect;
 mod rm;
+mod script;
 mod sql;
+mod stdin;
 mod tar;
 mod tee;
+#[cfg(test)]
+mod test_util;
+mod trace;
 mod wrappers;
 
-use config::{Config, Permission, PermissionResult};
+use config::{Config, Permission, PermissionResult, RuleMatch};
 use serde::{Deserialize, Serialize};
 use std::io::{self, Read};
+use std::path::PathBuf;
+use stdin::PipedOperands;
+use trace::Trace;
 
 /// Input from Claude Code hook
 #[derive(Debug, Deserialize)]
@@ -60,6 +80,12 @@ struct HookSpecificOutput {
 }
 
 fn main() {
+    // Subcommands (check, ...) for use outside the hook
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
     // Read input from stdin
     let mut input = String::new();
     if let Err(e) = io::stdin().read_to_string(&mut input) {
@@ -93,39 +119,66 @@ fn main() {
         }
     };
 
-    // Load config
-    let config = Config::load_or_default();
+    // Load config, overlaying the project config found from the session cwd
+    let config = Config::load_for_cwd(
+        hook_input
+            .cwd
+            .as_deref()
+            .or(hook_input.tool_input.cwd.as_deref()),
+    );
     let edit_mode = edits_allowed(hook_input.permission_mode.as_deref());
 
     // Analyze the command (bash or nushell)
+    let mut trace = Trace::default();
     let result = if is_nushell {
-        analyze_nushell_command(
+        analyze_nushell_command_traced(
             &command,
             &config,
             edit_mode,
             hook_input.tool_input.cwd.as_deref(),
+            &mut trace,
         )
     } else {
-        analyze_command(&command, &config, edit_mode, hook_input.cwd.as_deref())
+        analyze_command_traced(
+            &command,
+            &config,
+            edit_mode,
+            hook_input.cwd.as_deref(),
+            &mut trace,
+        )
     };
 
-    // For "passthrough" permission on Bash, let Claude Code's built-in system handle it
     // For nushell MCP, there's no built-in permission system, so ask explicitly
-    let result = if result.permission == Permission::Passthrough {
-        if is_nushell {
-            PermissionResult {
-                permission: Permission::Ask,
-                reason: result.reason,
-                suggestion: result.suggestion,
-            }
-        } else {
-            // Bash: let Claude Code handle it
-            return;
+    let result = if result.permission == Permission::Passthrough && is_nushell {
+        PermissionResult {
+            permission: Permission::Ask,
+            reason: result.reason,
+            suggestion: result.suggestion,
         }
     } else {
         result
     };
 
+    if config.audit.enabled {
+        let entry = audit::AuditEntry::new(
+            &hook_input.tool_name,
+            &command,
+            hook_input.cwd.as_deref(),
+            hook_input.permission_mode.as_deref(),
+            result.permission,
+            &result.reason,
+            &trace,
+        );
+        if let Err(e) = audit::record(&config.audit, &entry) {
+            eprintln!("Warning: {}", e);
+        }
+    }
+
+    // For "passthrough" permission on Bash, let Claude Code's built-in system handle it
+    if result.permission == Permission::Passthrough {
+        return;
+    }
+
     // Build reason, optionally with AI advice
     let reason = if config.enable_advice
         && matches!(result.permission, Permission::Ask | Permission::Deny)
@@ -144,12 +197,7 @@ fn main() {
     let output = HookOutput {
         hook_output: HookSpecificOutput {
             event_name: "PreToolUse".to_string(),
-            decision: match result.permission {
-                Permission::Allow => "allow".to_string(),
-                Permission::Passthrough => unreachable!(),
-                Permission::Ask => "ask".to_string(),
-                Permission::Deny => "deny".to_string(),
-            },
+            decision: result.permission.as_str().to_string(),
             reason,
         },
     };
@@ -161,12 +209,94 @@ fn main() {
 }
 
 /// Analyze a command and return the most restrictive permission
+#[cfg(test)]
 fn analyze_command(
     command: &str,
     config: &Config,
     edit_mode: bool,
     initial_cwd: Option<&str>,
 ) -> PermissionResult {
+    analyze_command_traced(
+        command,
+        config,
+        edit_mode,
+        initial_cwd,
+        &mut Trace::default(),
+    )
+}
+
+/// Analyze a command, recording how the decision was reached
+fn analyze_command_traced(
+    command: &str,
+    config: &Config,
+    edit_mode: bool,
+    initial_cwd: Option<&str>,
+    trace: &mut Trace,
+) -> PermissionResult {
+    let start = start_dir(initial_cwd);
+    let location = Location {
+        project_dir: initial_cwd,
+        cwd: start.as_deref(),
+        stdin_roots: None,
+        piped: None,
+        depth: 0,
+        script_depth: 0,
+        written: &[],
+        assigned: &[],
+        language: script::Language::Bash,
+    };
+    analyze_command_at(command, config, edit_mode, location, trace)
+}
+
+/// Directories a command is decided against
+#[derive(Debug, Clone, Copy)]
+struct Location<'a> {
+    /// Directory the session started in, for project-based rules and allowances
+    project_dir: Option<&'a str>,
+    /// Directory the command runs in, None when it can't be known statically
+    cwd: Option<&'a str>,
+    /// Directories bounding the paths piped into the command (`find /tmp/x | ...`)
+    stdin_roots: Option<&'a [String]>,
+    /// Operands the command receives from xargs, parallel or a `while read` loop
+    piped: Option<&'a PipedOperands>,
+    /// Number of wrappers the command is nested in (`sudo bash -c "..."` is 2)
+    depth: usize,
+    /// Number of inspected scripts the command runs in (scripts running scripts)
+    script_depth: usize,
+    /// Files written by the commands that ran before it, which can't be inspected
+    /// as scripts ahead of time (`echo ... > x.sh && bash x.sh`)
+    written: &'a [PathBuf],
+    /// Variables the command line sets, whose values aren't the environment's
+    /// (`TMPDIR=/etc; rm -f "$TMPDIR/x"`)
+    assigned: &'a [String],
+    /// Language the command is written in
+    language: script::Language,
+}
+
+/// Wrappers nested deeper than this (`bash -c "bash -c \"...\""`) are asked about
+const MAX_WRAPPER_DEPTH: usize = 16;
+
+/// The directory a command line starts in: the session cwd, or the process cwd
+fn start_dir(initial_cwd: Option<&str>) -> Option<String> {
+    initial_cwd.map(String::from).or_else(|| {
+        std::env::current_dir()
+            .ok()
+            .and_then(|dir| dir.to_str().map(String::from))
+    })
+}
+
+/// Analyze a bash command line running in `location`
+fn analyze_command_at(
+    command: &str,
+    config: &Config,
+    edit_mode: bool,
+    location: Location,
+    trace: &mut Trace,
+) -> PermissionResult {
+    let location = Location {
+        language: script::Language::Bash,
+        ..location
+    };
     let analysis = analyzer::analyze(command);
 
     if !analysis.success {
@@ -177,6 +307,22 @@ fn analyze_command(
         };
     }
 
+    if let Some(name) = analysis.recursive_function {
+        return PermissionResult {
+            permission: Permission::Deny,
+            reason: format!("function '{}' calls itself (fork bomb)", name),
+            suggestion: None,
+        };
+    }
+
+    if analysis.expansion_limited {
+        return PermissionResult {
+            permission: Permission::Ask,
+            reason: "function calls expand to too many commands to check".to_string(),
+            suggestion: None,
+        };
+    }
+
     if analysis.commands.is_empty() {
         return PermissionResult {
             permission: Permission::Allow,
@@ -185,54 +331,134 @@ fn analyze_command(
         };
     }
 
-    // Track virtual cwd through command chain (for cd /tmp/claude && tar -xf ...)
-    // Only trust virtual_cwd if control flow is predictable (no conditionals)
-    let has_uncertain_flow = command.contains(" || ")
-        || command.contains("if ")
-        || command.contains("case ")
-        || command.contains("while ")
-        || command.contains("for ")
-        || command.contains("until ");
-
-    let mut virtual_cwd: Option<String> = initial_cwd.map(String::from);
+    // Follow cd/pushd/popd through the control flow (for cd /tmp/claude && tar -xf ...)
+    let dirs = cwd::command_dirs(&analysis, location.cwd);
 
     // Check each command and return the most restrictive result
-    let mut most_restrictive = PermissionResult::default();
-    most_restrictive.permission = Permission::Allow;
+    let mut most_restrictive = PermissionResult {
+        permission: Permission::Allow,
+        ..Default::default()
+    };
+    let mut most_restrictive_text = String::new();
+    let mut most_restrictive_handler = String::new();
+
+    let mut assigned = location.assigned.to_vec();
+    assigned.extend(analysis.assigned.iter().cloned());
+    let mut written = location.written.to_vec();
+    for (idx, (cmd, dir)) in analysis.commands.iter().zip(&dirs).enumerate() {
+        // A command reading a pipe gets the producer's paths; otherwise it
+        // shares the stdin of the wrapper it runs in
+        let piped_in = cmd.stdin.as_ref().is_some_and(|s| s.piped);
+        let roots = stdin::producer_roots(&analysis.commands, &dirs, &assigned, idx);
+        let stdin_roots = if piped_in {
+            roots.as_deref()
+        } else {
+            location.stdin_roots
+        };
+        let read_loop = cmd
+            .stdin
+            .as_ref()
+            .and_then(|s| PipedOperands::from_read_loop(s, stdin_roots));
+        let location = Location {
+            cwd: dir.as_deref(),
+            stdin_roots,
+            piped: read_loop.as_ref().or(location.piped),
+            written: &written,
+            assigned: &assigned,
+            ..location
+        };
+        trace.enter_command(cmd);
+        let result = check_single_command(cmd, config, edit_mode, location, trace);
+
+        if idx == 0 || result.permission > most_restrictive.permission {
+            most_restrictive = result;
+            most_restrictive_text = cmd.text.clone();
+            most_restrictive_handler = trace.last_handler();
+        }
 
-    for cmd in &analysis.commands {
-        let result = check_single_command(
+        // Output redirects are file writes, checked independently of the command itself
+        if let Some(redirect_result) = redirect::check_redirects(
             cmd,
-            config,
             edit_mode,
-            virtual_cwd.as_deref(),
-            initial_cwd,
-            has_uncertain_flow,
-        );
-
-        if result.permission > most_restrictive.permission {
-            most_restrictive = result;
+            location.cwd,
+            location.assigned,
+            location.project_dir,
+        ) {
+            trace.decision("redirect::check_redirects", &redirect_result);
+            if redirect_result.permission > most_restrictive.permission {
+                most_restrictive = redirect_result;
+                most_restrictive_text = cmd.text.clone();
+                most_restrictive_handler = trace.last_handler();
+            }
         }
 
-        // Track cd commands to update virtual cwd for subsequent commands
-        // (only if flow is predictable)
-        if !has_uncertain_flow && cmd.name == "cd" {
-            if let Some(dir) = cmd.args.first() {
-                virtual_cwd = Some(dir.clone());
+        // Risky environment variables change what the command (or later ones) run
+        if let Some(assign_result) = assign::check_assignments(cmd, config) {
+            trace.decision("assign::check_assignments", &assign_result);
+            if assign_result.permission > most_restrictive.permission {
+                most_restrictive = assign_result;
+                most_restrictive_text = cmd.text.clone();
+                most_restrictive_handler = trace.last_handler();
             }
         }
+        trace.leave_command();
+        written.extend(script::written_paths(cmd, dir.as_deref()));
     }
 
+    trace.most_restrictive(
+        &most_restrictive_text,
+        most_restrictive.permission,
+        &most_restrictive_handler,
+    );
     most_restrictive
 }
 
 /// Analyze a nushell command and return the most restrictive permission
+#[cfg(test)]
 fn analyze_nushell_command(
     command: &str,
     config: &Config,
     edit_mode: bool,
     cwd: Option<&str>,
 ) -> PermissionResult {
+    analyze_nushell_command_traced(command, config, edit_mode, cwd, &mut Trace::default())
+}
+
+/// Analyze a nushell command, recording how the decision was reached
+fn analyze_nushell_command_traced(
+    command: &str,
+    config: &Config,
+    edit_mode: bool,
+    cwd: Option<&str>,
+    trace: &mut Trace,
+) -> PermissionResult {
+    let start = start_dir(cwd);
+    let location = Location {
+        project_dir: cwd,
+        cwd: start.as_deref(),
+        stdin_roots: None,
+        piped: None,
+        depth: 0,
+        script_depth: 0,
+        written: &[],
+        assigned: &[],
+        language: script::Language::Nushell,
+    };
+    analyze_nushell_command_at(command, config, edit_mode, location, trace)
+}
+
+/// Analyze a nushell command running in `location`
+fn analyze_nushell_command_at(
+    command: &str,
+    config: &Config,
+    edit_mode: bool,
+    location: Location,
+    trace: &mut Trace,
+) -> PermissionResult {
+    let location = Location {
+        language: script::Language::Nushell,
+        ..location
+    };
     let analysis = nushell::analyze(command);
 
     if !analysis.success {
@@ -256,18 +482,130 @@ fn analyze_nushell_command(
     }
 
     // Check each external command against the same rules as bash
-    let mut most_restrictive = PermissionResult::default();
-    most_restrictive.permission = Permission::Allow;
+    check_commands(&analysis.commands, config, edit_mode, location, trace)
+}
+
+/// Analyze a fish command running in `location`
+fn analyze_fish_command_at(
+    command: &str,
+    config: &Config,
+    edit_mode: bool,
+    location: Location,
+    trace: &mut Trace,
+) -> PermissionResult {
+    let location = Location {
+        language: script::Language::Fish,
+        ..location
+    };
+    let analysis = fish::analyze(command);
+
+    if !analysis.success {
+        return PermissionResult {
+            permission: Permission::Deny,
+            reason: format!("Fish syntax error: {}", analysis.error.unwrap_or_default()),
+            suggestion: Some("Fix the syntax error and try again".to_string()),
+        };
+    }
+
+    if analysis.commands.is_empty() {
+        return PermissionResult {
+            permission: Permission::Allow,
+            reason: "No commands found".to_string(),
+            suggestion: None,
+        };
+    }
+
+    check_commands(&analysis.commands, config, edit_mode, location, trace)
+}
+
+/// Analyze a command string in `language` (`nu -c`, `fish -c`, a script)
+fn analyze_language_at(
+    language: script::Language,
+    command: &str,
+    config: &Config,
+    edit_mode: bool,
+    location: Location,
+    trace: &mut Trace,
+) -> PermissionResult {
+    match language {
+        script::Language::Bash => analyze_command_at(command, config, edit_mode, location, trace),
+        script::Language::Nushell => {
+            analyze_nushell_command_at(command, config, edit_mode, location, trace)
+        }
+        script::Language::Fish => {
+            analyze_fish_command_at(command, config, edit_mode, location, trace)
+        }
+    }
+}
 
-    for cmd in &analysis.commands {
-        // For nushell, cwd is both virtual and initial (no cd tracking)
-        let result = check_single_command(cmd, config, edit_mode, cwd, cwd, false);
+/// Check commands from a parser without control flow (nushell, fish), all in
+/// `location`, and return the most restrictive result
+fn check_commands(
+    commands: &[analyzer::Command],
+    config: &Config,
+    edit_mode: bool,
+    location: Location,
+    trace: &mut Trace,
+) -> PermissionResult {
+    let mut most_restrictive = PermissionResult {
+        permission: Permission::Allow,
+        ..Default::default()
+    };
+    let mut most_restrictive_text = String::new();
+    let mut most_restrictive_handler = String::new();
+
+    let cwd = location.cwd;
+    let mut assigned = location.assigned.to_vec();
+    assigned.extend(commands.iter().flat_map(analyzer::set_variables));
+    let mut written = location.written.to_vec();
+    for (idx, cmd) in commands.iter().enumerate() {
+        // No cd tracking
+        let location = Location {
+            written: &written,
+            assigned: &assigned,
+            ..location
+        };
+        trace.enter_command(cmd);
+        let result = check_single_command(cmd, config, edit_mode, location, trace);
 
-        if result.permission > most_restrictive.permission {
+        if idx == 0 || result.permission > most_restrictive.permission {
             most_restrictive = result;
+            most_restrictive_text = cmd.text.clone();
+            most_restrictive_handler = trace.last_handler();
+        }
+
+        // Fish redirects and `set` assignments, checked as for bash
+        if let Some(redirect_result) = redirect::check_redirects(
+            cmd,
+            edit_mode,
+            location.cwd,
+            location.assigned,
+            location.project_dir,
+        ) {
+            trace.decision("redirect::check_redirects", &redirect_result);
+            if redirect_result.permission > most_restrictive.permission {
+                most_restrictive = redirect_result;
+                most_restrictive_text = cmd.text.clone();
+                most_restrictive_handler = trace.last_handler();
+            }
+        }
+        if let Some(assign_result) = assign::check_assignments(cmd, config) {
+            trace.decision("assign::check_assignments", &assign_result);
+            if assign_result.permission > most_restrictive.permission {
+                most_restrictive = assign_result;
+                most_restrictive_text = cmd.text.clone();
+                most_restrictive_handler = trace.last_handler();
+            }
         }
+        trace.leave_command();
+        written.extend(script::written_paths(cmd, cwd));
     }
 
+    trace.most_restrictive(
+        &most_restrictive_text,
+        most_restrictive.permission,
+        &most_restrictive_handler,
+    );
     most_restrictive
 }
 
@@ -276,19 +614,123 @@ fn check_single_command(
     cmd: &analyzer::Command,
     config: &Config,
     edit_mode: bool,
-    virtual_cwd: Option<&str>,
-    initial_cwd: Option<&str>,
-    has_uncertain_flow: bool,
+    location: Location,
+    trace: &mut Trace,
 ) -> PermissionResult {
+    let (result, handler) = decide_single_command(cmd, config, edit_mode, location, trace);
+    trace.decision(&handler, &result);
+    result
+}
+
+/// Decide a single command, returning the result and the rule or handler that decided it
+fn decide_single_command(
+    cmd: &analyzer::Command,
+    config: &Config,
+    edit_mode: bool,
+    location: Location,
+    trace: &mut Trace,
+) -> (PermissionResult, String) {
+    // Standalone assignments only set variables; their values were analyzed as
+    // separate commands and risky variables are checked by assign::check_assignments
+    if cmd.is_assignment_only() {
+        return (
+            PermissionResult {
+                permission: Permission::Allow,
+                reason: "variable assignment".to_string(),
+                suggestion: None,
+            },
+            "assignment check".to_string(),
+        );
+    }
+
+    // A command name from an expansion can't be matched against rules
+    if cmd.dynamic_name {
+        return (
+            PermissionResult {
+                permission: Permission::parse(&config.dynamic_commands).unwrap_or(Permission::Ask),
+                reason: format!("command name '{}' is only known at runtime", cmd.name),
+                suggestion: None,
+            },
+            "dynamic command name".to_string(),
+        );
+    }
+
     // Check if this is a wrapper command
-    if let Some(unwrap_result) = wrappers::unwrap_command(cmd, config) {
+    if let Some(mut unwrap_result) = wrappers::unwrap_command(cmd, config) {
+        trace.unwrap(
+            &unwrap_result.wrapper,
+            unwrap_result.inner_command.as_deref(),
+            unwrap_result.host.as_deref(),
+        );
+
+        // Scripts run by a shell or sourced are decided by their contents (opt-in)
+        if let Some(ref path) = unwrap_result.script
+            && let Some(result) = check_script(path, config, edit_mode, location, trace)
+        {
+            return (result, format!("script {}", path));
+        }
+
+        // Fish substitutions and variables can't be passed on as bash words
+        if location.language == script::Language::Fish
+            && unwrap_result.language == script::Language::Bash
+            && cmd.dynamic_args.iter().any(|&dynamic| dynamic)
+        {
+            unwrap_result.dynamic = true;
+        }
+
+        // A command string built from expansions can't be analyzed before it runs
+        if unwrap_result.dynamic {
+            return (
+                PermissionResult {
+                    permission: Permission::parse(&config.dynamic_commands)
+                        .unwrap_or(Permission::Ask),
+                    reason: format!(
+                        "{} runs a command only known at runtime",
+                        unwrap_result.wrapper
+                    ),
+                    suggestion: None,
+                },
+                format!("wrapper {}", unwrap_result.wrapper),
+            );
+        }
+
         // If there's an inner command, recursively analyze it
         if let Some(ref inner) = unwrap_result.inner_command {
-            let inner_result = analyze_command(inner, config, edit_mode, virtual_cwd);
+            if location.depth >= MAX_WRAPPER_DEPTH {
+                return (
+                    PermissionResult {
+                        permission: Permission::Ask,
+                        reason: format!("wrappers nested more than {} deep", MAX_WRAPPER_DEPTH),
+                        suggestion: None,
+                    },
+                    format!("wrapper {}", unwrap_result.wrapper),
+                );
+            }
+            // xargs/parallel hand the inner command operands from their input
+            let mut piped = unwrap_result.piped.clone();
+            if let Some(piped) = &mut piped
+                && piped.from_stdin
+            {
+                piped.roots = location.stdin_roots.map(<[String]>::to_vec);
+            }
+            // The inner command runs where the wrapper does
+            let location = Location {
+                piped: piped.as_ref().or(location.piped),
+                depth: location.depth + 1,
+                ..location
+            };
+            let inner_result = analyze_language_at(
+                unwrap_result.language,
+                inner,
+                config,
+                edit_mode,
+                location,
+                trace,
+            );
 
             // For SSH with host, check host rules too
             if unwrap_result.host.is_some() {
-                let host_result = config.check_command_with_host(
+                let (host_result, rule) = config.explain_command_with_host(
                     &cmd.name,
                     &cmd.args,
                     unwrap_result.host.as_deref(),
@@ -296,94 +738,111 @@ fn check_single_command(
 
                 // Return the more restrictive of host check and inner command check
                 if host_result.permission > inner_result.permission {
-                    return host_result;
+                    return (host_result, rule_label(rule));
                 }
             }
 
-            return inner_result;
+            return (
+                inner_result,
+                format!("wrapper {} (inner command)", unwrap_result.wrapper),
+            );
         } else if unwrap_result.host.is_some() {
             // Wrapper with host but no inner command (like scp)
-            return config.check_command_with_host(
+            let (host_result, rule) = config.explain_command_with_host(
                 &cmd.name,
                 &cmd.args,
                 unwrap_result.host.as_deref(),
             );
+            return (host_result, rule_label(rule));
         }
     }
 
-    // Special handling for sed -i (in-place edit)
-    if cmd.name == "sed" && cmd.args.iter().any(|a| a == "-i" || a.starts_with("-i")) {
-        if !edit_mode {
-            return PermissionResult {
-                permission: Permission::Ask,
-                reason: "sed -i modifies files (not in edit mode)".to_string(),
-                suggestion: None,
-            };
+    match Handler::of(cmd) {
+        // Special handling for sed -i (in-place edit)
+        Some(Handler::SedInPlace) if !edit_mode => {
+            return (
+                PermissionResult {
+                    permission: Permission::Ask,
+                    reason: "sed -i modifies files (not in edit mode)".to_string(),
+                    suggestion: None,
+                },
+                "sed -i check".to_string(),
+            );
         }
-    }
+        Some(Handler::Sql) => {
+            // Special handling for mysql/mariadb - allow read-only queries
+            if sql::MYSQL_COMMANDS.contains(&cmd.name.as_str())
+                && let Some(result) = sql::check_mysql_query(cmd)
+            {
+                return (result, "sql::check_mysql_query".to_string());
+            }
 
-    // Special handling for mysql/mariadb - allow read-only queries
-    if matches!(
-        cmd.name.as_str(),
-        "mysql"
-            | "mariadb"
-            | "mysql-prod"
-            | "mysql-prod-root"
-            | "mysql-external"
-            | "mysql-replication"
-    ) {
-        if let Some(result) = sql::check_mysql_query(cmd) {
-            return result;
-        }
-    }
+            // Special handling for sqlite3 - allow read-only queries
+            if cmd.name == "sqlite3"
+                && let Some(result) = sql::check_sqlite3_query(cmd)
+            {
+                return (result, "sql::check_sqlite3_query".to_string());
+            }
 
-    // Special handling for sqlite3 - allow read-only queries
-    if cmd.name == "sqlite3" {
-        if let Some(result) = sql::check_sqlite3_query(cmd) {
-            return result;
+            // Queries read from a file (`sqlite3 db < query.sql`), inspected like scripts
+            if let Some(analyzer::Input::File(path)) = cmd.input()
+                && let Some(result) = check_sql_file(path, config, location)
+            {
+                return (result, format!("sql file {}", path));
+            }
         }
-    }
+        Some(Handler::Git) => {
+            // Special handling for git push - check every ref it changes
+            if let Some(result) = git::check_git_push(cmd, location.cwd, location.assigned, config)
+            {
+                return (result, "git::check_git_push".to_string());
+            }
 
-    // Special handling for git push - check target branch
-    if cmd.name == "git" && cmd.args.first().map(|s| s.as_str()) == Some("push") {
-        if let Some(result) = git::check_git_push(cmd) {
-            return result;
-        }
-    }
+            // Destructive git commands - decided by what they would lose in the repository
+            if let Some(result) =
+                git::check_git_destructive(cmd, location.cwd, location.assigned, config)
+            {
+                return (result, "git::check_git_destructive".to_string());
+            }
 
-    // Special handling for git checkout - allow -b, ask for others
-    if cmd.name == "git" && cmd.args.first().map(|s| s.as_str()) == Some("checkout") {
-        if let Some(result) = git::check_git_checkout(cmd) {
-            return result;
+            // Special handling for git checkout - allow -b, ask for others
+            if let Some(result) = git::check_git_checkout(cmd) {
+                return (result, "git::check_git_checkout".to_string());
+            }
         }
-    }
-
-    // Special handling for docker run - allow if no rw bind mounts
-    if cmd.name == "docker" && cmd.args.first().map(|s| s.as_str()) == Some("run") {
-        if let Some(result) = docker::check_docker_run(cmd) {
-            return result;
+        // Special handling for docker run - allow if no rw bind mounts
+        Some(Handler::DockerRun) => {
+            if let Some(result) = docker::check_docker_run(cmd) {
+                return (result, "docker::check_docker_run".to_string());
+            }
         }
-    }
-
-    // Special handling for rm - allow deletion under /tmp/ or project dir
-    if cmd.name == "rm" {
-        if let Some(result) = rm::check_rm(cmd, initial_cwd) {
-            return result;
+        // Special handling for rm - allow deletion under /tmp/ or project dir
+        Some(Handler::Rm) => {
+            if let Some(result) = rm::check_rm(
+                cmd,
+                location.cwd,
+                location.assigned,
+                location.project_dir,
+                location.piped,
+            ) {
+                return (result, "rm::check_rm".to_string());
+            }
         }
-    }
-
-    // Special handling for tee - allow writing to /tmp/ or /tmp/claude/ based on project
-    if cmd.name == "tee" {
-        if let Some(result) = tee::check_tee(cmd, initial_cwd) {
-            return result;
+        // Special handling for tee - allow writing to /tmp/ or /tmp/claude/ based on project
+        Some(Handler::Tee) => {
+            if let Some(result) =
+                tee::check_tee(cmd, location.cwd, location.assigned, location.piped)
+            {
+                return (result, "tee::check_tee".to_string());
+            }
         }
-    }
-
-    // Special handling for tar - allow extraction to /tmp/claude/
-    if cmd.name == "tar" {
-        if let Some(result) = tar::check_tar(cmd, virtual_cwd, has_uncertain_flow) {
-            return result;
+        // Special handling for tar - allow extraction to /tmp/claude/
+        Some(Handler::Tar) => {
+            if let Some(result) = tar::check_tar(cmd, location.cwd, location.assigned) {
+                return (result, "tar::check_tar".to_string());
+            }
         }
+        _ => {}
     }
 
     // Special handling for --help and --version - always allow
@@ -392,35 +851,197 @@ fn check_single_command(
         .iter()
         .any(|a| a == "--help" || a == "-h" || a == "help")
     {
-        return PermissionResult {
-            permission: Permission::Allow,
-            reason: "help request".to_string(),
-            suggestion: None,
-        };
+        return (
+            PermissionResult {
+                permission: Permission::Allow,
+                reason: "help request".to_string(),
+                suggestion: None,
+            },
+            "help check".to_string(),
+        );
     }
     if cmd
         .args
         .iter()
         .any(|a| a == "--version" || a == "-V" || a == "version")
     {
-        return PermissionResult {
-            permission: Permission::Allow,
-            reason: "version check".to_string(),
-            suggestion: None,
-        };
+        return (
+            PermissionResult {
+                permission: Permission::Allow,
+                reason: "version check".to_string(),
+                suggestion: None,
+            },
+            "version check".to_string(),
+        );
+    }
+
+    // Executed scripts are decided by their contents (opt-in)
+    if cmd.name.contains('/')
+        && let Some(result) = check_script(&cmd.name, config, edit_mode, location, trace)
+    {
+        return (result, format!("script {}", cmd.name));
     }
 
     // Allow scripts under /tmp/ (e.g., bash /tmp/claude/run-qemu.sh)
     if cmd.name.starts_with("/tmp/") {
-        return PermissionResult {
-            permission: Permission::Allow,
-            reason: "script in /tmp".to_string(),
+        return (
+            PermissionResult {
+                permission: Permission::Allow,
+                reason: "script in /tmp".to_string(),
+                suggestion: None,
+            },
+            "/tmp script check".to_string(),
+        );
+    }
+
+    // Regular command - check against rules in the directory it runs in,
+    // falling back to the project dir when that can't be known
+    let rules_cwd = location.cwd.or(location.project_dir);
+    let (result, rule) = config.explain_command_with_cwd(&cmd.name, &cmd.args, rules_cwd);
+    (result, rule_label(rule))
+}
+
+/// Special handlers that decide a command before the rules (when they return
+/// a result); `learn` never proposes rules for the commands they take
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Handler {
+    /// `sed -i`, which asks outside edit mode
+    SedInPlace,
+    /// mysql/mariadb and sqlite3 queries
+    Sql,
+    /// `git push`, destructive git commands and `git checkout`
+    Git,
+    /// `docker run`
+    DockerRun,
+    Rm,
+    Tee,
+    Tar,
+}
+
+impl Handler {
+    /// The special handler a command goes to, if any
+    fn of(cmd: &analyzer::Command) -> Option<Handler> {
+        match cmd.name.as_str() {
+            "sed" if cmd.args.iter().any(|a| a.starts_with("-i")) => Some(Handler::SedInPlace),
+            "sqlite3" => Some(Handler::Sql),
+            name if sql::MYSQL_COMMANDS.contains(&name) => Some(Handler::Sql),
+            "git" if git::is_handled(cmd) => Some(Handler::Git),
+            "docker" if cmd.args.first().is_some_and(|a| a == "run") => Some(Handler::DockerRun),
+            "rm" => Some(Handler::Rm),
+            "tee" => Some(Handler::Tee),
+            "tar" => Some(Handler::Tar),
+            _ => None,
+        }
+    }
+}
+
+/// Analyze the contents of a script file when `[scripts] inspect` is enabled
+/// Returns None for files that aren't shell scripts, so their path is checked instead
+fn check_script(
+    path: &str,
+    config: &Config,
+    edit_mode: bool,
+    location: Location,
+    trace: &mut Trace,
+) -> Option<PermissionResult> {
+    if !config.scripts.inspect {
+        return None;
+    }
+
+    if location.cwd.is_none() && !path.starts_with('/') && !path.starts_with("~/") {
+        return Some(PermissionResult {
+            permission: Permission::Ask,
+            reason: format!("can't locate script {}: working directory unknown", path),
             suggestion: None,
-        };
+        });
+    }
+
+    if location
+        .written
+        .contains(&script::resolve_path(path, location.cwd))
+    {
+        return Some(PermissionResult {
+            permission: Permission::Ask,
+            reason: format!("script {} is written by the command before it runs", path),
+            suggestion: None,
+        });
+    }
+
+    let read = script::read_script(&config.scripts, path, location.cwd);
+    let (resolved, content, language) = match read {
+        script::ScriptRead::Contents {
+            path,
+            content,
+            language,
+        } => (path, content, language),
+        script::ScriptRead::Uninspectable(reason) => {
+            return Some(PermissionResult {
+                permission: Permission::Ask,
+                reason,
+                suggestion: None,
+            });
+        }
+        script::ScriptRead::Missing => {
+            return Some(PermissionResult {
+                permission: Permission::Ask,
+                reason: format!("script {} doesn't exist", path),
+                suggestion: None,
+            });
+        }
+        script::ScriptRead::Skipped => return None,
+    };
+
+    if location.script_depth >= config.scripts.max_depth {
+        return Some(PermissionResult {
+            permission: Permission::Ask,
+            reason: format!("scripts nested more than {} deep", config.scripts.max_depth),
+            suggestion: None,
+        });
+    }
+    let location = Location {
+        script_depth: location.script_depth + 1,
+        ..location
+    };
+
+    let resolved = resolved.display().to_string();
+    trace.unwrap("script", Some(&resolved), None);
+    let result = analyze_language_at(language, &content, config, edit_mode, location, trace);
+
+    Some(PermissionResult {
+        reason: format!("script {}: {}", resolved, result.reason),
+        ..result
+    })
+}
+
+/// Check the queries in a file fed to a SQL client when `[scripts] inspect` is enabled
+/// Returns None if the file can't be read, so the command's rules decide
+fn check_sql_file(path: &str, config: &Config, location: Location) -> Option<PermissionResult> {
+    if !config.scripts.inspect || (location.cwd.is_none() && !path.starts_with('/')) {
+        return None;
     }
+    match script::read_text(&config.scripts, path, location.cwd) {
+        Ok((resolved, content)) => {
+            let result = sql::check_query_readonly(&content);
+            Some(PermissionResult {
+                reason: format!("{}: {}", resolved.display(), result.reason),
+                ..result
+            })
+        }
+        Err(script::ScriptRead::Uninspectable(reason)) => Some(PermissionResult {
+            permission: Permission::Ask,
+            reason,
+            suggestion: None,
+        }),
+        Err(_) => None,
+    }
+}
 
-    // Regular command - check against rules (use initial_cwd for project-based rules)
-    config.check_command_with_cwd(&cmd.name, &cmd.args, initial_cwd)
+/// Describe the rule that decided a command, or the config default
+fn rule_label(rule: Option<RuleMatch>) -> String {
+    match rule {
+        Some(rule) => rule.to_string(),
+        None => "default".to_string(),
+    }
 }
 
 /// Format the reason string
@@ -517,6 +1138,184 @@ mod tests {
         assert_eq!(result.permission, Permission::Allow);
     }
 
+    #[test]
+    fn test_risky_assignments() {
+        let config = test_config();
+        let result = analyze_command("LD_PRELOAD=/tmp/x.so ls", &config, false, None);
+        assert_eq!(result.permission, Permission::Ask);
+        assert_eq!(result.reason, "sets LD_PRELOAD");
+        let result = analyze_command("export PATH=/tmp/evil:$PATH", &config, false, None);
+        assert_eq!(result.permission, Permission::Ask);
+        let result = analyze_command("export FOO=bar && ls", &config, false, None);
+        assert_eq!(result.permission, Permission::Allow);
+    }
+
+    #[test]
+    fn test_assignment_values_checked() {
+        let config = test_config();
+        let result = analyze_command("X=$(mkfs /dev/sda)", &config, false, None);
+        assert_eq!(result.permission, Permission::Deny);
+        let result = analyze_command("X=$(pwd)", &config, false, None);
+        assert_eq!(result.permission, Permission::Allow);
+    }
+
+    #[test]
+    fn test_dynamic_command_name_asks() {
+        let config: Config = toml::from_str(
+            r#"
+            default = "allow"
+            [[rules]]
+            commands = ["rm"]
+            permission = "deny"
+            "#,
+        )
+        .unwrap();
+        let result = analyze_command("$CMD -rf /", &config, false, None);
+        assert_eq!(result.permission, Permission::Ask);
+        assert!(result.reason.contains("$CMD"), "{}", result.reason);
+        let result = analyze_command("\"$(echo rm)\" x", &config, false, None);
+        assert_eq!(result.permission, Permission::Ask);
+
+        let config: Config = toml::from_str(r#"dynamic_commands = "deny""#).unwrap();
+        let result = analyze_command("${tool} delete", &config, false, None);
+        assert_eq!(result.permission, Permission::Deny);
+    }
+
+    #[test]
+    fn test_quoted_names_reach_handlers() {
+        let config = test_config();
+        for cmd in [
+            "\\rm -rf /tmp/claude/x",
+            "'rm' -rf /tmp/claude/x",
+            "command rm -rf /tmp/claude/x",
+        ] {
+            let mut trace = Trace::default();
+            let result = analyze_command_traced(cmd, &config, false, None, &mut trace);
+            assert_eq!(result.permission, Permission::Allow, "{}", cmd);
+            assert_eq!(trace.deciding_handler(), Some("rm::check_rm"));
+        }
+        let result = analyze_command("r''m -rf /", &config, false, None);
+        assert_eq!(result.permission, Permission::Passthrough);
+    }
+
+    #[test]
+    fn test_function_body_checked() {
+        let config = test_config();
+        let result = analyze_command("f() { mkfs /dev/sda; }; f", &config, false, None);
+        assert_eq!(result.permission, Permission::Deny);
+        let result = analyze_command("f() { ls; }; f && f", &config, false, None);
+        assert_eq!(result.permission, Permission::Allow);
+        let result = analyze_command(":(){ :|:& };:", &config, false, None);
+        assert_eq!(result.permission, Permission::Deny);
+        assert!(result.reason.contains("calls itself"), "{}", result.reason);
+
+        // Bodies run without a direct call are still checked
+        for command in [
+            "f() { mkfs /dev/sda; }; trap f EXIT",
+            "f() { mkfs /dev/sda; }; eval f",
+            "f() { mkfs /dev/sda; }; echo x | xargs f",
+        ] {
+            let result = analyze_command(command, &config, false, None);
+            assert_eq!(result.permission, Permission::Deny, "{}", command);
+        }
+    }
+
+    #[test]
+    fn test_eval_unwrapped() {
+        let config = test_config();
+        let result = analyze_command("eval 'mkfs /dev/sda'", &config, false, None);
+        assert_eq!(result.permission, Permission::Deny);
+        let result = analyze_command("eval 'ls $DIR'", &config, false, None);
+        assert_eq!(result.permission, Permission::Allow);
+        // Expansions can add any syntax to the evaluated string
+        let result = analyze_command(
+            "D='/tmp; mkfs /dev/sda'; eval \"ls $D\"",
+            &config,
+            false,
+            None,
+        );
+        assert_eq!(result.permission, Permission::Ask);
+        assert_eq!(result.reason, "eval runs a command only known at runtime");
+        let result = analyze_command("eval $(cat cmd.txt)", &config, false, None);
+        assert_eq!(result.permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_source_uses_script_policy() {
+        let config: Config = toml::from_str(
+            r#"
+            default = "ask"
+            [[rules]]
+            commands = ["./scripts/env.sh"]
+            permission = "allow"
+            "#,
+        )
+        .unwrap();
+        let result = analyze_command("source ./scripts/env.sh", &config, false, None);
+        assert_eq!(result.permission, Permission::Allow);
+        let result = analyze_command(". /tmp/claude/setup.sh", &config, false, None);
+        assert_eq!(result.permission, Permission::Allow);
+        let result = analyze_command(". ~/.evil.sh", &config, false, None);
+        assert_eq!(result.permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_script_inspection() {
+        let dir = crate::test_util::temp_dir("inspect");
+        std::fs::write(dir.join("safe.sh"), "#!/bin/sh\nls -la\npwd\n").unwrap();
+        std::fs::write(dir.join("evil.sh"), "ls\nmkfs /dev/sda\n").unwrap();
+        std::fs::write(dir.join("outer.sh"), "bash ./evil.sh\n").unwrap();
+        let cwd = dir.to_str();
+
+        let mut config = test_config();
+        // Without inspection, scripts are checked by path
+        let result = analyze_command("bash evil.sh", &config, false, cwd);
+        assert_eq!(result.permission, Permission::Passthrough);
+
+        config.scripts.inspect = true;
+        let result = analyze_command("bash safe.sh", &config, false, cwd);
+        assert_eq!(result.permission, Permission::Allow);
+        let result = analyze_command("./evil.sh", &config, false, cwd);
+        assert_eq!(result.permission, Permission::Deny);
+        assert!(result.reason.contains("evil.sh"), "{}", result.reason);
+        let result = analyze_command("source outer.sh", &config, false, cwd);
+        assert_eq!(result.permission, Permission::Deny);
+
+        // Scripts that don't exist yet, or are written by the command itself,
+        // run whatever the command puts there
+        let result = analyze_command("bash missing.sh", &config, false, cwd);
+        assert_eq!(result.permission, Permission::Ask);
+        assert!(result.reason.contains("doesn't exist"), "{}", result.reason);
+        let result = analyze_command("bash < missing.sh", &config, false, cwd);
+        assert_eq!(result.permission, Permission::Ask);
+        let result = analyze_command(
+            "echo 'mkfs /dev/sda' > /tmp/claude/new.sh && bash /tmp/claude/new.sh",
+            &config,
+            false,
+            cwd,
+        );
+        assert_eq!(result.permission, Permission::Ask);
+        for command in [
+            "echo 'mkfs /dev/sda' > safe.sh && bash ./safe.sh",
+            "echo 'mkfs /dev/sda' | tee safe.sh; source safe.sh",
+            "echo 'mkfs /dev/sda' > safe.sh; bash -c 'bash safe.sh'",
+            "fish -c 'echo mkfs /dev/sda > safe.sh; bash safe.sh'",
+        ] {
+            let result = analyze_command(command, &config, false, cwd);
+            assert_eq!(result.permission, Permission::Ask, "{}", command);
+            assert!(result.reason.contains("written"), "{}", result.reason);
+        }
+        let result = analyze_command("bash safe.sh > safe.sh", &config, false, cwd);
+        assert_eq!(result.permission, Permission::Allow);
+
+        config.scripts.max_depth = 1;
+        let result = analyze_command("sh outer.sh", &config, false, cwd);
+        assert_eq!(result.permission, Permission::Ask);
+        assert!(result.reason.contains("nested"), "{}", result.reason);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_git_suggestion() {
         let config = test_config();
@@ -606,6 +1405,409 @@ mod tests {
         assert_eq!(result.permission, Permission::Allow);
     }
 
+    #[test]
+    fn test_redirect_to_dotfile_asks() {
+        let config = test_config();
+        let result = analyze_command("echo x > ~/.bashrc", &config, true, None);
+        assert_eq!(result.permission, Permission::Ask);
+        let result = analyze_command("cat foo >> /etc/hosts", &config, true, None);
+        assert_eq!(result.permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_redirect_to_dev_null_allowed() {
+        let config = test_config();
+        let result = analyze_command("ls > /dev/null 2>&1", &config, false, None);
+        assert_eq!(result.permission, Permission::Allow);
+        let result = analyze_command("echo hi >&2", &config, false, None);
+        assert_eq!(result.permission, Permission::Allow);
+    }
+
+    #[test]
+    fn test_redirect_to_tmp_allowed() {
+        let config = test_config();
+        let result = analyze_command("ls -la > /tmp/claude/out.txt", &config, false, None);
+        assert_eq!(result.permission, Permission::Allow);
+    }
+
+    #[test]
+    fn test_redirect_to_process_substitution() {
+        let config = test_config();
+        // The pipe isn't a file; the tee inside decides
+        let result = analyze_command("ls > >(tee /tmp/claude/out.txt)", &config, false, None);
+        assert_eq!(result.permission, Permission::Allow);
+        let result = analyze_command("ls > >(tee /etc/x)", &config, false, None);
+        assert_ne!(result.permission, Permission::Allow);
+        assert!(!result.reason.contains(">("), "{}", result.reason);
+    }
+
+    #[test]
+    fn test_redirect_to_project_needs_edit_mode() {
+        let config = test_config();
+        let cwd = Some("/home/test/project");
+        let result = analyze_command("echo x > notes.txt", &config, false, cwd);
+        assert_eq!(result.permission, Permission::Ask);
+        let result = analyze_command("echo x > notes.txt", &config, true, cwd);
+        assert_eq!(result.permission, Permission::Allow);
+    }
+
+    #[test]
+    fn test_cwd_follows_control_flow() {
+        let config = test_config();
+        let cwd = Some("/home/test/project");
+        let allowed = |command: &str| {
+            analyze_command(command, &config, false, cwd).permission == Permission::Allow
+        };
+
+        assert!(allowed("cd /tmp/claude/x && tar -xf a.tar"));
+        // Words in arguments are not control flow
+        assert!(allowed(
+            "echo \"wait for it\"; cd /tmp/claude/x && tar -xf a.tar"
+        ));
+        assert!(allowed("cd /tmp/claude && cd x && tar -xf a.tar"));
+        // cd in a subshell or a branch doesn't decide where tar runs
+        assert!(!allowed("(cd /tmp/claude/x); tar -xf a.tar"));
+        assert!(!allowed(
+            "if [ -d y ]; then cd /tmp/claude/x; fi; tar -xf a.tar"
+        ));
+        // rm and redirects resolve relative paths where the command runs
+        assert!(allowed("cd /tmp/claude/x && rm -rf build"));
+        assert!(!allowed("cd /etc && rm passwd"));
+        assert!(!allowed("cd /etc && sudo rm passwd"));
+        assert!(allowed("cd /tmp/claude && ls > out.txt"));
+    }
+
+    #[test]
+    fn test_stdin_content_analyzed() {
+        let config = test_config();
+        let decide = |command: &str| analyze_command(command, &config, false, None).permission;
+
+        // Shells reading commands from a heredoc or here-string
+        assert_eq!(decide("bash <<'EOF'\nls\npwd\nEOF"), Permission::Allow);
+        assert_eq!(
+            decide("bash <<'EOF'\nls\nmkfs /dev/sda\nEOF"),
+            Permission::Deny
+        );
+        assert_eq!(decide("sh <<< 'mkfs /dev/sda'"), Permission::Deny);
+        assert_eq!(
+            decide("cat <<EOF | sh\nmkfs /dev/sda\nEOF"),
+            Permission::Deny
+        );
+        // Data for a command that doesn't run it
+        assert_eq!(decide("cat <<EOF\nmkfs /dev/sda\nEOF"), Permission::Allow);
+
+        // The remote shell of ssh
+        assert_eq!(
+            decide("ssh host <<'EOF'\nmkfs /dev/sda\nEOF"),
+            Permission::Deny
+        );
+        assert_eq!(decide("ssh host <<'EOF'\nuptime\nEOF"), Permission::Allow);
+
+        // The shell parses what an unquoted heredoc or a here-string expanded to
+        for command in [
+            "X='; mkfs /dev/sda'; bash <<EOF\nls $X\nEOF",
+            "X='; mkfs /dev/sda'; ssh host <<EOF\nls $X\nEOF",
+            "X='; mkfs /dev/sda'; sh <<< \"ls $X\"",
+            "cat <<EOF | sh\nls $(cat f)\nEOF",
+        ] {
+            assert_eq!(decide(command), Permission::Ask, "{}", command);
+        }
+        assert_eq!(decide("bash <<EOF\nls \\$X\nEOF"), Permission::Allow);
+        assert_eq!(decide("mysql db <<EOF\nSELECT $X\nEOF"), Permission::Ask);
+
+        // SQL clients, also behind ssh
+        assert_eq!(
+            decide("ssh db \"mariadb -e \\\"SHOW MASTER STATUS\\\"\""),
+            Permission::Allow
+        );
+        assert_eq!(
+            decide("ssh db \"mariadb -e \\\"DROP TABLE x\\\"\""),
+            Permission::Ask
+        );
+        assert_eq!(decide("mysql db <<< \"DROP TABLE x\""), Permission::Ask);
+        assert_eq!(decide("mysql db <<< \"SELECT 1\""), Permission::Allow);
+        assert_eq!(
+            decide("sqlite3 app.db <<'EOF'\n.tables\nDELETE FROM users;\nEOF"),
+            Permission::Ask
+        );
+    }
+
+    #[test]
+    fn test_wrapper_quoting() {
+        let config = test_config();
+        let decide = |command: &str| analyze_command(command, &config, false, None).permission;
+
+        // The inner command sees the words the shell would
+        assert_eq!(
+            decide("sudo bash -c 'rm -rf \"/tmp/claude/a b\"'"),
+            Permission::Allow
+        );
+        assert_eq!(
+            decide("bash -c 'rm -rf /tmp/claude/a /etc'"),
+            Permission::Passthrough
+        );
+        assert_eq!(
+            decide("bash -c \"echo 'a && mkfs /dev/sda'\""),
+            Permission::Allow
+        );
+        assert_eq!(decide("bash -c $'mkfs\\x20/dev/sda'"), Permission::Deny);
+        assert_eq!(
+            decide("ssh host \"cd /x && mkfs /dev/sda\""),
+            Permission::Deny
+        );
+        assert_eq!(decide("eval \"echo 'mkfs /dev/sda'\""), Permission::Allow);
+
+        // The inner shell parses what the outer one expanded
+        for command in [
+            "X='; mkfs /dev/sda'; bash -c \"ls $X\"",
+            "X='; mkfs /dev/sda'; ssh host \"ls $X\"",
+            "sudo sh -c \"ls $(cat f)\"",
+            "X='; mkfs /dev/sda'; sudo -u me bash -c \"echo $X\"",
+        ] {
+            assert_eq!(decide(command), Permission::Ask, "{}", command);
+        }
+        assert_eq!(decide("bash -c 'ls $X'"), Permission::Allow);
+
+        // Each level of nesting is unquoted once, up to a limit
+        let mut command = "mkfs /dev/sda".to_string();
+        for _ in 0..3 {
+            command = format!("bash -c {}", quote::quote(&command));
+        }
+        assert_eq!(decide(&command), Permission::Deny);
+        let command = format!("{}ls", "eval ".repeat(MAX_WRAPPER_DEPTH));
+        assert_eq!(decide(&command), Permission::Allow);
+        let command = format!("{}ls", "eval ".repeat(MAX_WRAPPER_DEPTH + 1));
+        assert_eq!(decide(&command), Permission::Ask);
+    }
+
+    #[test]
+    fn test_nested_shell_languages() {
+        let config = test_config();
+        let decide = |command: &str| analyze_command(command, &config, false, None).permission;
+
+        // Each shell's command string goes to its own analyzer
+        assert_eq!(decide("nu -c 'ls | where size > 1kb'"), Permission::Allow);
+        assert_eq!(decide("nu -c '^mkfs /dev/sda'"), Permission::Deny);
+        assert_eq!(decide("fish -c 'echo (pwd); and ls'"), Permission::Allow);
+        assert_eq!(
+            decide("fish -c 'if test -f x; mkfs /dev/sda; end'"),
+            Permission::Deny
+        );
+        assert_eq!(
+            decide("fish -c 'echo \"$(mkfs /dev/sda)\"'"),
+            Permission::Deny
+        );
+        assert_eq!(
+            decide("fish -c 'set -gx LD_PRELOAD /tmp/x.so'"),
+            Permission::Ask
+        );
+        assert_eq!(
+            decide("fish -c 'set -x PAGER evil; git log'"),
+            Permission::Ask
+        );
+        // Fish substitutions and variables handed to a bash wrapper
+        for command in [
+            "fish -c 'eval (echo mkfs /dev/sda)'",
+            "fish -c 'sudo (echo mkfs) /dev/sda'",
+            "fish -c 'timeout 5 $cmd'",
+        ] {
+            let result = analyze_command(command, &config, false, None);
+            assert_eq!(result.permission, Permission::Ask, "{}", command);
+            assert!(result.reason.contains("runtime"), "{}", result.reason);
+        }
+        assert_eq!(decide("fish -c 'sudo ls -la'"), Permission::Allow);
+        assert_eq!(decide("fish <<< 'mkfs /dev/sda'"), Permission::Deny);
+
+        // Bash run from nushell
+        let decide_nu =
+            |command: &str| analyze_nushell_command(command, &config, false, None).permission;
+        assert_eq!(
+            decide_nu("^bash -c 'for f in a b; do echo $f; done'"),
+            Permission::Allow
+        );
+        assert_eq!(
+            decide_nu("^bash -c 'ls && mkfs /dev/sda'"),
+            Permission::Deny
+        );
+        assert_eq!(decide_nu("^fish -c 'mkfs /dev/sda'"), Permission::Deny);
+    }
+
+    #[test]
+    fn test_git_push_in_tracked_directory() {
+        let config = test_config();
+        let dir = crate::test_util::temp_dir("push");
+        let status = std::process::Command::new("git")
+            .args(["init", "-q", "-b", "main", dir.to_str().unwrap()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let decide = |command: &str| analyze_command(command, &config, false, Some("/")).permission;
+
+        // The branch is read where the push runs, not in the hook's directory
+        let cd = format!("cd {} && git push", dir.display());
+        assert_eq!(decide(&cd), Permission::Ask);
+        let dash_c = format!("git -C {} push origin HEAD:feature", dir.display());
+        assert_eq!(decide(&dash_c), Permission::Allow);
+        assert_eq!(decide("cd \"$X\" && git push"), Permission::Ask);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_stdin_files_inspected() {
+        let dir = crate::test_util::temp_dir("stdin");
+        std::fs::write(dir.join("read.sql"), "SELECT * FROM users;\n").unwrap();
+        std::fs::write(dir.join("drop.sql"), "DROP TABLE users;\n").unwrap();
+        std::fs::write(dir.join("evil.sh"), "mkfs /dev/sda\n").unwrap();
+        let cwd = dir.to_str();
+
+        let mut config = test_config();
+        config.scripts.inspect = true;
+        let decide = |command: &str| analyze_command(command, &config, false, cwd).permission;
+        assert_eq!(decide("sqlite3 app.db < read.sql"), Permission::Allow);
+        assert_eq!(decide("sqlite3 app.db < drop.sql"), Permission::Ask);
+        assert_eq!(decide("bash < evil.sh"), Permission::Deny);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_piped_operands() {
+        let config = test_config();
+        let cwd = Some("/home/test/project");
+        let allowed = |command: &str| {
+            analyze_command(command, &config, false, cwd).permission == Permission::Allow
+        };
+
+        // The producer bounds what xargs, parallel or a read loop act on
+        assert!(allowed("find /tmp/claude -name x -print0 | xargs -0 rm"));
+        assert!(allowed(
+            "find /tmp/claude -name '*.o' -print0 | grep -zv keep | xargs -0 -r rm -f"
+        ));
+        assert!(allowed("fd -0 . /tmp/claude | xargs -0 -I{} rm {}"));
+        assert!(allowed("find /tmp/claude -print0 | parallel -0 rm"));
+        assert!(allowed(
+            "find /tmp/claude -type f -print0 | while read -r -d '' f; do rm \"$f\"; done"
+        ));
+        assert!(allowed("find /tmp/claude -print0 | sudo xargs --null rm"));
+
+        // Only NULs separate paths: `a /etc/passwd` is two operands to plain
+        // xargs, and a newline in a name splits it for read
+        assert!(!allowed("find /tmp/claude | xargs rm"));
+        assert!(!allowed("find /tmp/claude -print0 | xargs rm"));
+        assert!(!allowed("find /tmp/claude | xargs -0 rm"));
+        assert!(!allowed(
+            "find /tmp/claude -print0 | while read -r f; do rm \"$f\"; done"
+        ));
+        assert!(!allowed("find /tmp/claude -print0 | xargs -0 -d x rm"));
+
+        assert!(!allowed("find / -name '*.log' -print0 | xargs -0 rm"));
+        assert!(!allowed("cat list.txt | xargs -0 rm"));
+        assert!(!allowed("xargs -I{} rm {} < list.txt"));
+        assert!(!allowed(
+            "find /tmp/claude -print0 | xargs -0 -I{} rm {}/../x"
+        ));
+        assert!(!allowed(
+            "find ~ -print0 | while read -d '' f; do rm \"$f\"; done"
+        ));
+        assert!(!allowed(
+            "find /tmp/claude -print0 | while read -d '' f; do rm $f; done"
+        ));
+        assert!(!allowed("find /tmp/claude -print0 | xargs -0 -a list rm"));
+
+        // parallel with inputs on the command line
+        assert!(allowed("parallel rm ::: /tmp/claude/a /tmp/claude/b"));
+        assert!(!allowed("parallel rm {} ::: /tmp/claude/a /etc/passwd"));
+        assert!(!allowed("parallel rm {.}.o :::: files.txt"));
+    }
+
+    #[test]
+    fn test_rm_paths_expanded() {
+        let config = test_config();
+        let cwd = Some("/home/test/project");
+        for command in [
+            "rm -rf ~/Documents",
+            "rm -rf $HOME/x",
+            "rm -rf /tmp/../home/*",
+        ] {
+            let result = analyze_command(command, &config, false, cwd);
+            assert_ne!(result.permission, Permission::Allow, "{}", command);
+        }
+        let result = analyze_command("rm -rf /tmp/claude/{a,b}", &config, false, cwd);
+        assert_eq!(result.permission, Permission::Allow);
+
+        // Variables the command line sets don't have their environment value
+        // ($PWD would otherwise expand to the cwd)
+        let cwd = Some("/tmp/claude/work");
+        let result = analyze_command("rm -rf \"$PWD/x\"", &config, false, cwd);
+        assert_eq!(result.permission, Permission::Allow);
+        for command in [
+            "PWD=/etc; rm -rf \"$PWD/x\"",
+            "for PWD in /etc; do rm -rf $PWD/x; done",
+            "read -r PWD < f; rm -rf $PWD/x",
+            "export PWD=/; bash -c 'rm -rf $PWD/x'",
+            "unset PWD; tee $PWD/x",
+        ] {
+            let result = analyze_command(command, &config, false, cwd);
+            assert_ne!(result.permission, Permission::Allow, "{}", command);
+        }
+        let result = analyze_command("unset X; rm -rf \"$PWD/x\"", &config, false, cwd);
+        assert_eq!(result.permission, Permission::Allow);
+    }
+
+    #[test]
+    fn test_command_substitution_checked() {
+        let config = test_config();
+        // echo is allowed, but the substituted rm is not
+        let result = analyze_command("echo $(rm -rf ~)", &config, false, None);
+        assert_eq!(result.permission, Permission::Passthrough);
+        let result = analyze_command("cat <(mkfs /dev/sda)", &config, false, None);
+        assert_eq!(result.permission, Permission::Deny);
+        let result = analyze_command("echo \"$(ls)\"", &config, false, None);
+        assert_eq!(result.permission, Permission::Allow);
+    }
+
+    #[test]
+    fn test_trace_records_unwrap_and_handler() {
+        let config = test_config();
+        let mut trace = Trace::default();
+        let result = analyze_command_traced(
+            "ls && sudo rm -rf /tmp/claude/x",
+            &config,
+            false,
+            None,
+            &mut trace,
+        );
+        assert_eq!(result.permission, Permission::Allow);
+
+        let rendered = trace.render();
+        assert!(rendered.contains("command: ls\n"), "{}", rendered);
+        assert!(rendered.contains("allow by rule #1 \"ls\""), "{}", rendered);
+        assert!(
+            rendered.contains("unwrap sudo -> rm -rf /tmp/claude/x"),
+            "{}",
+            rendered
+        );
+        assert!(rendered.contains("allow by rm::check_rm"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_trace_most_restrictive() {
+        let config = test_config();
+        let mut trace = Trace::default();
+        analyze_command_traced("ls; mkfs /dev/sda; pwd", &config, false, None, &mut trace);
+        let rendered = trace.render();
+        assert!(
+            rendered.contains("most restrictive: mkfs /dev/sda (deny by rule #"),
+            "{}",
+            rendered
+        );
+        assert_eq!(
+            trace.deciding_handler().map(|h| h.ends_with("\"mkfs\"")),
+            Some(true)
+        );
+    }
+
     #[test]
     fn test_cwd_propagates_through_wrapper() {
         // Create a config with a cwd-restricted rule and sudo wrapper
@@ -9,6 +9,7 @@ use nu_protocol::Span;
 use nu_protocol::engine::{EngineState, StateWorkingSet};
 
 use crate::analyzer::Command;
+use crate::quote;
 
 /// Result of analyzing a nushell command
 #[derive(Debug)]
@@ -90,22 +91,23 @@ fn extract_from_expression(
             let name = span_to_string(head.span, source);
             let text = span_to_string(expr.span, source);
 
-            let arg_strings: Vec<String> = args
+            let (arg_strings, raw_args) = args
                 .iter()
-                .filter_map(|arg| match arg {
-                    nu_protocol::ast::ExternalArgument::Regular(e) => {
-                        Some(span_to_string(e.span, source))
-                    }
+                .map(|arg| match arg {
+                    nu_protocol::ast::ExternalArgument::Regular(e) => argument(e, source),
                     nu_protocol::ast::ExternalArgument::Spread(e) => {
-                        Some(span_to_string(e.span, source))
+                        let text = span_to_string(e.span, source);
+                        (text.clone(), text)
                     }
                 })
-                .collect();
+                .unzip();
 
             commands.push(Command {
                 name: name.trim_start_matches('^').to_string(),
                 args: arg_strings,
+                raw_args,
                 text: text.trim_start_matches('^').to_string(),
+                ..Default::default()
             });
         }
         Expr::Call(call) => {
@@ -118,16 +120,18 @@ fn extract_from_expression(
 
             if dangerous_builtins.contains(&call_name.as_str()) {
                 // Extract arguments
-                let arg_strings: Vec<String> = call
+                let (arg_strings, raw_args) = call
                     .arguments
                     .iter()
-                    .filter_map(|arg| arg.expr().map(|e| span_to_string(e.span, source)))
-                    .collect();
+                    .filter_map(|arg| arg.expr().map(|e| argument(e, source)))
+                    .unzip();
 
                 commands.push(Command {
                     name: call_name.clone(),
                     args: arg_strings,
+                    raw_args,
                     text: span_to_string(expr.span, source),
+                    ..Default::default()
                 });
             }
 
@@ -180,6 +184,36 @@ fn extract_from_expression(
     }
 }
 
+/// An argument's value, and the same word written for bash so wrappers that pass
+/// it on (`^bash -c 'rm -rf x'`, `^sudo rm "a b"`) keep its quoting
+/// Bare globs and `~` keep their meaning; anything else that isn't a plain
+/// string (variables, subexpressions) is left as written
+fn argument(expr: &nu_protocol::ast::Expression, source: &[u8]) -> (String, String) {
+    use nu_protocol::ast::Expr;
+
+    match &expr.expr {
+        Expr::String(value) | Expr::RawString(value) => (value.clone(), quote::quote(value)),
+        Expr::GlobPattern(value, quoted)
+        | Expr::Filepath(value, quoted)
+        | Expr::Directory(value, quoted) => {
+            let bare = !quoted
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "/._-+=:@,%*?[]~".contains(c));
+            let raw = if bare {
+                value.clone()
+            } else {
+                quote::quote(value)
+            };
+            (value.clone(), raw)
+        }
+        _ => {
+            let text = span_to_string(expr.span, source);
+            (text.clone(), text)
+        }
+    }
+}
+
 /// Convert a span to a string
 fn span_to_string(span: Span, source: &[u8]) -> String {
     let start = span.start;
@@ -205,6 +239,18 @@ mod tests {
         assert_eq!(result.commands[0].args, vec!["status"]);
     }
 
+    #[test]
+    fn test_quoted_arguments() {
+        // Values lose nushell's quotes; raw args are re-quoted for bash
+        let result = analyze(r#"^rm "a b" *.log ~/x `c d`"#);
+        let rm = &result.commands[0];
+        assert_eq!(rm.args, vec!["a b", "*.log", "~/x", "c d"]);
+        assert_eq!(rm.raw_args, vec!["'a b'", "*.log", "~/x", "'c d'"]);
+
+        let result = analyze(r#"^bash -c 'rm -rf "a b"'"#);
+        assert_eq!(result.commands[0].arg_string(1), r#"rm -rf "a b""#);
+    }
+
     #[test]
     fn test_bare_command() {
         // Bare commands without ^ are also extracted
@@ -0,0 +1,90 @@
+//! Path resolution for the file checks
+//!
+//! The rm, tee, tar and redirect checks resolve the paths a command touches
+//! with `realpath -m`, so `..` and symlinks can't lead them out of the
+//! directories they allow.
+
+use std::process::Command as ProcessCommand;
+
+/// Resolve a path the way a command running in `cwd` (None if unknown) sees it
+/// A path that can't be resolved is judged by its resolved parent directory
+/// Returns None for relative paths with an unknown cwd and suspicious paths
+pub fn resolve_in(path: &str, cwd: Option<&str>) -> Option<String> {
+    if path.is_empty() || path.contains('\0') || path.contains('\n') {
+        return None;
+    }
+
+    let path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/{}", cwd?.trim_end_matches('/'), path)
+    };
+
+    resolve(&path).or_else(|| {
+        // Path doesn't resolve - check the parent it would be created in
+        let parent = std::path::Path::new(&path).parent()?.to_str()?;
+        if parent.is_empty() {
+            return None;
+        }
+        resolve(parent)
+    })
+}
+
+/// Resolve a path using realpath
+pub fn resolve(path: &str) -> Option<String> {
+    let output = ProcessCommand::new("realpath")
+        .arg("-m") // don't require path to exist
+        .arg("--")
+        .arg(path)
+        .output()
+        .ok()?;
+
+    if output.status.success() {
+        let resolved = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !resolved.is_empty() {
+            return Some(resolved);
+        }
+    }
+
+    None
+}
+
+/// Check if a resolved path is strictly under a directory (not the directory itself)
+pub fn is_under(resolved: &str, dir: &str) -> bool {
+    let prefix = format!("{}/", dir.trim_end_matches('/'));
+    resolved
+        .strip_prefix(&prefix)
+        .is_some_and(|after| !after.is_empty() && !after.chars().all(|c| c == '/'))
+}
+
+/// Check if a resolved path is under /tmp/ (but not /tmp itself)
+pub fn is_under_tmp(resolved: &str) -> bool {
+    is_under(resolved, "/tmp")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_in() {
+        assert_eq!(
+            resolve_in("../b/./c", Some("/tmp/a")).as_deref(),
+            Some("/tmp/b/c")
+        );
+        assert_eq!(resolve_in("/tmp/x/../y", None).as_deref(), Some("/tmp/y"));
+        assert_eq!(resolve_in("out.txt", None), None);
+        assert_eq!(resolve_in("a\nb", Some("/tmp")), None);
+    }
+
+    #[test]
+    fn test_is_under() {
+        assert!(is_under_tmp("/tmp/x"));
+        assert!(!is_under_tmp("/tmp"));
+        assert!(!is_under_tmp("/tmp/"));
+        assert!(!is_under_tmp("/tmpfoo/x"));
+        assert!(is_under("/home/p/src", "/home/p/"));
+        assert!(!is_under("/home/p", "/home/p"));
+        assert!(!is_under("/home/project2/x", "/home/p"));
+    }
+}
@@ -0,0 +1,177 @@
+//! Policy test suites
+//!
+//! A policy file lists commands with the decision they are expected to get.
+//! Running it against a config catches regressions when rules change.
+//!
+//! ```toml
+//! config = "config.toml"   # optional, relative to the policy file
+//!
+//! [[cases]]
+//! command = "git push --force origin main"
+//! cwd = "/home/me/project"
+//! mode = "acceptEdits"
+//! expect = "ask"
+//! ```
+
+use crate::config::{Config, Permission};
+use crate::trace::Trace;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// A policy test file
+#[derive(Debug, Deserialize)]
+pub struct PolicyFile {
+    /// Config to test against, relative to the policy file
+    #[serde(default)]
+    pub config: Option<String>,
+    #[serde(default)]
+    pub cases: Vec<PolicyCase>,
+}
+
+/// A single expected decision
+#[derive(Debug, Deserialize)]
+pub struct PolicyCase {
+    pub command: String,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Permission mode, e.g. "acceptEdits"
+    #[serde(default)]
+    pub mode: Option<String>,
+    /// Expected permission: allow, passthrough, ask or deny
+    pub expect: String,
+    /// Analyze as a nushell command instead of bash
+    #[serde(default)]
+    pub nushell: bool,
+}
+
+/// Outcome of running a case whose decision didn't match
+#[derive(Debug)]
+pub struct Failure {
+    pub command: String,
+    pub expected: String,
+    pub actual: Permission,
+    pub handler: String,
+    pub reason: String,
+}
+
+impl PolicyFile {
+    /// Load a policy file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read policy file {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse policy file {}: {}", path.display(), e))
+    }
+
+    /// The config path named by the file, resolved relative to it
+    pub fn config_path(&self, policy_path: &Path) -> Option<PathBuf> {
+        let config = self.config.as_ref()?;
+        let dir = policy_path.parent().unwrap_or(Path::new("."));
+        Some(dir.join(config))
+    }
+}
+
+impl PolicyCase {
+    /// Run the case and return a failure if the decision doesn't match
+    pub fn run(&self, config: &Config) -> Result<Option<Failure>, String> {
+        let expected = Permission::parse(&self.expect)
+            .ok_or_else(|| format!("Unknown expected permission '{}'", self.expect))?;
+
+        let edit_mode = crate::edits_allowed(self.mode.as_deref());
+        let cwd = self.cwd.as_deref();
+        let mut trace = Trace::default();
+        let result = if self.nushell {
+            crate::analyze_nushell_command_traced(&self.command, config, edit_mode, cwd, &mut trace)
+        } else {
+            crate::analyze_command_traced(&self.command, config, edit_mode, cwd, &mut trace)
+        };
+
+        if result.permission == expected {
+            return Ok(None);
+        }
+
+        Ok(Some(Failure {
+            command: self.command.clone(),
+            expected: expected.as_str().to_string(),
+            actual: result.permission,
+            handler: trace.deciding_handler().unwrap_or("analyzer").to_string(),
+            reason: result.reason,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(command: &str, expect: &str) -> PolicyCase {
+        PolicyCase {
+            command: command.to_string(),
+            cwd: None,
+            mode: None,
+            expect: expect.to_string(),
+            nushell: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_policy_file() {
+        let policy: PolicyFile = toml::from_str(
+            r#"
+            config = "config.toml"
+
+            [[cases]]
+            command = "ls"
+            expect = "allow"
+
+            [[cases]]
+            command = "ls"
+            expect = "allow"
+            nushell = true
+            mode = "acceptEdits"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(policy.cases.len(), 2);
+        assert!(policy.cases[1].nushell);
+        assert_eq!(
+            policy.config_path(Path::new("/home/me/dotfiles/policy.toml")),
+            Some(PathBuf::from("/home/me/dotfiles/config.toml"))
+        );
+    }
+
+    #[test]
+    fn test_matching_case_passes() {
+        let config = Config::default();
+        assert!(case("ls -la", "allow").run(&config).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_mismatch_reports_deciding_rule() {
+        let config: Config = toml::from_str(
+            r#"
+            [[rules]]
+            commands = ["ls"]
+            permission = "allow"
+
+            [[rules]]
+            commands = ["mkfs"]
+            permission = "deny"
+            reason = "filesystem"
+            "#,
+        )
+        .unwrap();
+        let failure = case("ls && mkfs /dev/sda", "allow")
+            .run(&config)
+            .unwrap()
+            .unwrap();
+        assert_eq!(failure.actual, Permission::Deny);
+        assert_eq!(failure.handler, "rule #2 \"mkfs\"");
+    }
+
+    #[test]
+    fn test_unknown_expectation_is_error() {
+        let config = Config::default();
+        assert!(case("ls", "maybe").run(&config).is_err());
+    }
+}
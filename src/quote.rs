@@ -0,0 +1,209 @@
+//! Shell quoting
+//!
+//! Wrappers pass their inner command on as shell source, built from the words
+//! as written so the inner parse sees the same word boundaries. A value that
+//! has no source form (a nushell argument) is quoted for bash. A string run
+//! through another shell (`bash -c`, `ssh host cmd`, `eval`) is what that shell
+//! receives: the word after quote removal.
+
+/// Quote a value so a POSIX shell reads it back as the same single word
+pub fn quote(value: &str) -> String {
+    if !value.is_empty() && value.chars().all(is_plain) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Characters that never need quoting
+fn is_plain(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "/._-+=:@,%".contains(c)
+}
+
+/// Remove one level of quoting from a word as written: single, double and
+/// `$'...'` quotes and backslash escapes. Expansions are left as written.
+pub fn unquote(word: &str) -> String {
+    let mut out = String::new();
+    let mut rest = word;
+    while let Some(c) = rest.chars().next() {
+        if let Some(body) = rest.strip_prefix("$'") {
+            let end = closing(body, '\'', true);
+            out.push_str(&decode_ansi_c(&body[..end]));
+            rest = body.get(end + 1..).unwrap_or_default();
+        } else if c == '\'' {
+            let body = &rest[1..];
+            let end = closing(body, '\'', false);
+            out.push_str(&body[..end]);
+            rest = body.get(end + 1..).unwrap_or_default();
+        } else if c == '"' || rest.starts_with("$\"") {
+            let body = &rest[rest.find('"').unwrap_or_default() + 1..];
+            let end = closing(body, '"', true);
+            out.push_str(&unescape_double_quoted(&body[..end]));
+            rest = body.get(end + 1..).unwrap_or_default();
+        } else if c == '\\' {
+            let mut chars = rest[1..].chars();
+            match chars.next() {
+                Some('\n') | None => {}
+                Some(next) => out.push(next),
+            }
+            rest = chars.as_str();
+        } else {
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+/// Byte offset of the quote closing `text`, skipping backslash escapes if
+/// `escapes` (the end of `text` if unterminated)
+fn closing(text: &str, quote: char, escapes: bool) -> usize {
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            return i;
+        }
+        if escapes && c == '\\' {
+            chars.next();
+        }
+    }
+    text.len()
+}
+
+/// Remove backslash escapes from an unquoted word (`\rm` -> `rm`)
+pub fn unescape_word(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\n') | None => {}
+                Some(next) => out.push(next),
+            },
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Remove the backslash escapes that are special inside double quotes
+pub fn unescape_double_quoted(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('\n')) => {
+                chars.next();
+            }
+            ('\\', Some(&next @ ('$' | '`' | '"' | '\\'))) => {
+                out.push(next);
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Decode the escapes of an ANSI-C quoted string (`$'\x72m'` -> `rm`)
+pub fn decode_ansi_c(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let Some(escape) = chars.next() else {
+            out.push('\\');
+            break;
+        };
+        let decoded = match escape {
+            'a' => Some('\x07'),
+            'b' => Some('\x08'),
+            'e' | 'E' => Some('\x1b'),
+            'f' => Some('\x0c'),
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            'v' => Some('\x0b'),
+            '\\' | '\'' | '"' | '?' => Some(escape),
+            'c' => chars.next().map(|ctl| ((ctl as u8) & 0x1f) as char),
+            'x' => take_code(&mut chars, 16, 2),
+            'u' => take_code(&mut chars, 16, 4),
+            'U' => take_code(&mut chars, 16, 8),
+            '0'..='7' => {
+                let mut digits = escape.to_string();
+                while digits.len() < 3
+                    && let Some(&d @ '0'..='7') = chars.peek()
+                {
+                    digits.push(d);
+                    chars.next();
+                }
+                u32::from_str_radix(&digits, 8)
+                    .ok()
+                    .and_then(char::from_u32)
+            }
+            _ => None,
+        };
+        match decoded {
+            Some(decoded) => out.push(decoded),
+            None => {
+                out.push('\\');
+                out.push(escape);
+            }
+        }
+    }
+    out
+}
+
+/// Read up to `max_digits` digits in `radix` as a character code
+fn take_code(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    radix: u32,
+    max_digits: usize,
+) -> Option<char> {
+    let mut digits = String::new();
+    while digits.len() < max_digits
+        && let Some(&d) = chars.peek()
+        && d.is_digit(radix)
+    {
+        digits.push(d);
+        chars.next();
+    }
+    u32::from_str_radix(&digits, radix)
+        .ok()
+        .and_then(char::from_u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("ls"), "ls");
+        assert_eq!(quote("/tmp/a.txt"), "/tmp/a.txt");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+        assert_eq!(quote(""), "''");
+    }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote("'rm -rf /tmp/a b'"), "rm -rf /tmp/a b");
+        assert_eq!(unquote("\"cd /x && rm \\\"y\\\"\""), "cd /x && rm \"y\"");
+        assert_eq!(unquote("r''m"), "rm");
+        assert_eq!(unquote("\\rm a\\ b"), "rm a b");
+        assert_eq!(unquote("$'\\x72m'"), "rm");
+        assert_eq!(unquote("\"$HOME\"/x"), "$HOME/x");
+        assert_eq!(unquote("'unterminated"), "unterminated");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for value in ["a b", "it's", "$(rm -rf ~)", "x\"y", "", "\\n"] {
+            assert_eq!(unquote(&quote(value)), value);
+        }
+    }
+}
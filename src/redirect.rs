@@ -0,0 +1,243 @@
+//! Output redirection special handling
+//!
+//! Treats `>`, `>>`, `&>` and friends as file writes. Auto-allows writes to
+//! /dev/null, fd duplication and files under /tmp/. Writes to the project
+//! directory are allowed in edit mode; everything else asks.
+
+use crate::analyzer::{Command, Redirect};
+use crate::config::{Permission, PermissionResult};
+use crate::expand;
+use crate::paths;
+
+/// Device files that are always safe to write to
+const SAFE_DEVICES: &[&str] = &["/dev/null", "/dev/stdout", "/dev/stderr", "/dev/tty"];
+
+/// Check the output redirects of a command
+/// `cwd` is the directory the command runs in (None if unknown), variables in
+/// `assigned` (set by the command line) are unknown, and `project_dir` is the
+/// directory the session started in
+/// Returns None if the command has no write redirects
+pub fn check_redirects(
+    cmd: &Command,
+    edit_mode: bool,
+    cwd: Option<&str>,
+    assigned: &[String],
+    project_dir: Option<&str>,
+) -> Option<PermissionResult> {
+    let targets: Vec<&Redirect> = cmd
+        .redirects
+        .iter()
+        .filter(|r| r.is_write() && !r.is_fd_duplication())
+        .collect();
+
+    if targets.is_empty() {
+        return None;
+    }
+
+    let mut result = PermissionResult {
+        permission: Permission::Allow,
+        reason: "redirect to /tmp or project dir".to_string(),
+        suggestion: None,
+    };
+
+    for redirect in targets {
+        let target_result = check_target(redirect, edit_mode, cwd, assigned, project_dir);
+        if target_result.permission > result.permission {
+            result = target_result;
+        }
+    }
+
+    Some(result)
+}
+
+/// Check a single redirect target
+fn check_target(
+    redirect: &Redirect,
+    edit_mode: bool,
+    cwd: Option<&str>,
+    assigned: &[String],
+    project_dir: Option<&str>,
+) -> PermissionResult {
+    let target = redirect.target.as_str();
+
+    // Expand the target as the shell would (~, $VAR, globs); bash refuses a
+    // target that expands to several words, but it still can't be checked
+    let path = match expand::expand_word(&redirect.raw_target, cwd, assigned).as_deref() {
+        Some([path]) => path.clone(),
+        _ => {
+            return PermissionResult {
+                permission: Permission::Ask,
+                reason: format!("redirect to unresolvable path '{}'", target),
+                suggestion: None,
+            };
+        }
+    };
+
+    if SAFE_DEVICES.contains(&path.as_str()) || path.starts_with("/dev/fd/") {
+        return allow("redirect to device");
+    }
+
+    let Some(resolved) = paths::resolve_in(&path, cwd) else {
+        return PermissionResult {
+            permission: Permission::Ask,
+            reason: format!("redirect to unresolvable path '{}'", target),
+            suggestion: None,
+        };
+    };
+
+    if paths::is_under_tmp(&resolved) {
+        return allow("redirect to /tmp");
+    }
+
+    if let Some(dir) = project_dir
+        && paths::is_under(&resolved, dir)
+    {
+        if edit_mode {
+            return allow("redirect to project dir");
+        }
+        return PermissionResult {
+            permission: Permission::Ask,
+            reason: format!("redirect writes to '{}' (not in edit mode)", target),
+            suggestion: None,
+        };
+    }
+
+    PermissionResult {
+        permission: Permission::Ask,
+        reason: format!("redirect writes outside project dir: '{}'", target),
+        suggestion: None,
+    }
+}
+
+fn allow(reason: &str) -> PermissionResult {
+    PermissionResult {
+        permission: Permission::Allow,
+        reason: reason.to_string(),
+        suggestion: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_cmd(redirects: &[(&str, &str)]) -> Command {
+        Command {
+            name: "echo".to_string(),
+            args: vec!["x".to_string()],
+            text: "echo x".to_string(),
+            redirects: redirects
+                .iter()
+                .map(|(op, target)| Redirect {
+                    operator: op.to_string(),
+                    target: crate::quote::unquote(target),
+                    raw_target: target.to_string(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_no_redirects() {
+        let cmd = make_cmd(&[]);
+        assert!(check_redirects(&cmd, false, None, &[], None).is_none());
+    }
+
+    #[test]
+    fn test_input_redirect_ignored() {
+        let cmd = make_cmd(&[("<", "/etc/passwd")]);
+        assert!(check_redirects(&cmd, false, None, &[], None).is_none());
+    }
+
+    #[test]
+    fn test_dev_null_allowed() {
+        let cmd = make_cmd(&[(">", "/dev/null"), (">&", "1")]);
+        let result = check_redirects(&cmd, false, None, &[], None).unwrap();
+        assert_eq!(result.permission, Permission::Allow);
+    }
+
+    #[test]
+    fn test_tmp_allowed() {
+        let cmd = make_cmd(&[(">>", "/tmp/claude/out.log")]);
+        let result = check_redirects(&cmd, false, None, &[], None).unwrap();
+        assert_eq!(result.permission, Permission::Allow);
+    }
+
+    #[test]
+    fn test_tmp_itself_asks() {
+        let cmd = make_cmd(&[(">", "/tmp")]);
+        let result = check_redirects(&cmd, true, None, &[], None).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_home_dotfile_asks() {
+        let cmd = make_cmd(&[(">", "~/.bashrc")]);
+        let result = check_redirects(&cmd, true, Some("/home/test/project"), &[], None);
+        assert_eq!(result.unwrap().permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_etc_asks() {
+        let cmd = make_cmd(&[(">>", "/etc/hosts")]);
+        let result = check_redirects(&cmd, true, None, &[], None).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_project_file_requires_edit_mode() {
+        let cwd = Some("/home/test/project");
+        let cmd = make_cmd(&[(">", "out.txt")]);
+        let result = check_redirects(&cmd, false, cwd, &[], cwd).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+
+        let result = check_redirects(&cmd, true, cwd, &[], cwd).unwrap();
+        assert_eq!(result.permission, Permission::Allow);
+    }
+
+    #[test]
+    fn test_expansion_asks() {
+        let cmd = make_cmd(&[(">", "$HOME/x")]);
+        let result = check_redirects(&cmd, true, None, &[], None).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_target_expanded_like_the_shell() {
+        let check = |target: &str, assigned: &[String]| {
+            let cmd = make_cmd(&[(">", target)]);
+            let cwd = Some("/tmp/claude");
+            check_redirects(&cmd, false, cwd, assigned, None)
+                .unwrap()
+                .permission
+        };
+        assert_eq!(check("'/tmp/claude/a b'", &[]), Permission::Allow);
+        assert_eq!(check("$PWD/out.txt", &[]), Permission::Allow);
+        assert_eq!(check("~/out.txt", &[]), Permission::Ask);
+        // `$HOME` expands to the same file as `~`
+        assert_eq!(check("$HOME/out.txt", &[]), Permission::Ask);
+        // Set by the command line, so only known at runtime
+        assert_eq!(check("$PWD/out.txt", &["PWD".to_string()]), Permission::Ask);
+        assert_eq!(check("$(mktemp)", &[]), Permission::Ask);
+        // Several words: bash refuses the redirect, but it can't be checked
+        assert_eq!(check("/tmp/claude/{a,b}", &[]), Permission::Ask);
+    }
+
+    #[test]
+    fn test_relative_target_with_unknown_cwd_asks() {
+        let cmd = make_cmd(&[(">", "out.txt")]);
+        let result = check_redirects(&cmd, true, None, &[], Some("/home/test/project")).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_relative_target_resolved_against_cwd() {
+        let cmd = make_cmd(&[(">", "out.txt")]);
+        let project = Some("/home/test/project");
+        let result = check_redirects(&cmd, false, Some("/tmp/claude"), &[], project).unwrap();
+        assert_eq!(result.permission, Permission::Allow);
+        let result = check_redirects(&cmd, true, Some("/etc"), &[], project).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+    }
+}
@@ -4,35 +4,67 @@
 
 use crate::analyzer::Command;
 use crate::config::{Permission, PermissionResult};
-use std::process::Command as ProcessCommand;
+use crate::expand;
+use crate::paths;
+use crate::stdin::PipedOperands;
 
 /// Check if an rm command should be auto-allowed
-/// Allows deletion of files under /tmp/ or the project directory (initial_cwd)
-pub fn check_rm(cmd: &Command, initial_cwd: Option<&str>) -> Option<PermissionResult> {
+/// Allows deletion of files under /tmp/ or the project directory
+/// Relative paths are resolved against `cwd`, the directory rm runs in (None if unknown),
+/// and variables in `assigned` (set by the command line) are unknown
+///
+/// `piped` holds operands from xargs or a read loop, which are only safe when
+/// bounded by the roots of the command producing them
+pub fn check_rm(
+    cmd: &Command,
+    cwd: Option<&str>,
+    assigned: &[String],
+    project_dir: Option<&str>,
+    piped: Option<&PipedOperands>,
+) -> Option<PermissionResult> {
     if cmd.name != "rm" {
         return None;
     }
 
-    // Extract file arguments (skip flags)
+    // Extract file arguments (skip flags) as written, so they can be expanded
     let file_args: Vec<&str> = cmd
         .args
         .iter()
-        .filter(|a| !a.starts_with('-'))
-        .map(|s| s.as_str())
+        .enumerate()
+        .filter(|(_, a)| !a.starts_with('-'))
+        .map(|(i, _)| cmd.raw_arg(i))
         .collect();
 
+    // Operands appended by xargs (`find /tmp/x | xargs rm`)
+    let mut uses_piped = piped.is_some_and(|p| p.appended);
+
     // No files specified - let normal handling deal with it
-    if file_args.is_empty() {
+    if file_args.is_empty() && !uses_piped {
         return None;
     }
 
-    // Check each file argument
-    for path in &file_args {
-        if !is_safe_path(path, initial_cwd) {
+    // Check every path each argument expands to (~, $VAR, {a,b}, globs)
+    for word in &file_args {
+        if let Some(piped) = piped {
+            if piped.is_placeholder(word) {
+                uses_piped = true;
+                continue;
+            }
+            // Built from an operand (`{}.bak`, `$f/x`): can't be bounded
+            if piped.mentions(word) {
+                return None;
+            }
+        }
+        let paths = expand::expand_word(word, cwd, assigned)?;
+        if paths.is_empty() || !paths.iter().all(|p| is_safe_path(p, cwd, project_dir)) {
             return None;
         }
     }
 
+    if uses_piped && !piped.is_some_and(|p| p.within(|path| is_safe_path(path, cwd, project_dir))) {
+        return None;
+    }
+
     Some(PermissionResult {
         permission: Permission::Allow,
         reason: "rm in /tmp or project dir".to_string(),
@@ -41,78 +73,11 @@ pub fn check_rm(cmd: &Command, initial_cwd: Option<&str>) -> Option<PermissionRe
 }
 
 /// Check if a path is safe to delete (under /tmp/ or project dir)
-fn is_safe_path(path: &str, initial_cwd: Option<&str>) -> bool {
-    if path.is_empty() {
-        return false;
-    }
-
-    if path.contains('\0') || path.contains('\n') {
-        return false;
-    }
-
-    let resolved = match resolve_path(path) {
-        Some(p) => p,
-        None => {
-            // Path doesn't exist - check parent
-            if let Some(parent) = std::path::Path::new(path).parent() {
-                if let Some(parent_str) = parent.to_str() {
-                    if !parent_str.is_empty() {
-                        if let Some(resolved_parent) = resolve_path(parent_str) {
-                            return is_under_allowed_dir(&resolved_parent, initial_cwd);
-                        }
-                    }
-                }
-            }
-            return false;
-        }
-    };
-
-    is_under_allowed_dir(&resolved, initial_cwd)
-}
-
-/// Check if a resolved path is under /tmp/ or project dir
-fn is_under_allowed_dir(resolved: &str, initial_cwd: Option<&str>) -> bool {
-    // Allow /tmp/
-    if resolved.starts_with("/tmp/") {
-        let after = &resolved[5..];
-        if !after.is_empty() && !after.chars().all(|c| c == '/') {
-            return true;
-        }
-    }
-
-    // Allow project directory
-    if let Some(cwd) = initial_cwd {
-        let cwd_prefix = if cwd.ends_with('/') {
-            cwd.to_string()
-        } else {
-            format!("{}/", cwd)
-        };
-
-        if resolved.starts_with(&cwd_prefix) {
-            return true;
-        }
-    }
-
-    false
-}
-
-/// Resolve a path using realpath
-fn resolve_path(path: &str) -> Option<String> {
-    let output = ProcessCommand::new("realpath")
-        .arg("-m") // don't require path to exist
-        .arg("--")
-        .arg(path)
-        .output()
-        .ok()?;
-
-    if output.status.success() {
-        let resolved = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if !resolved.is_empty() {
-            return Some(resolved);
-        }
-    }
-
-    None
+fn is_safe_path(path: &str, cwd: Option<&str>, project_dir: Option<&str>) -> bool {
+    paths::resolve_in(path, cwd).is_some_and(|resolved| {
+        paths::is_under_tmp(&resolved)
+            || project_dir.is_some_and(|dir| paths::is_under(&resolved, dir))
+    })
 }
 
 #[cfg(test)]
@@ -124,41 +89,42 @@ mod tests {
             name: "rm".to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("rm {}", args.join(" ")),
+            ..Default::default()
         }
     }
 
     #[test]
     fn test_rm_tmp_file() {
         let cmd = make_cmd(&["/tmp/test.txt"]);
-        let result = check_rm(&cmd, None).unwrap();
+        let result = check_rm(&cmd, None, &[], None, None).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
 
     #[test]
     fn test_rm_tmp_subdir() {
         let cmd = make_cmd(&["-rf", "/tmp/mydir/subdir"]);
-        let result = check_rm(&cmd, None).unwrap();
+        let result = check_rm(&cmd, None, &[], None, None).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
 
     #[test]
     fn test_rm_tmp_itself_not_allowed() {
         let cmd = make_cmd(&["-rf", "/tmp"]);
-        let result = check_rm(&cmd, None);
+        let result = check_rm(&cmd, None, &[], None, None);
         assert!(result.is_none()); // passthrough
     }
 
     #[test]
     fn test_rm_tmp_slash_not_allowed() {
         let cmd = make_cmd(&["-rf", "/tmp/"]);
-        let result = check_rm(&cmd, None);
+        let result = check_rm(&cmd, None, &[], None, None);
         assert!(result.is_none()); // passthrough
     }
 
     #[test]
     fn test_rm_home_not_allowed() {
         let cmd = make_cmd(&["/home/user/file"]);
-        let result = check_rm(&cmd, None);
+        let result = check_rm(&cmd, None, &[], None, None);
         assert!(result.is_none()); // passthrough
     }
 
@@ -166,28 +132,130 @@ mod tests {
     fn test_rm_project_file() {
         // Use /syncthing as project dir since it exists on this system
         let cmd = make_cmd(&["/syncthing/Sync/Projects/test/target/debug/test"]);
-        let result = check_rm(&cmd, Some("/syncthing/Sync/Projects/test")).unwrap();
+        let result =
+            check_rm(&cmd, None, &[], Some("/syncthing/Sync/Projects/test"), None).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
 
     #[test]
     fn test_rm_outside_project() {
         let cmd = make_cmd(&["/var/other/file"]);
-        let result = check_rm(&cmd, Some("/syncthing/Sync/Projects/test"));
+        let result = check_rm(&cmd, None, &[], Some("/syncthing/Sync/Projects/test"), None);
         assert!(result.is_none()); // passthrough
     }
 
+    #[test]
+    fn test_rm_relative_to_cwd() {
+        // Simulates: cd /tmp/build && rm -rf out
+        let cmd = make_cmd(&["-rf", "out"]);
+        let result = check_rm(
+            &cmd,
+            Some("/tmp/build"),
+            &[],
+            Some("/home/user/project"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(result.permission, Permission::Allow);
+
+        // Simulates: cd /etc && rm passwd, started in the project
+        let cmd = make_cmd(&["passwd"]);
+        assert!(check_rm(&cmd, Some("/etc"), &[], Some("/home/user/project"), None).is_none());
+        // Unknown cwd (cd in a conditional)
+        assert!(check_rm(&cmd, None, &[], Some("/home/user/project"), None).is_none());
+    }
+
+    #[test]
+    fn test_rm_expands_words() {
+        let project = Some("/home/user/project");
+        let raw = |args: &[&str]| Command {
+            raw_args: args.iter().map(|s| s.to_string()).collect(),
+            ..make_cmd(args)
+        };
+
+        // ~ is the home directory, not a directory named ~ in the project
+        assert!(check_rm(&raw(&["-rf", "~/Documents"]), project, &[], project, None).is_none());
+        assert!(check_rm(&raw(&["-rf", "$HOME/x"]), project, &[], project, None).is_none());
+        assert!(
+            check_rm(
+                &raw(&["-rf", "/tmp/../home/*"]),
+                project,
+                &[],
+                project,
+                None
+            )
+            .is_none()
+        );
+        assert!(check_rm(&raw(&["-rf", "$(pwd)/x"]), project, &[], project, None).is_none());
+        assert!(check_rm(&raw(&["-rf", "/tmp/{a,b}"]), project, &[], project, None).is_some());
+        assert!(
+            check_rm(
+                &raw(&["-rf", "/tmp/{a,../etc}"]),
+                project,
+                &[],
+                project,
+                None
+            )
+            .is_none()
+        );
+        // A quoted ~ is a file in the cwd
+        assert!(check_rm(&raw(&["'~'"]), project, &[], project, None).is_some());
+    }
+
+    #[test]
+    fn test_rm_piped_operands() {
+        let project = Some("/home/user/project");
+        let mut appended = PipedOperands::appended();
+        // find / | xargs rm
+        appended.roots = Some(vec!["/".to_string()]);
+        assert!(check_rm(&make_cmd(&["-f"]), project, &[], project, Some(&appended)).is_none());
+        // find /tmp/claude | xargs rm: a file named `a /etc/passwd` is two operands
+        appended.roots = Some(vec!["/tmp/claude".to_string()]);
+        assert!(check_rm(&make_cmd(&["-f"]), project, &[], project, Some(&appended)).is_none());
+        // find /tmp/claude -print0 | xargs -0 rm
+        appended.null_separated = true;
+        assert!(check_rm(&make_cmd(&["-f"]), project, &[], project, Some(&appended)).is_some());
+        assert!(
+            check_rm(
+                &make_cmd(&["/etc/x"]),
+                project,
+                &[],
+                project,
+                Some(&appended)
+            )
+            .is_none()
+        );
+        // Unknown producer
+        appended.roots = None;
+        assert!(check_rm(&make_cmd(&["-f"]), project, &[], project, Some(&appended)).is_none());
+
+        // xargs -I{} rm {} / {}.bak
+        let mut replacing = PipedOperands::replacing("{}", &[]);
+        replacing.roots = Some(vec!["/tmp/claude".to_string()]);
+        replacing.null_separated = true;
+        let cmd = |args: &[&str]| Command {
+            raw_args: args.iter().map(|s| s.to_string()).collect(),
+            ..make_cmd(args)
+        };
+        assert!(check_rm(&cmd(&["{}"]), project, &[], project, Some(&replacing)).is_some());
+        assert!(check_rm(&cmd(&["{}.bak"]), project, &[], project, Some(&replacing)).is_none());
+        // The operands aren't used
+        replacing.roots = None;
+        assert!(check_rm(&cmd(&["/tmp/a"]), project, &[], project, Some(&replacing)).is_some());
+        assert!(check_rm(&cmd(&["{}"]), project, &[], project, Some(&replacing)).is_none());
+    }
+
     #[test]
     fn test_rm_multiple_tmp_files() {
         let cmd = make_cmd(&["/tmp/a", "/tmp/b", "/tmp/c"]);
-        let result = check_rm(&cmd, None).unwrap();
+        let result = check_rm(&cmd, None, &[], None, None).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
 
     #[test]
     fn test_rm_mixed_paths_not_allowed() {
         let cmd = make_cmd(&["/tmp/a", "/home/user/b"]);
-        let result = check_rm(&cmd, None);
+        let result = check_rm(&cmd, None, &[], None, None);
         assert!(result.is_none()); // passthrough because /home path
     }
 
@@ -197,8 +265,9 @@ mod tests {
             name: "ls".to_string(),
             args: vec!["/tmp".to_string()],
             text: "ls /tmp".to_string(),
+            ..Default::default()
         };
-        let result = check_rm(&cmd, None);
+        let result = check_rm(&cmd, None, &[], None, None);
         assert!(result.is_none());
     }
 }
@@ -0,0 +1,225 @@
+//! Script content inspection
+//!
+//! With `[scripts] inspect = true`, a command that runs a script file
+//! (`bash run.sh`, `./run.sh`, `source env.sh`) is decided by what the script
+//! does rather than by its path. The file is read from disk and analyzed with
+//! the bash, nushell or fish analyzer, picked by shebang or extension.
+
+use crate::analyzer::Command;
+use crate::config::ScriptsConfig;
+use std::path::{Path, PathBuf};
+
+/// Shell language of a script or a command string (`nu -c '...'`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    Bash,
+    Nushell,
+    Fish,
+}
+
+/// Outcome of reading a script for inspection
+#[derive(Debug)]
+pub enum ScriptRead {
+    /// The script contents, ready to analyze
+    Contents {
+        path: PathBuf,
+        content: String,
+        language: Language,
+    },
+    /// A shell script that can't be inspected, with the reason
+    Uninspectable(String),
+    /// The file doesn't exist (yet), so what it will run is unknown
+    Missing,
+    /// Not a shell script (a directory, binary or another interpreter); the path is checked instead
+    Skipped,
+}
+
+/// Read a script file, resolving relative paths against `cwd`
+pub fn read_script(config: &ScriptsConfig, path: &str, cwd: Option<&str>) -> ScriptRead {
+    let (resolved, content) = match read_text(config, path, cwd) {
+        Ok(read) => read,
+        Err(skipped_or_uninspectable) => return skipped_or_uninspectable,
+    };
+    let Some(language) = detect_language(&resolved, &content) else {
+        return ScriptRead::Skipped;
+    };
+
+    ScriptRead::Contents {
+        path: resolved,
+        content,
+        language,
+    }
+}
+
+/// Read a text file fed to a command (`sqlite3 db < schema.sql`), resolving
+/// relative paths against `cwd`
+/// Fails with [`ScriptRead::Missing`] for missing files, [`ScriptRead::Skipped`]
+/// for other unreadable or binary files and [`ScriptRead::Uninspectable`] for
+/// files over the size limit
+pub fn read_text(
+    config: &ScriptsConfig,
+    path: &str,
+    cwd: Option<&str>,
+) -> Result<(PathBuf, String), ScriptRead> {
+    let resolved = resolve_path(path, cwd);
+    let metadata = match std::fs::metadata(&resolved) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(ScriptRead::Missing),
+        Err(_) => return Err(ScriptRead::Skipped),
+    };
+    if !metadata.is_file() {
+        return Err(ScriptRead::Skipped);
+    }
+    if metadata.len() > config.max_size_kb * 1024 {
+        return Err(ScriptRead::Uninspectable(format!(
+            "script {} is larger than {} KB",
+            resolved.display(),
+            config.max_size_kb
+        )));
+    }
+
+    // Binaries aren't valid UTF-8
+    match std::fs::read_to_string(&resolved) {
+        Ok(content) => Ok((resolved, content)),
+        Err(_) => Err(ScriptRead::Skipped),
+    }
+}
+
+/// Pick the analyzer from the shebang, falling back to the extension
+fn detect_language(path: &Path, content: &str) -> Option<Language> {
+    if let Some(shebang) = content.lines().next().and_then(|l| l.strip_prefix("#!")) {
+        let mut words = shebang.split_whitespace();
+        let mut interpreter = words.next()?.rsplit('/').next()?;
+        // #!/usr/bin/env -S nu
+        if interpreter == "env" {
+            interpreter = words.find(|w| !w.starts_with('-'))?;
+        }
+        return match interpreter {
+            "sh" | "bash" | "zsh" | "dash" | "ksh" => Some(Language::Bash),
+            "nu" => Some(Language::Nushell),
+            "fish" => Some(Language::Fish),
+            _ => None,
+        };
+    }
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("nu") => Some(Language::Nushell),
+        Some("fish") => Some(Language::Fish),
+        Some("sh" | "bash" | "zsh") | None => Some(Language::Bash),
+        Some(_) => None,
+    }
+}
+
+/// Files a command writes (output redirects, `tee` operands), resolved against
+/// `cwd`, so a later command running one of them as a script can be caught
+pub fn written_paths(cmd: &Command, cwd: Option<&str>) -> Vec<PathBuf> {
+    let redirects = cmd
+        .redirects
+        .iter()
+        .filter(|r| r.is_write() && !r.is_fd_duplication())
+        .map(|r| r.target.as_str());
+    let tee = cmd
+        .args
+        .iter()
+        .filter(|_| cmd.name == "tee")
+        .filter(|a| !a.starts_with('-'))
+        .map(String::as_str);
+    redirects
+        .chain(tee)
+        .map(|path| resolve_path(path, cwd))
+        .collect()
+}
+
+/// Resolve `~/` and relative paths
+pub fn resolve_path(path: &str, cwd: Option<&str>) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        return Path::new(&std::env::var("HOME").unwrap_or_default()).join(rest);
+    }
+    let path = Path::new(path);
+    match cwd {
+        Some(cwd) if path.is_relative() => Path::new(cwd).join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_detect_language() {
+        let sh = Path::new("run.sh");
+        assert_eq!(detect_language(sh, "#!/bin/bash\nls"), Some(Language::Bash));
+        assert_eq!(
+            detect_language(Path::new("run"), "#!/usr/bin/env -S nu\nls"),
+            Some(Language::Nushell)
+        );
+        assert_eq!(
+            detect_language(Path::new("x.nu"), "ls"),
+            Some(Language::Nushell)
+        );
+        assert_eq!(
+            detect_language(Path::new("run"), "ls"),
+            Some(Language::Bash)
+        );
+        assert_eq!(
+            detect_language(Path::new("x.fish"), "ls"),
+            Some(Language::Fish)
+        );
+        assert_eq!(detect_language(sh, "#!/usr/bin/env python3\n"), None);
+        assert_eq!(detect_language(Path::new("x.py"), "print()"), None);
+    }
+
+    #[test]
+    fn test_read_script() {
+        let dir = temp_dir("script");
+        std::fs::write(dir.join("run.sh"), "rm -rf ~\n").unwrap();
+        std::fs::write(dir.join("big.sh"), "#".repeat(2048)).unwrap();
+        let config = ScriptsConfig {
+            inspect: true,
+            max_size_kb: 1,
+            max_depth: 3,
+        };
+        let cwd = dir.to_str();
+
+        match read_script(&config, "./run.sh", cwd) {
+            ScriptRead::Contents {
+                content, language, ..
+            } => {
+                assert_eq!(content, "rm -rf ~\n");
+                assert_eq!(language, Language::Bash);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(
+            read_script(&config, "big.sh", cwd),
+            ScriptRead::Uninspectable(_)
+        ));
+        assert!(matches!(
+            read_script(&config, "missing.sh", cwd),
+            ScriptRead::Missing
+        ));
+        assert!(matches!(
+            read_script(&config, ".", cwd),
+            ScriptRead::Skipped
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_written_paths() {
+        let analysis = crate::analyzer::analyze("echo x > a.sh 2>&1 | tee -a ~/b.sh /tmp/c.sh");
+        let cwd = Some("/work");
+        assert_eq!(
+            written_paths(&analysis.commands[0], cwd),
+            vec![PathBuf::from("/work/a.sh")]
+        );
+        let home = std::env::var("HOME").unwrap_or_default();
+        assert_eq!(
+            written_paths(&analysis.commands[1], cwd),
+            vec![Path::new(&home).join("b.sh"), PathBuf::from("/tmp/c.sh")]
+        );
+    }
+}
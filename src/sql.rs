@@ -1,24 +1,23 @@
 //! SQL query analysis for mysql/mariadb/sqlite3 commands
+//!
+//! The query comes from the command line (`mysql -e`, `sqlite3 db "..."`) or,
+//! without one, from a heredoc or here-string (`mysql db <<< "SELECT 1"`).
 
-use crate::analyzer::Command;
+use crate::analyzer::{Command, Input};
 use crate::config::{Permission, PermissionResult};
 
-/// Strip surrounding quotes from a query string (including escaped quotes)
-fn strip_quotes(query: &str) -> String {
-    let query = query.trim();
-    // Handle escaped quotes first
-    let query = query.strip_prefix("\\\"").unwrap_or(query);
-    let query = query.strip_suffix("\\\"").unwrap_or(query);
-    // Then regular quotes
-    let query = query.strip_prefix('"').unwrap_or(query);
-    let query = query.strip_suffix('"').unwrap_or(query);
-    let query = query.strip_prefix('\'').unwrap_or(query);
-    let query = query.strip_suffix('\'').unwrap_or(query);
-    query.to_string()
-}
+/// Client commands whose queries are checked as mysql/mariadb queries
+pub const MYSQL_COMMANDS: &[&str] = &[
+    "mysql",
+    "mariadb",
+    "mysql-prod",
+    "mysql-prod-root",
+    "mysql-external",
+    "mysql-replication",
+];
 
 /// Check if a SQL query is read-only
-fn check_query_readonly(query: &str) -> PermissionResult {
+pub fn check_query_readonly(query: &str) -> PermissionResult {
     // Read-only SQL statements
     let read_only_prefixes = [
         "SELECT",
@@ -43,8 +42,31 @@ fn check_query_readonly(query: &str) -> PermissionResult {
         ".DATABASES",
     ];
 
-    // Split by semicolons and check ALL statements
-    for statement in query.split(';') {
+    // Client commands run shell commands or other files (mysql `\! rm`, `\. x.sql`)
+    if query.contains("\\!") || query.contains("\\.") {
+        return PermissionResult {
+            permission: Permission::Ask,
+            reason: "SQL client command".to_string(),
+            suggestion: None,
+        };
+    }
+
+    // sqlite3 dot commands end at the newline, SQL statements at a semicolon
+    let mut statements = Vec::new();
+    let mut sql = String::new();
+    for line in query.lines() {
+        let pending = sql.rsplit(';').next().unwrap_or_default();
+        if line.trim_start().starts_with('.') && pending.trim().is_empty() {
+            statements.push(line.to_string());
+        } else {
+            sql.push_str(line);
+            sql.push('\n');
+        }
+    }
+    statements.extend(sql.split(';').map(String::from));
+
+    // Check ALL statements
+    for statement in &statements {
         let trimmed = statement.trim().to_uppercase();
         if trimmed.is_empty() {
             continue;
@@ -66,25 +88,17 @@ fn check_query_readonly(query: &str) -> PermissionResult {
 }
 
 /// Extract query from mysql/mariadb command
-fn extract_mysql_query(cmd: &Command) -> Option<String> {
-    let mut iter = cmd.args.iter().enumerate();
-    while let Some((idx, arg)) = iter.next() {
+/// Arguments are taken with their quoting already removed by the analyzer (or
+/// by the wrapper that passed the command on, `ssh host "mysql -e \"...\""`)
+fn extract_mysql_query(cmd: &Command) -> Option<&str> {
+    let iter = cmd.args.iter().enumerate();
+    for (idx, arg) in iter {
         if arg == "-e" || arg == "--execute" {
-            // Check if next arg starts with escaped quote - if so, join until closing quote
-            if let Some(next) = cmd.args.get(idx + 1) {
-                if next.starts_with("\\\"") || next.starts_with("\"") {
-                    // Join all args from here until we find one ending with escaped quote
-                    let remaining: Vec<&str> =
-                        cmd.args[idx + 1..].iter().map(|s| s.as_str()).collect();
-                    return Some(remaining.join(" "));
-                }
-                return Some(next.clone());
-            }
-            return None;
-        } else if arg.starts_with("-e") {
-            return Some(arg[2..].to_string());
-        } else if arg.starts_with("--execute=") {
-            return Some(arg[10..].to_string());
+            return cmd.args.get(idx + 1).map(String::as_str);
+        } else if let Some(query) = arg.strip_prefix("--execute=") {
+            return Some(query);
+        } else if let Some(query) = arg.strip_prefix("-e") {
+            return Some(query);
         }
     }
     None
@@ -121,25 +135,49 @@ fn extract_sqlite3_query(cmd: &Command) -> Option<&str> {
 
 /// Check if a mysql/mariadb command has a read-only query
 pub fn check_mysql_query(cmd: &Command) -> Option<PermissionResult> {
-    let query = extract_mysql_query(cmd)?;
-    Some(check_query_readonly(&strip_quotes(&query)))
+    match extract_mysql_query(cmd) {
+        Some(query) => Some(check_query_readonly(query)),
+        None => check_stdin_query(cmd),
+    }
 }
 
 /// Check if a sqlite3 command has a read-only query
 pub fn check_sqlite3_query(cmd: &Command) -> Option<PermissionResult> {
-    let query = extract_sqlite3_query(cmd)?;
-    Some(check_query_readonly(&strip_quotes(query)))
+    match extract_sqlite3_query(cmd) {
+        Some(query) => Some(check_query_readonly(query)),
+        None => check_stdin_query(cmd),
+    }
+}
+
+/// Check a query fed through a heredoc or here-string
+fn check_stdin_query(cmd: &Command) -> Option<PermissionResult> {
+    match cmd.input()? {
+        Input::Text(query) => Some(check_query_readonly(query)),
+        // Expansions can add any statement to the query
+        Input::Dynamic(_) => Some(PermissionResult {
+            permission: Permission::Ask,
+            reason: "SQL query only known at runtime".to_string(),
+            suggestion: None,
+        }),
+        Input::File(_) => None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// The first command of a command line, with its words unquoted
+    fn parse(command: &str) -> Command {
+        crate::analyzer::analyze(command).commands.remove(0)
+    }
+
     fn make_cmd(name: &str, args: &[&str]) -> Command {
         Command {
             name: name.to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("{} {}", name, args.join(" ")),
+            ..Default::default()
         }
     }
 
@@ -154,7 +192,7 @@ mod tests {
 
     #[test]
     fn test_select_with_quotes_allowed() {
-        let cmd = make_cmd("mysql", &["-e", "\"SELECT * FROM users\""]);
+        let cmd = parse("mysql -e \"SELECT * FROM users\"");
         let result = check_mysql_query(&cmd).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
@@ -240,10 +278,7 @@ mod tests {
 
     #[test]
     fn test_sqlite3_select_with_quotes() {
-        let cmd = make_cmd(
-            "sqlite3",
-            &["/data/db.sqlite3", "\"SELECT uuid FROM orgs;\""],
-        );
+        let cmd = parse("sqlite3 /data/db.sqlite3 'SELECT uuid FROM orgs;'");
         let result = check_sqlite3_query(&cmd).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
@@ -285,10 +320,56 @@ mod tests {
 
     #[test]
     fn test_escaped_quotes() {
-        // Simulating what happens after SSH unwraps: mariadb -e \"SHOW MASTER STATUS\"
-        let cmd = make_cmd("mariadb", &["-e", "\\\"SHOW", "MASTER", "STATUS\\\""]);
-        let result = check_mysql_query(&cmd);
-        assert!(result.is_some());
-        assert_eq!(result.unwrap().permission, Permission::Allow);
+        // What ssh hands on for `ssh db "mariadb -e \"SHOW MASTER STATUS\""`
+        let cmd = parse("mariadb -e \"SHOW MASTER STATUS\"");
+        let result = check_mysql_query(&cmd).unwrap();
+        assert_eq!(result.permission, Permission::Allow);
+        // Quotes inside the query are part of it, not another layer to strip
+        let cmd = parse("mysql -e '\"DROP TABLE users\"'");
+        let result = check_mysql_query(&cmd).unwrap();
+        assert_eq!(result.permission, Permission::Ask);
+    }
+
+    #[test]
+    fn test_stdin_query() {
+        let with_input = |name: &str, args: &[&str], query: &str| Command {
+            stdin: Some(crate::analyzer::Stdin {
+                input: Some(Input::Text(query.to_string())),
+                ..Default::default()
+            }),
+            ..make_cmd(name, args)
+        };
+
+        let cmd = with_input("mysql", &["db"], "DROP TABLE x\n");
+        assert_eq!(check_mysql_query(&cmd).unwrap().permission, Permission::Ask);
+        let cmd = with_input("mysql", &["db"], "SELECT 1;\nSHOW TABLES;\n");
+        assert_eq!(
+            check_mysql_query(&cmd).unwrap().permission,
+            Permission::Allow
+        );
+        let cmd = with_input("sqlite3", &["app.db"], ".tables\nDELETE FROM users;\n");
+        assert_eq!(
+            check_sqlite3_query(&cmd).unwrap().permission,
+            Permission::Ask
+        );
+        let cmd = with_input("sqlite3", &["app.db"], ".schema\nSELECT *\nFROM users;\n");
+        assert_eq!(
+            check_sqlite3_query(&cmd).unwrap().permission,
+            Permission::Allow
+        );
+        // The command line query wins over stdin
+        let cmd = with_input("mysql", &["-e", "SELECT 1"], "DROP TABLE x");
+        assert_eq!(
+            check_mysql_query(&cmd).unwrap().permission,
+            Permission::Allow
+        );
+    }
+
+    #[test]
+    fn test_client_commands_ask() {
+        let result = check_query_readonly("SELECT 1 \\! rm -rf /");
+        assert_eq!(result.permission, Permission::Ask);
+        let result = check_query_readonly(".shell rm -rf /");
+        assert_eq!(result.permission, Permission::Ask);
     }
 }
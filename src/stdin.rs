@@ -0,0 +1,423 @@
+//! Operands read from stdin
+//!
+//! `find . | xargs rm`, `find . | while read f; do rm "$f"; done` and
+//! `find . | parallel rm` act on paths that only exist once the pipeline runs,
+//! so they are unknown to path checks. When the producer is analyzable, such as
+//! `find <root> -print0` or `fd -0 . <root>`, every path it prints is under one
+//! of its roots, which then bound the operands. Only NUL separates paths
+//! reliably: plain xargs splits at blanks and quotes and `read` at newlines, so
+//! a file named `a /etc/passwd` becomes two operands. Both sides must use NULs
+//! (`xargs -0`, `read -d ''`).
+
+use crate::analyzer::{Command, Stdin};
+use crate::expand;
+
+/// Operands a command receives from stdin, through xargs, parallel or a read loop
+#[derive(Debug, Clone, Default)]
+pub struct PipedOperands {
+    /// Directories every operand is under, None if unknown
+    pub roots: Option<Vec<String>>,
+    /// Whether the operands come from stdin (so a producer can bound them)
+    pub from_stdin: bool,
+    /// Whether the operands are appended to the command's own arguments (`xargs rm`)
+    pub appended: bool,
+    /// Whether the input is split at NULs (`xargs -0`, `read -d ''`), the only
+    /// way the roots of a producer writing NULs bound the operands
+    pub null_separated: bool,
+    /// Arguments replaced by a whole operand, as written (`{}`, `"$f"`)
+    placeholders: Vec<String>,
+    /// Text that marks an argument as built from an operand (`{}` in `{}.bak`)
+    tokens: Vec<String>,
+}
+
+impl PipedOperands {
+    /// Operands appended to the command line (`xargs rm`, `parallel rm`)
+    pub fn appended() -> Self {
+        PipedOperands {
+            from_stdin: true,
+            appended: true,
+            ..Default::default()
+        }
+    }
+
+    /// Operands substituted for a replace string (`xargs -I{} rm {}`)
+    /// `tokens` are other forms built from an operand (parallel's `{.}`)
+    pub fn replacing(replace: &str, tokens: &[&str]) -> Self {
+        let mut all_tokens = vec![replace.to_string()];
+        all_tokens.extend(tokens.iter().map(|t| t.to_string()));
+        PipedOperands {
+            from_stdin: true,
+            placeholders: vec![
+                replace.to_string(),
+                format!("'{}'", replace),
+                format!("\"{}\"", replace),
+            ],
+            tokens: all_tokens,
+            ..Default::default()
+        }
+    }
+
+    /// Operands held by the variables of an enclosing `while read VAR` loop
+    /// Only a double-quoted expansion is a whole operand; `$f` is split and globbed
+    pub fn from_read_loop(stdin: &Stdin, roots: Option<&[String]>) -> Option<Self> {
+        if stdin.variables.is_empty() {
+            return None;
+        }
+        let mut operands = PipedOperands {
+            roots: roots.map(<[String]>::to_vec),
+            from_stdin: true,
+            null_separated: stdin.null_separated,
+            ..Default::default()
+        };
+        for var in &stdin.variables {
+            operands.placeholders.push(format!("\"${}\"", var));
+            operands.placeholders.push(format!("\"${{{}}}\"", var));
+            operands.tokens.push(format!("${}", var));
+            operands.tokens.push(format!("${{{}", var));
+        }
+        Some(operands)
+    }
+
+    /// Whether an argument (as written) is replaced by a whole operand
+    pub fn is_placeholder(&self, raw: &str) -> bool {
+        self.placeholders.iter().any(|p| p == raw)
+    }
+
+    /// Whether an argument (as written) is built from an operand
+    pub fn mentions(&self, raw: &str) -> bool {
+        self.tokens.iter().any(|t| raw.contains(t.as_str()))
+    }
+
+    /// Whether every operand passes `is_safe`: the input must be split at NULs,
+    /// the roots must be known, and each root and anything under it must be
+    /// safe (`find .` prints `.` itself, which rm refuses, so the root of `.`
+    /// and `..` only needs its contents)
+    pub fn within(&self, is_safe: impl Fn(&str) -> bool) -> bool {
+        let Some(roots) = self.roots.as_ref().filter(|_| self.null_separated) else {
+            return false;
+        };
+        !roots.is_empty()
+            && roots.iter().all(|root| {
+                let root = root.trim_end_matches('/');
+                let dots = root
+                    .rsplit('/')
+                    .next()
+                    .is_some_and(|c| c == "." || c == "..");
+                (dots || is_safe(root)) && is_safe(&format!("{}/x", root))
+            })
+    }
+}
+
+/// Directories bounding the data piped into command `index` of `commands`,
+/// given `dirs`, the directory each command runs in, and `assigned`, the
+/// variables the command line sets
+/// Returns None when the producer isn't `find -print0`/`fd -0` (possibly
+/// through filters like `sort -z` or `grep -z` that only drop paths)
+pub fn producer_roots(
+    commands: &[Command],
+    dirs: &[Option<String>],
+    assigned: &[String],
+    index: usize,
+) -> Option<Vec<String>> {
+    // A redirect (`xargs rm < list`) replaces the pipe
+    let piped_from = |i: usize| {
+        let stdin = commands[i].stdin.as_ref()?;
+        stdin.input.is_none().then_some(stdin.producer?)
+    };
+    commands.get(index)?;
+    let mut producer = piped_from(index)?;
+    while is_null_filter(&commands[producer]) {
+        producer = piped_from(producer)?;
+    }
+    let cmd = &commands[producer];
+    let words = match cmd.name.as_str() {
+        "find" => find_roots(cmd)?,
+        "fd" | "fdfind" => fd_roots(cmd)?,
+        _ => return None,
+    };
+
+    // Relative roots print relative paths, which the consumer resolves in its own cwd
+    let cwd = dirs.get(producer)?.as_deref();
+    let consumer_cwd = dirs.get(index)?.as_deref();
+    let mut roots = Vec::new();
+    for word in words {
+        for root in expand::expand_word(word, cwd, assigned)? {
+            if root.starts_with('/') {
+                roots.push(root);
+            } else if cwd.is_some() && cwd == consumer_cwd {
+                roots.push(format!("{}/{}", cwd?.trim_end_matches('/'), root));
+            } else {
+                return None;
+            }
+        }
+    }
+    Some(roots)
+}
+
+/// `find [-P] [root...] [expression] -print0`, roots as written (default `.`)
+/// Returns None without `-print0`, when symlinks are followed (`-H`, `-L`,
+/// `-follow`), so printed paths can lead outside the roots, or when the
+/// expression prints something other than paths or runs commands (`-printf`,
+/// `-ls`, `-exec`, ...)
+fn find_roots(cmd: &Command) -> Option<Vec<&str>> {
+    if !cmd.args.iter().any(|arg| arg == "-print0") {
+        return None;
+    }
+    let unbounded = cmd.args.iter().any(|arg| {
+        matches!(
+            arg.as_str(),
+            "-H" | "-L"
+                | "-follow"
+                | "-printf"
+                | "-ls"
+                | "-fls"
+                | "-exec"
+                | "-execdir"
+                | "-ok"
+                | "-okdir"
+        ) || arg.starts_with("-fprint")
+    });
+    if unbounded {
+        return None;
+    }
+
+    let mut roots = Vec::new();
+    for (i, arg) in cmd.args.iter().enumerate() {
+        if arg == "-P" && roots.is_empty() {
+            continue;
+        }
+        if arg.starts_with('-') || arg == "(" || arg == "!" {
+            break;
+        }
+        roots.push(cmd.raw_arg(i));
+    }
+    if roots.is_empty() {
+        roots.push(".");
+    }
+    Some(roots)
+}
+
+/// `fd -0 [options] [pattern] [path...]`, roots as written (default `.`)
+/// Returns None without `-0`, or for options that change what's printed or
+/// run (`-x`, `--base-directory`)
+fn fd_roots(cmd: &Command) -> Option<Vec<&str>> {
+    if !cmd.args.iter().any(|arg| arg == "-0" || arg == "--print0") {
+        return None;
+    }
+    let mut positional = Vec::new();
+    let mut search_paths = Vec::new();
+    let mut args = cmd.args.iter().enumerate();
+    while let Some((i, arg)) = args.next() {
+        match arg.as_str() {
+            "-x" | "--exec" | "-X" | "--exec-batch" | "--base-directory" => return None,
+            _ if arg.starts_with("--base-directory=") => return None,
+            "--search-path" => search_paths.push(cmd.raw_arg(args.next()?.0)),
+            "-e" | "--extension" | "-t" | "--type" | "-d" | "--max-depth" | "-E" | "--exclude"
+            | "-S" | "--size" | "-j" | "--threads" | "--changed-within" | "--changed-before"
+            | "-o" | "--owner" | "--min-depth" | "--exact-depth" => {
+                args.next();
+            }
+            _ if arg.starts_with('-') => {}
+            _ => positional.push(cmd.raw_arg(i)),
+        }
+    }
+
+    let mut roots: Vec<&str> = positional.into_iter().skip(1).collect();
+    roots.extend(search_paths);
+    if roots.is_empty() {
+        roots.push(".");
+    }
+    Some(roots)
+}
+
+/// Commands that pass some of their NUL-separated input through unchanged
+/// Line filters need `-z`: otherwise they split a path containing a newline
+/// and can reorder its pieces into new paths
+fn is_null_filter(cmd: &Command) -> bool {
+    let args = &cmd.args;
+    let zero = args.iter().any(|a| {
+        matches!(a.as_str(), "--zero-terminated" | "--null-data")
+            || (a.starts_with('-') && !a.starts_with("--") && a.contains('z'))
+    });
+    if cmd.name != "cat" && !zero {
+        return false;
+    }
+    match cmd.name.as_str() {
+        // Counts, byte offsets and file arguments change or replace the lines
+        "sort" | "uniq" | "head" | "tail" | "cat" => args.iter().all(|a| {
+            (a.starts_with('-') || a.chars().all(|c| c.is_ascii_digit()))
+                && !matches!(a.as_str(), "-c" | "--count" | "--bytes")
+                && !a.starts_with("--bytes=")
+                && !(cmd.name != "sort" && !a.starts_with("--") && a.contains('c'))
+        }),
+        "grep" | "egrep" | "fgrep" => {
+            // Only the pattern, no file to search
+            let (mut explicit, mut positional) = (false, 0);
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--null-data" => {}
+                    // Clusters of -v, -i, -E, -F, -G, -P, -x, -w, -s and -z
+                    _ if arg.len() > 1
+                        && arg[1..].chars().all(|c| "viEFGPxwsz".contains(c))
+                        && arg.starts_with('-') => {}
+                    "-e" => {
+                        explicit = true;
+                        args.next();
+                    }
+                    _ if arg.starts_with('-') => return false,
+                    _ => positional += 1,
+                }
+            }
+            if explicit {
+                positional == 0
+            } else {
+                positional == 1
+            }
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::analyze;
+
+    fn roots(command: &str, cwd: &str) -> Option<Vec<String>> {
+        let analysis = analyze(command);
+        let dirs = vec![Some(cwd.to_string()); analysis.commands.len()];
+        producer_roots(&analysis.commands, &dirs, &[], analysis.commands.len() - 1)
+    }
+
+    #[test]
+    fn test_find_roots() {
+        assert_eq!(
+            roots("find /tmp/claude -name x -print0 | xargs -0 rm", "/home"),
+            Some(vec!["/tmp/claude".to_string()])
+        );
+        assert_eq!(
+            roots("find -P a b -type f -print0 | xargs -0 rm", "/home/p"),
+            Some(vec!["/home/p/a".to_string(), "/home/p/b".to_string()])
+        );
+        // Newline-separated paths can be split into other paths
+        assert_eq!(roots("find /tmp/claude | xargs rm", "/home"), None);
+        // Followed symlinks lead outside the roots; other actions print
+        // something else than the found paths, or act on them directly
+        for command in [
+            "find -L a b -type f -print0 | xargs -0 rm",
+            "find -H /tmp/claude -print0 | xargs -0 rm",
+            "find /tmp/claude -follow -print0 | xargs -0 rm",
+            "find /tmp/claude -printf '/etc/%f\\0' -print0 | xargs -0 rm",
+            "find /tmp/claude -fprint /tmp/claude/list -print0 | xargs -0 rm",
+            "find /tmp/claude -exec echo /etc/passwd ';' -print0 | xargs -0 rm",
+            "find /tmp/claude -execdir pwd + -print0 | xargs -0 rm",
+            "find /tmp/claude -ok echo {} ';' -print0 | xargs -0 rm",
+            "find /tmp/claude -ls -print0 | xargs -0 rm",
+        ] {
+            assert_eq!(roots(command, "/home/p"), None, "{}", command);
+        }
+        assert_eq!(
+            roots("find -print0 | xargs -0 rm", "/home/p"),
+            Some(vec!["/home/p/.".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_fd_roots() {
+        assert_eq!(
+            roots("fd -0 . /tmp/claude | xargs -0 rm", "/home"),
+            Some(vec!["/tmp/claude".to_string()])
+        );
+        assert_eq!(
+            roots(
+                "fd --print0 -e log pat --search-path /tmp/a | xargs -0 rm",
+                "/home"
+            ),
+            Some(vec!["/tmp/a".to_string()])
+        );
+        assert_eq!(roots("fd . /tmp/claude | xargs rm", "/home"), None);
+        assert_eq!(roots("fd -0 -x rm . /tmp | xargs -0 rm", "/home"), None);
+    }
+
+    #[test]
+    fn test_roots_through_filters() {
+        assert_eq!(
+            roots(
+                "find /tmp/claude -print0 | grep -zv keep | sort -z | cat | xargs -0 rm",
+                "/home"
+            ),
+            Some(vec!["/tmp/claude".to_string()])
+        );
+        // Line filters can splice the pieces of a path with a newline
+        assert_eq!(
+            roots("find /tmp/claude -print0 | sort | xargs -0 rm", "/home"),
+            None
+        );
+        assert_eq!(
+            roots("find /tmp/claude -print0 | uniq -zc | xargs -0 rm", "/home"),
+            None
+        );
+        assert_eq!(
+            roots(
+                "find /tmp/claude -print0 | cat /etc/x | xargs -0 rm",
+                "/home"
+            ),
+            None
+        );
+        assert_eq!(
+            roots(
+                "find /tmp/claude -print0 | grep -zo x | xargs -0 rm",
+                "/home"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_unknown_producers() {
+        assert_eq!(roots("cat list.txt | xargs -0 rm", "/home"), None);
+        assert_eq!(
+            roots("find / -print0 | xargs -0 rm", "/home"),
+            Some(vec!["/".to_string()])
+        );
+        assert_eq!(roots("xargs -0 rm", "/home"), None);
+        assert_eq!(
+            roots("find /tmp/claude -print0 | xargs -0 rm < list", "/home"),
+            None
+        );
+
+        // find runs elsewhere (`(cd /tmp && find .) | xargs rm`): its relative
+        // output means nothing in the consumer's cwd
+        let analysis = analyze("find . -print0 | xargs -0 rm");
+        let dirs = vec![Some("/tmp".to_string()), Some("/home".to_string())];
+        assert_eq!(producer_roots(&analysis.commands, &dirs, &[], 1), None);
+        assert_eq!(roots("find $DIR -print0 | xargs -0 rm", "/home"), None);
+    }
+
+    #[test]
+    fn test_read_loop_placeholders() {
+        let analysis = analyze("find /tmp | while read -r f; do rm \"$f\" $f.bak; done");
+        let rm = analysis.commands.last().unwrap();
+        let operands = PipedOperands::from_read_loop(rm.stdin.as_ref().unwrap(), None).unwrap();
+        assert!(operands.is_placeholder(rm.raw_arg(0)));
+        assert!(!operands.is_placeholder(rm.raw_arg(1)));
+        assert!(operands.mentions(rm.raw_arg(1)));
+    }
+
+    #[test]
+    fn test_within() {
+        let mut operands = PipedOperands::appended();
+        let is_tmp = |p: &str| p.starts_with("/tmp/");
+        assert!(!operands.within(is_tmp));
+        operands.roots = Some(vec!["/tmp/claude".to_string()]);
+        // Split at blanks or newlines, a path can turn into others
+        assert!(!operands.within(is_tmp));
+        operands.null_separated = true;
+        assert!(operands.within(is_tmp));
+        operands.roots = Some(vec!["/tmp".to_string()]);
+        assert!(!operands.within(is_tmp));
+        operands.roots = Some(vec!["/tmp/claude/.".to_string()]);
+        assert!(operands.within(is_tmp));
+    }
+}
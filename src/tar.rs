@@ -4,7 +4,8 @@
 
 use crate::analyzer::Command;
 use crate::config::{Permission, PermissionResult};
-use std::process::Command as ProcessCommand;
+use crate::expand;
+use crate::paths;
 
 const SAFE_PREFIX: &str = "/tmp/claude/";
 
@@ -12,10 +13,13 @@ const SAFE_PREFIX: &str = "/tmp/claude/";
 /// Allows:
 /// - List mode (tar -t) - read-only
 /// - Extraction to /tmp/claude/ subdirectories
+///
+/// `cwd` is the directory the command runs in, None if it can't be known, and
+/// `assigned` the variables the command line sets
 pub fn check_tar(
     cmd: &Command,
-    virtual_cwd: Option<&str>,
-    has_uncertain_flow: bool,
+    cwd: Option<&str>,
+    assigned: &[String],
 ) -> Option<PermissionResult> {
     if cmd.name != "tar" {
         return None;
@@ -56,10 +60,16 @@ pub fn check_tar(
     }
 
     // Find the -C/--directory target
-    let target_dir = find_target_dir(&cmd.args);
-
-    if let Some(dir) = target_dir {
-        if is_safe_tmp_claude_path(dir) {
+    let target_dir = find_target_dir(cmd);
+
+    if let Some(word) = target_dir {
+        // Expand ~, $VAR and globs; a relative -C is taken from the directory tar runs in
+        let dir = match expand::expand_word(word, cwd, assigned).as_deref() {
+            Some([dir]) if dir.starts_with('/') => Some(dir.clone()),
+            Some([dir]) => cwd.map(|cwd| format!("{}/{}", cwd.trim_end_matches('/'), dir)),
+            _ => None,
+        };
+        if dir.is_some_and(|dir| is_safe_tmp_claude_path(&dir)) {
             return Some(PermissionResult {
                 permission: Permission::Allow,
                 reason: "tar extract to /tmp/claude".to_string(),
@@ -70,9 +80,10 @@ pub fn check_tar(
         return None;
     }
 
-    // No -C specified - allow if current directory (or virtual cwd) is under /tmp/claude/
-    // But NOT if there's uncertain control flow (cd might have changed in conditional)
-    if !has_uncertain_flow && is_cwd_safe(virtual_cwd) {
+    // No -C specified - allow if the command runs under /tmp/claude/
+    if let Some(cwd) = cwd
+        && is_safe_tmp_claude_path(cwd)
+    {
         return Some(PermissionResult {
             permission: Permission::Allow,
             reason: "tar extract (cwd in /tmp/claude)".to_string(),
@@ -84,56 +95,25 @@ pub fn check_tar(
     None
 }
 
-/// Check if cwd (real or virtual) is under /tmp/claude/
-fn is_cwd_safe(virtual_cwd: Option<&str>) -> bool {
-    // First check virtual_cwd (from cd commands in the chain)
-    if let Some(vcwd) = virtual_cwd {
-        if let Some(resolved) = resolve_path(vcwd) {
-            if resolved.starts_with(SAFE_PREFIX) {
-                return true;
-            }
-        }
-    }
-
-    // Fall back to actual cwd
-    is_cwd_under_tmp_claude()
-}
-
-/// Check if current working directory is under /tmp/claude/
-fn is_cwd_under_tmp_claude() -> bool {
-    if let Ok(cwd) = std::env::current_dir() {
-        if let Some(cwd_str) = cwd.to_str() {
-            if let Some(resolved) = resolve_path(cwd_str) {
-                return resolved.starts_with(SAFE_PREFIX);
-            }
-        }
-    }
-    false
-}
-
-/// Find the target directory from -C or --directory flag
-fn find_target_dir(args: &[String]) -> Option<&str> {
-    let mut i = 0;
-    while i < args.len() {
-        let arg = &args[i];
-
-        if arg == "-C" || arg == "--directory" {
-            if let Some(dir) = args.get(i + 1) {
-                return Some(dir);
-            }
+/// Find the target directory from -C or --directory flag, as written
+fn find_target_dir(cmd: &Command) -> Option<&str> {
+    for (i, arg) in cmd.args.iter().enumerate() {
+        if (arg == "-C" || arg == "--directory") && i + 1 < cmd.args.len() {
+            return Some(cmd.raw_arg(i + 1));
         }
 
-        if let Some(dir) = arg.strip_prefix("-C") {
-            if !dir.is_empty() {
-                return Some(dir);
-            }
+        // A quoted `"-Cdir"` keeps its quotes in the raw form; give up on the directory
+        if arg.starts_with("-C") && arg.len() > 2 {
+            return Some(cmd.raw_arg(i).strip_prefix("-C").unwrap_or_default());
         }
 
-        if let Some(dir) = arg.strip_prefix("--directory=") {
-            return Some(dir);
+        if arg.starts_with("--directory=") {
+            return Some(
+                cmd.raw_arg(i)
+                    .strip_prefix("--directory=")
+                    .unwrap_or_default(),
+            );
         }
-
-        i += 1;
     }
 
     None
@@ -145,43 +125,7 @@ fn is_safe_tmp_claude_path(path: &str) -> bool {
         return false;
     }
 
-    // Use realpath to resolve the path
-    let resolved = match resolve_path(path) {
-        Some(p) => p,
-        None => return false,
-    };
-
-    // Must start with /tmp/claude/
-    if !resolved.starts_with(SAFE_PREFIX) {
-        return false;
-    }
-
-    // Must have something after /tmp/claude/
-    let after_prefix = &resolved[SAFE_PREFIX.len()..];
-    if after_prefix.is_empty() || after_prefix.chars().all(|c| c == '/') {
-        return false;
-    }
-
-    true
-}
-
-/// Resolve a path using realpath
-fn resolve_path(path: &str) -> Option<String> {
-    let output = ProcessCommand::new("realpath")
-        .arg("-m")
-        .arg("--")
-        .arg(path)
-        .output()
-        .ok()?;
-
-    if output.status.success() {
-        let resolved = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if !resolved.is_empty() {
-            return Some(resolved);
-        }
-    }
-
-    None
+    paths::resolve(path).is_some_and(|resolved| paths::is_under(&resolved, SAFE_PREFIX))
 }
 
 #[cfg(test)]
@@ -193,41 +137,42 @@ mod tests {
             name: "tar".to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("tar {}", args.join(" ")),
+            ..Default::default()
         }
     }
 
     #[test]
     fn test_tar_extract_to_tmp_claude() {
         let cmd = make_cmd(&["-xf", "-", "-C", "/tmp/claude/test"]);
-        let result = check_tar(&cmd, None, false).unwrap();
+        let result = check_tar(&cmd, None, &[]).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
 
     #[test]
     fn test_tar_extract_to_tmp_claude_subdir() {
         let cmd = make_cmd(&["-xzf", "file.tar.gz", "-C", "/tmp/claude/deep/path"]);
-        let result = check_tar(&cmd, None, false).unwrap();
+        let result = check_tar(&cmd, None, &[]).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
 
     #[test]
     fn test_tar_extract_to_home_not_allowed() {
         let cmd = make_cmd(&["-xf", "file.tar", "-C", "/home/user"]);
-        let result = check_tar(&cmd, None, false);
+        let result = check_tar(&cmd, None, &[]);
         assert!(result.is_none());
     }
 
     #[test]
     fn test_tar_extract_to_tmp_not_allowed() {
         let cmd = make_cmd(&["-xf", "file.tar", "-C", "/tmp"]);
-        let result = check_tar(&cmd, None, false);
+        let result = check_tar(&cmd, None, &[]);
         assert!(result.is_none());
     }
 
     #[test]
     fn test_tar_extract_no_directory() {
         let cmd = make_cmd(&["-xf", "file.tar"]);
-        let result = check_tar(&cmd, None, false);
+        let result = check_tar(&cmd, None, &[]);
         assert!(result.is_none()); // passthrough, extracts to cwd (not /tmp/claude)
     }
 
@@ -235,7 +180,7 @@ mod tests {
     fn test_tar_extract_with_virtual_cwd() {
         // Simulates: cd /tmp/claude/dir && tar -xf file.tar
         let cmd = make_cmd(&["-xf", "file.tar"]);
-        let result = check_tar(&cmd, Some("/tmp/claude/mydir"), false).unwrap();
+        let result = check_tar(&cmd, Some("/tmp/claude/mydir"), &[]).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
 
@@ -243,45 +188,68 @@ mod tests {
     fn test_tar_extract_with_virtual_cwd_unsafe() {
         // Simulates: cd /home/user && tar -xf file.tar
         let cmd = make_cmd(&["-xf", "file.tar"]);
-        let result = check_tar(&cmd, Some("/home/user"), false);
+        let result = check_tar(&cmd, Some("/home/user"), &[]);
         assert!(result.is_none());
     }
 
     #[test]
-    fn test_tar_extract_with_uncertain_flow() {
+    fn test_tar_extract_with_unknown_cwd() {
         // Simulates: if true; then cd /; fi && tar -xf file.tar
-        // Even with virtual_cwd in /tmp/claude, uncertain flow should passthrough
         let cmd = make_cmd(&["-xf", "file.tar"]);
-        let result = check_tar(&cmd, Some("/tmp/claude/mydir"), true);
+        let result = check_tar(&cmd, None, &[]);
         assert!(result.is_none());
     }
 
     #[test]
-    fn test_tar_extract_explicit_c_with_uncertain_flow() {
-        // Explicit -C /tmp/claude is still allowed even with uncertain flow
+    fn test_tar_extract_explicit_c_with_unknown_cwd() {
+        // Explicit -C /tmp/claude is still allowed when the cwd is unknown
         let cmd = make_cmd(&["-xf", "file.tar", "-C", "/tmp/claude/dir"]);
-        let result = check_tar(&cmd, None, true).unwrap();
+        let result = check_tar(&cmd, None, &[]).unwrap();
+        assert_eq!(result.permission, Permission::Allow);
+    }
+
+    #[test]
+    fn test_tar_extract_relative_c() {
+        // Simulates: cd /tmp/claude && tar -xf file.tar -C out
+        let cmd = make_cmd(&["-xf", "file.tar", "-C", "out"]);
+        let result = check_tar(&cmd, Some("/tmp/claude"), &[]).unwrap();
         assert_eq!(result.permission, Permission::Allow);
+        assert!(check_tar(&cmd, Some("/home/user"), &[]).is_none());
+        assert!(check_tar(&cmd, None, &[]).is_none());
+    }
+
+    #[test]
+    fn test_tar_expands_target_dir() {
+        let cmd = Command {
+            raw_args: vec!["-xf".into(), "a.tar".into(), "-C".into(), "~".into()],
+            ..make_cmd(&["-xf", "a.tar", "-C", "~"])
+        };
+        assert!(check_tar(&cmd, Some("/tmp/claude"), &[]).is_none());
+        let cmd = Command {
+            raw_args: vec!["-xf".into(), "a.tar".into(), "-C'/tmp/claude/x'".into()],
+            ..make_cmd(&["-xf", "a.tar", "-C/tmp/claude/x"])
+        };
+        assert!(check_tar(&cmd, None, &[]).is_some());
     }
 
     #[test]
     fn test_tar_create_not_handled() {
         let cmd = make_cmd(&["-cf", "file.tar", "/tmp/claude/test"]);
-        let result = check_tar(&cmd, None, false);
+        let result = check_tar(&cmd, None, &[]);
         assert!(result.is_none());
     }
 
     #[test]
     fn test_tar_list_allowed() {
         let cmd = make_cmd(&["-tf", "file.tar"]);
-        let result = check_tar(&cmd, None, false).unwrap();
+        let result = check_tar(&cmd, None, &[]).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
 
     #[test]
     fn test_tar_list_verbose_allowed() {
         let cmd = make_cmd(&["-tvf", "file.tar"]);
-        let result = check_tar(&cmd, None, false).unwrap();
+        let result = check_tar(&cmd, None, &[]).unwrap();
         assert_eq!(result.permission, Permission::Allow);
     }
 }
@@ -0,0 +1,11 @@
+//! Shared test fixtures
+
+use std::path::PathBuf;
+
+/// An empty directory for a test, unique to the test process
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cbh-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
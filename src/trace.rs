@@ -0,0 +1,196 @@
+//! Decision tracing
+//!
+//! Records each extracted command, wrapper unwrap step and the rule or special
+//! handler that decided it. Used by the `check` subcommand.
+
+use crate::analyzer::Command;
+use crate::config::{Permission, PermissionResult};
+
+/// A single step in reaching a decision
+#[derive(Debug, Clone)]
+pub enum TraceEvent {
+    /// A command extracted from the input
+    Command {
+        text: String,
+        name: String,
+        args: Vec<String>,
+    },
+    /// A wrapper was unwrapped
+    Unwrap {
+        wrapper: String,
+        inner: Option<String>,
+        host: Option<String>,
+    },
+    /// A rule or special handler decided a command
+    Decision {
+        handler: String,
+        permission: Permission,
+        reason: String,
+    },
+    /// The command that determined the overall result
+    MostRestrictive {
+        text: String,
+        permission: Permission,
+        handler: String,
+    },
+}
+
+/// Collected trace events with their nesting depth
+#[derive(Debug, Default)]
+pub struct Trace {
+    pub events: Vec<(usize, TraceEvent)>,
+    depth: usize,
+}
+
+impl Trace {
+    /// Record an extracted command and nest subsequent events under it
+    pub fn enter_command(&mut self, cmd: &Command) {
+        self.push(TraceEvent::Command {
+            text: cmd.text.clone(),
+            name: cmd.name.clone(),
+            args: cmd.args.clone(),
+        });
+        self.depth += 1;
+    }
+
+    /// Leave the current command
+    pub fn leave_command(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    /// Record a wrapper unwrap step
+    pub fn unwrap(&mut self, wrapper: &str, inner: Option<&str>, host: Option<&str>) {
+        self.push(TraceEvent::Unwrap {
+            wrapper: wrapper.to_string(),
+            inner: inner.map(String::from),
+            host: host.map(String::from),
+        });
+    }
+
+    /// Record which rule or handler decided the current command
+    pub fn decision(&mut self, handler: &str, result: &PermissionResult) {
+        self.push(TraceEvent::Decision {
+            handler: handler.to_string(),
+            permission: result.permission,
+            reason: result.reason.clone(),
+        });
+    }
+
+    /// Record the most restrictive command (only at the top level)
+    pub fn most_restrictive(&mut self, text: &str, permission: Permission, handler: &str) {
+        if self.depth == 0 {
+            self.push(TraceEvent::MostRestrictive {
+                text: text.to_string(),
+                permission,
+                handler: handler.to_string(),
+            });
+        }
+    }
+
+    /// The handler of the most recent decision
+    pub fn last_handler(&self) -> String {
+        self.events
+            .iter()
+            .rev()
+            .find_map(|(_, event)| match event {
+                TraceEvent::Decision { handler, .. } => Some(handler.clone()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// The rule or handler that decided the overall result
+    pub fn deciding_handler(&self) -> Option<&str> {
+        self.events
+            .iter()
+            .rev()
+            .find_map(|(depth, event)| match event {
+                TraceEvent::MostRestrictive { handler, .. } if *depth == 0 => {
+                    Some(handler.as_str())
+                }
+                _ => None,
+            })
+    }
+
+    fn push(&mut self, event: TraceEvent) {
+        self.events.push((self.depth, event));
+    }
+
+    /// Render the trace as indented text
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (depth, event) in &self.events {
+            let indent = "  ".repeat(*depth);
+            let line = match event {
+                TraceEvent::Command { text, .. } => format!("command: {}", text),
+                TraceEvent::Unwrap {
+                    wrapper,
+                    inner,
+                    host,
+                } => {
+                    let mut line = format!("unwrap {}", wrapper);
+                    if let Some(host) = host {
+                        line.push_str(&format!(" (host: {})", host));
+                    }
+                    if let Some(inner) = inner {
+                        line.push_str(&format!(" -> {}", inner));
+                    }
+                    line
+                }
+                TraceEvent::Decision {
+                    handler,
+                    permission,
+                    reason,
+                } => format!("{} by {}: {}", permission.as_str(), handler, reason),
+                TraceEvent::MostRestrictive {
+                    text,
+                    permission,
+                    handler,
+                } => format!(
+                    "most restrictive: {} ({} by {})",
+                    text,
+                    permission.as_str(),
+                    handler
+                ),
+            };
+            out.push_str(&indent);
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_nesting() {
+        let cmd = Command {
+            name: "sudo".to_string(),
+            args: vec!["ls".to_string()],
+            text: "sudo ls".to_string(),
+            ..Default::default()
+        };
+        let mut trace = Trace::default();
+        trace.enter_command(&cmd);
+        trace.unwrap("sudo", Some("ls"), None);
+        trace.decision(
+            "rule #1 \"ls\"",
+            &PermissionResult {
+                permission: Permission::Allow,
+                reason: "read-only commands".to_string(),
+                suggestion: None,
+            },
+        );
+        trace.leave_command();
+        trace.most_restrictive("sudo ls", Permission::Allow, &trace.last_handler());
+
+        assert_eq!(
+            trace.render(),
+            "command: sudo ls\n  unwrap sudo -> ls\n  allow by rule #1 \"ls\": read-only commands\nmost restrictive: sudo ls (allow by rule #1 \"ls\")\n"
+        );
+        assert_eq!(trace.deciding_handler(), Some("rule #1 \"ls\""));
+    }
+}
@@ -1,6 +1,7 @@
 //! env wrapper handling
 
 use crate::analyzer::Command;
+use crate::script::Language;
 use crate::wrappers::UnwrapResult;
 
 /// Unwrap env command
@@ -9,14 +10,14 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
     let mut skip_next = false;
     let mut found_command = false;
 
-    for arg in &cmd.args {
+    for (i, arg) in cmd.args.iter().enumerate() {
         if skip_next {
             skip_next = false;
             continue;
         }
 
         if found_command {
-            inner_parts.push(arg.clone());
+            inner_parts.push(cmd.raw_arg(i).to_string());
             continue;
         }
 
@@ -38,7 +39,7 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
         }
 
         found_command = true;
-        inner_parts.push(arg.clone());
+        inner_parts.push(cmd.raw_arg(i).to_string());
     }
 
     if inner_parts.is_empty() {
@@ -47,8 +48,12 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
 
     Some(UnwrapResult {
         inner_command: Some(inner_parts.join(" ")),
+        dynamic: false,
         host: None,
         wrapper: "env".to_string(),
+        script: None,
+        piped: None,
+        language: Language::Bash,
     })
 }
 
@@ -61,6 +66,7 @@ mod tests {
             name: "env".to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("env {}", args.join(" ")),
+            ..Default::default()
         }
     }
 
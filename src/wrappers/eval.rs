@@ -0,0 +1,89 @@
+//! eval wrapper handling
+//!
+//! `eval` joins its arguments with spaces and runs the result as a command,
+//! so the joined string is analyzed like `bash -c`. An expansion in the
+//! arguments (`eval "ls $DIR"`) can add any syntax to it, so the command is
+//! only known at runtime.
+
+use crate::analyzer::Command;
+use crate::script::Language;
+use crate::wrappers::UnwrapResult;
+
+/// Unwrap eval command
+pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
+    let indices: Vec<usize> = cmd
+        .args
+        .iter()
+        .enumerate()
+        .skip_while(|(_, arg)| *arg == "--")
+        .map(|(i, _)| i)
+        .collect();
+
+    if indices.is_empty() {
+        return None;
+    }
+
+    let parts: Vec<String> = indices.iter().map(|&i| cmd.arg_string(i)).collect();
+    Some(UnwrapResult {
+        inner_command: Some(parts.join(" ")),
+        dynamic: indices.iter().any(|&i| cmd.is_dynamic_arg(i)),
+        host: None,
+        wrapper: "eval".to_string(),
+        script: None,
+        piped: None,
+        language: Language::Bash,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::analyze;
+
+    fn unwrap_str(command: &str) -> Option<String> {
+        let analysis = analyze(command);
+        unwrap(&analysis.commands[0]).and_then(|r| r.inner_command)
+    }
+
+    fn is_dynamic(command: &str) -> bool {
+        let analysis = analyze(command);
+        unwrap(analysis.commands.last().unwrap()).unwrap().dynamic
+    }
+
+    #[test]
+    fn test_eval_joins_arguments() {
+        assert_eq!(unwrap_str("eval ls -la"), Some("ls -la".to_string()));
+        assert_eq!(
+            unwrap_str("eval 'rm -rf' /tmp/x"),
+            Some("rm -rf /tmp/x".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_string_with_expansion() {
+        assert_eq!(
+            unwrap_str("eval \"rm -rf $DIR\""),
+            Some("rm -rf $DIR".to_string())
+        );
+        assert!(is_dynamic("eval \"rm -rf $DIR\""));
+        assert!(is_dynamic("eval ls $(cat args)"));
+        assert!(is_dynamic("eval ls `cat args`"));
+        assert!(is_dynamic("eval \"ls ${D:-x}\""));
+        // Expanded by the inner command, not by eval
+        assert!(!is_dynamic("eval 'ls $DIR'"));
+        assert!(!is_dynamic("eval ls -la"));
+    }
+
+    #[test]
+    fn test_eval_nested_quotes() {
+        assert_eq!(
+            unwrap_str("eval 'echo \"a; b\"'"),
+            Some("echo \"a; b\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_no_args() {
+        assert_eq!(unwrap_str("eval"), None);
+    }
+}
@@ -1,6 +1,7 @@
 //! kubectl exec wrapper handling
 
 use crate::analyzer::Command;
+use crate::script::Language;
 use crate::wrappers::UnwrapResult;
 
 /// Unwrap kubectl exec command
@@ -13,7 +14,7 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
 
     let inner_command = match separator_pos {
         Some(pos) => {
-            let inner_parts: Vec<_> = cmd.args[pos + 1..].to_vec();
+            let inner_parts: Vec<_> = (pos + 1..cmd.args.len()).map(|i| cmd.raw_arg(i)).collect();
             if inner_parts.is_empty() {
                 None
             } else {
@@ -25,8 +26,12 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
 
     Some(UnwrapResult {
         inner_command,
+        dynamic: false,
         host: None,
         wrapper: "kubectl exec".to_string(),
+        script: None,
+        piped: None,
+        language: Language::Bash,
     })
 }
 
@@ -39,6 +44,7 @@ mod tests {
             name: "kubectl".to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("kubectl {}", args.join(" ")),
+            ..Default::default()
         }
     }
 
@@ -1,13 +1,15 @@
-//! Wrapper command handling (sudo, ssh, env, etc.)
+//! Wrapper command handling (sudo, ssh, env, eval, etc.)
 //!
 //! Unwraps wrapper commands to analyze the inner command.
 //! Simple wrappers are config-driven, complex ones have special handling.
 
 mod env;
+mod eval;
 mod kubectl;
 mod rsync;
 mod scp;
 mod shell;
+mod source;
 mod ssh;
 mod terminal_remote;
 mod timeout;
@@ -15,17 +17,29 @@ mod xargs;
 
 use crate::analyzer::Command;
 use crate::config::{Config, WrapperConfig};
+use crate::script::Language;
+use crate::stdin::PipedOperands;
 
 /// Result of unwrapping a wrapper command
 #[derive(Debug)]
 pub struct UnwrapResult {
-    /// The inner command after unwrapping
+    /// The inner command after unwrapping, as shell source with its words quoted
+    /// the way the inner shell receives them
     pub inner_command: Option<String>,
+    /// Whether the inner command's text comes from an expansion and so is only
+    /// known at runtime (`eval "ls $DIR"`)
+    pub dynamic: bool,
     /// For SSH/SCP: the extracted host
     pub host: Option<String>,
     /// The wrapper that was unwrapped (for debugging/tests)
     #[allow(dead_code)]
     pub wrapper: String,
+    /// A script file the wrapper runs (`bash run.sh`, `source env.sh`)
+    pub script: Option<String>,
+    /// For xargs/parallel: operands the inner command receives from the input
+    pub piped: Option<PipedOperands>,
+    /// Language the inner command is written in (`nu -c`, `fish -c`)
+    pub language: Language,
 }
 
 /// Check if a command is a wrapper and unwrap it
@@ -40,7 +54,10 @@ pub fn unwrap_command(cmd: &Command, config: &Config) -> Option<UnwrapResult> {
         "timeout" => return timeout::unwrap(cmd),
         "kitty-remote" | "wezterm-remote" => return terminal_remote::unwrap(cmd),
         "xargs" => return xargs::unwrap(cmd),
-        "sh" | "bash" | "zsh" => return shell::unwrap(cmd),
+        "parallel" => return xargs::unwrap_parallel(cmd),
+        "sh" | "bash" | "zsh" | "fish" | "nu" => return shell::unwrap(cmd),
+        "eval" => return eval::unwrap(cmd),
+        "source" | "." => return source::unwrap(cmd),
         _ => {}
     }
 
@@ -58,7 +75,7 @@ fn unwrap_generic(cmd: &Command, config: &WrapperConfig) -> Option<UnwrapResult>
     let mut skip_next = false;
     let mut found_command = false;
 
-    for arg in &cmd.args {
+    for (i, arg) in cmd.args.iter().enumerate() {
         if skip_next {
             skip_next = false;
             continue;
@@ -66,7 +83,7 @@ fn unwrap_generic(cmd: &Command, config: &WrapperConfig) -> Option<UnwrapResult>
 
         // Once we've found the command, everything after is part of it
         if found_command {
-            inner_parts.push(arg.clone());
+            inner_parts.push(cmd.raw_arg(i).to_string());
             continue;
         }
 
@@ -87,7 +104,7 @@ fn unwrap_generic(cmd: &Command, config: &WrapperConfig) -> Option<UnwrapResult>
 
         // This is the command - everything from here is the inner command
         found_command = true;
-        inner_parts.push(arg.clone());
+        inner_parts.push(cmd.raw_arg(i).to_string());
     }
 
     if inner_parts.is_empty() {
@@ -96,8 +113,12 @@ fn unwrap_generic(cmd: &Command, config: &WrapperConfig) -> Option<UnwrapResult>
 
     Some(UnwrapResult {
         inner_command: Some(inner_parts.join(" ")),
+        dynamic: false,
         host: None,
         wrapper: cmd.name.clone(),
+        script: None,
+        piped: None,
+        language: Language::Bash,
     })
 }
 
@@ -111,6 +132,7 @@ mod tests {
             name: name.to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("{} {}", name, args.join(" ")),
+            ..Default::default()
         }
     }
 
@@ -1,6 +1,7 @@
 //! rsync wrapper handling
 
 use crate::analyzer::Command;
+use crate::script::Language;
 use crate::wrappers::UnwrapResult;
 
 /// Unwrap rsync command - extract destination host
@@ -28,8 +29,12 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
 
     Some(UnwrapResult {
         inner_command: None,
+        dynamic: false,
         host,
         wrapper: "rsync".to_string(),
+        script: None,
+        piped: None,
+        language: Language::Bash,
     })
 }
 
@@ -42,6 +47,7 @@ mod tests {
             name: "rsync".to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("rsync {}", args.join(" ")),
+            ..Default::default()
         }
     }
 
@@ -1,6 +1,7 @@
 //! scp wrapper handling
 
 use crate::analyzer::Command;
+use crate::script::Language;
 use crate::wrappers::UnwrapResult;
 
 /// Unwrap scp command - extract destination host
@@ -27,8 +28,12 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
 
     Some(UnwrapResult {
         inner_command: None,
+        dynamic: false,
         host,
         wrapper: "scp".to_string(),
+        script: None,
+        piped: None,
+        language: Language::Bash,
     })
 }
 
@@ -41,6 +46,7 @@ mod tests {
             name: "scp".to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("scp {}", args.join(" ")),
+            ..Default::default()
         }
     }
 
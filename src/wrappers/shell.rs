@@ -1,9 +1,12 @@
 //! Shell wrapper handling
 //!
-//! - `bash -c "command"` - parse the command string
+//! - `bash -c "command"` - parse the command string (`nu -c` and `fish -c`
+//!   with their own analyzers)
 //! - `bash script.sh` - check script path as binary
+//! - `bash <<EOF`, `sh < script.sh` - commands read from stdin, like `-c` or a script
 
-use crate::analyzer::Command;
+use crate::analyzer::{Command, Input};
+use crate::script::Language;
 use crate::wrappers::UnwrapResult;
 
 /// Check if this is a shell command and unwrap it
@@ -13,46 +16,76 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
         return None;
     }
 
-    if cmd.args.is_empty() {
-        return None;
-    }
-
-    // Check for -c flag (nu also uses --commands)
-    if let Some(c_pos) = cmd.args.iter().position(|a| a == "-c" || a == "--commands") {
+    // Check for -c flag (nu also uses --commands, fish --command)
+    if let Some(c_pos) = cmd
+        .args
+        .iter()
+        .position(|a| matches!(a.as_str(), "-c" | "--commands" | "--command"))
+    {
         // -c mode: parse the command string
         if c_pos + 1 >= cmd.args.len() {
             return None;
         }
 
-        let inner_command = &cmd.args[c_pos + 1];
-        let stripped = strip_quotes(inner_command);
-
         return Some(UnwrapResult {
-            inner_command: Some(stripped),
+            inner_command: Some(cmd.arg_string(c_pos + 1)),
+            // The inner shell parses what the outer one expanded (`bash -c "ls $X"`)
+            dynamic: cmd.is_dynamic_arg(c_pos + 1),
             host: None,
             wrapper: cmd.name.clone(),
+            script: None,
+            piped: None,
+            language: language(&cmd.name),
         });
     }
 
     // Script mode: bash script.sh [args...]
     // Find first non-flag argument as the script path
-    let script = cmd.args.iter().find(|a| !a.starts_with('-'))?;
+    let script = cmd.args.iter().find(|a| !a.starts_with('-'));
+    // Without a script (or with -s), commands are read from stdin
+    let Some(script) = script.filter(|_| !cmd.args.iter().any(|a| a == "-s")) else {
+        return unwrap_stdin(cmd);
+    };
 
     // Return script path as the "command" to check against rules
     Some(UnwrapResult {
         inner_command: Some(script.clone()),
+        dynamic: false,
+        host: None,
+        wrapper: cmd.name.clone(),
+        script: Some(script.clone()),
+        piped: None,
+        language: Language::Bash,
+    })
+}
+
+/// Unwrap a shell reading its commands from a heredoc, here-string or file
+fn unwrap_stdin(cmd: &Command) -> Option<UnwrapResult> {
+    if !matches!(cmd.name.as_str(), "sh" | "bash" | "zsh" | "fish") {
+        return None;
+    }
+    let (inner_command, script) = match cmd.input()? {
+        Input::Text(text) | Input::Dynamic(text) => (text.clone(), None),
+        Input::File(path) => (path.clone(), Some(path.clone())),
+    };
+    Some(UnwrapResult {
+        inner_command: Some(inner_command),
+        // The shell parses what the heredoc expanded to (`bash <<EOF` with `$X`)
+        dynamic: matches!(cmd.input(), Some(Input::Dynamic(_))),
         host: None,
         wrapper: cmd.name.clone(),
+        script,
+        piped: None,
+        language: language(&cmd.name),
     })
 }
 
-/// Strip surrounding single or double quotes from a string
-fn strip_quotes(s: &str) -> String {
-    let s = s.trim();
-    if (s.starts_with('\'') && s.ends_with('\'')) || (s.starts_with('"') && s.ends_with('"')) {
-        s[1..s.len() - 1].to_string()
-    } else {
-        s.to_string()
+/// The language a shell reads its commands in
+fn language(shell: &str) -> Language {
+    match shell {
+        "nu" => Language::Nushell,
+        "fish" => Language::Fish,
+        _ => Language::Bash,
     }
 }
 
@@ -65,6 +98,7 @@ mod tests {
             name: name.to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("{} {}", name, args.join(" ")),
+            ..Default::default()
         }
     }
 
@@ -73,6 +107,7 @@ mod tests {
         let cmd = make_cmd("sh", &["-c", "ls -la"]);
         let result = unwrap(&cmd).unwrap();
         assert_eq!(result.inner_command, Some("ls -la".to_string()));
+        assert!(result.script.is_none());
     }
 
     #[test]
@@ -94,6 +129,7 @@ mod tests {
         let cmd = make_cmd("sh", &["script.sh"]);
         let result = unwrap(&cmd).unwrap();
         assert_eq!(result.inner_command, Some("script.sh".to_string()));
+        assert_eq!(result.script, Some("script.sh".to_string()));
     }
 
     #[test]
@@ -139,5 +175,55 @@ mod tests {
         let cmd = make_cmd("fish", &["-c", "ls -la"]);
         let result = unwrap(&cmd).unwrap();
         assert_eq!(result.inner_command, Some("ls -la".to_string()));
+        assert_eq!(result.language, Language::Fish);
+    }
+
+    #[test]
+    fn test_inner_language() {
+        let language = |name: &str, args: &[&str]| unwrap(&make_cmd(name, args)).unwrap().language;
+        assert_eq!(language("nu", &["-c", "ls"]), Language::Nushell);
+        assert_eq!(language("fish", &["--command", "ls"]), Language::Fish);
+        assert_eq!(language("bash", &["-c", "ls"]), Language::Bash);
+        assert_eq!(language("zsh", &["-c", "ls"]), Language::Bash);
+    }
+
+    #[test]
+    fn test_commands_from_stdin() {
+        let with_input = |args: &[&str], input: Input| Command {
+            stdin: Some(crate::analyzer::Stdin {
+                input: Some(input),
+                ..Default::default()
+            }),
+            ..make_cmd("bash", args)
+        };
+
+        let cmd = with_input(&[], Input::Text("rm -rf /\n".to_string()));
+        let result = unwrap(&cmd).unwrap();
+        assert_eq!(result.inner_command, Some("rm -rf /\n".to_string()));
+        assert!(result.script.is_none());
+
+        let cmd = with_input(&["-s", "arg"], Input::File("run.sh".to_string()));
+        let result = unwrap(&cmd).unwrap();
+        assert_eq!(result.script, Some("run.sh".to_string()));
+
+        // A script argument takes precedence over stdin
+        let cmd = with_input(&["x.sh"], Input::Text("ls\n".to_string()));
+        assert_eq!(
+            unwrap(&cmd).unwrap().inner_command,
+            Some("x.sh".to_string())
+        );
+        assert!(unwrap(&make_cmd("bash", &[])).is_none());
+    }
+
+    #[test]
+    fn test_c_expansions_dynamic() {
+        let dynamic = |command: &str| {
+            let analysis = crate::analyzer::analyze(command);
+            unwrap(analysis.commands.last().unwrap()).unwrap().dynamic
+        };
+        assert!(dynamic("bash -c \"ls $X\""));
+        assert!(dynamic("sh -c \"ls $(cat f)\""));
+        assert!(!dynamic("bash -c 'ls $X'"));
+        assert!(!dynamic("bash -c \"ls -la\""));
     }
 }
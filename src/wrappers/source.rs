@@ -0,0 +1,58 @@
+//! source / `.` wrapper handling
+//!
+//! The sourced file runs in the current shell, so its path is checked like
+//! the script of `bash script.sh` (rules for trusted setup scripts, /tmp scripts).
+
+use crate::analyzer::Command;
+use crate::script::Language;
+use crate::wrappers::UnwrapResult;
+
+/// Unwrap source or `.` command
+pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
+    let index = cmd.args.iter().position(|a| a != "--")?;
+
+    Some(UnwrapResult {
+        inner_command: Some(cmd.raw_arg(index).to_string()),
+        dynamic: false,
+        host: None,
+        wrapper: cmd.name.clone(),
+        script: Some(cmd.args[index].clone()),
+        piped: None,
+        language: Language::Bash,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_cmd(name: &str, args: &[&str]) -> Command {
+        Command {
+            name: name.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+            text: format!("{} {}", name, args.join(" ")),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_source_script() {
+        let cmd = make_cmd("source", &["./setup.sh", "arg"]);
+        let result = unwrap(&cmd).unwrap();
+        assert_eq!(result.inner_command, Some("./setup.sh".to_string()));
+    }
+
+    #[test]
+    fn test_dot_script() {
+        let cmd = make_cmd(".", &["venv/bin/activate"]);
+        let result = unwrap(&cmd).unwrap();
+        assert_eq!(result.inner_command, Some("venv/bin/activate".to_string()));
+        assert_eq!(result.wrapper, ".");
+    }
+
+    #[test]
+    fn test_source_no_args() {
+        let cmd = make_cmd("source", &[]);
+        assert!(unwrap(&cmd).is_none());
+    }
+}
@@ -1,23 +1,15 @@
 //! ssh wrapper handling
 
-use crate::analyzer::Command;
+use crate::analyzer::{Command, Input};
+use crate::script::Language;
 use crate::wrappers::UnwrapResult;
 
-/// Strip surrounding single or double quotes from a string
-fn strip_quotes(s: &str) -> String {
-    let s = s.trim();
-    if (s.starts_with('\'') && s.ends_with('\'')) || (s.starts_with('"') && s.ends_with('"')) {
-        s[1..s.len() - 1].to_string()
-    } else {
-        s.to_string()
-    }
-}
-
 /// Unwrap ssh command
 /// ssh [options] [user@]hostname [command]
 pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
     let mut host = None;
     let mut inner_parts = Vec::new();
+    let mut dynamic = false;
     let mut skip_next = false;
     let mut found_host = false;
 
@@ -26,7 +18,7 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
         "-Q", "-R", "-S", "-W", "-w",
     ];
 
-    for arg in &cmd.args {
+    for (i, arg) in cmd.args.iter().enumerate() {
         if skip_next {
             skip_next = false;
             continue;
@@ -39,10 +31,8 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
                 } else {
                     arg.as_str()
                 };
-                if opts_with_args.contains(&opt) {
-                    if arg.len() == 2 {
-                        skip_next = true;
-                    }
+                if opts_with_args.contains(&opt) && arg.len() == 2 {
+                    skip_next = true;
                 }
                 continue;
             }
@@ -57,22 +47,34 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
             continue;
         }
 
-        inner_parts.push(arg.clone());
+        // ssh joins the words with spaces for the remote shell
+        inner_parts.push(cmd.arg_string(i));
+        // The remote shell parses what the local one expanded (`ssh host "ls $X"`)
+        dynamic |= cmd.is_dynamic_arg(i);
     }
 
     let inner_command = if inner_parts.is_empty() {
-        None
-    } else if inner_parts.len() == 1 {
-        // Single argument - might be a quoted command string
-        Some(strip_quotes(&inner_parts[0]))
+        // Without a command, the remote shell runs what's fed to stdin (`ssh host <<EOF`)
+        match cmd.input() {
+            Some(Input::Text(text)) => Some(text.clone()),
+            Some(Input::Dynamic(text)) => {
+                dynamic = true;
+                Some(text.clone())
+            }
+            _ => None,
+        }
     } else {
         Some(inner_parts.join(" "))
     };
 
     Some(UnwrapResult {
         inner_command,
+        dynamic,
         host,
         wrapper: "ssh".to_string(),
+        script: None,
+        piped: None,
+        language: Language::Bash,
     })
 }
 
@@ -85,6 +87,7 @@ mod tests {
             name: "ssh".to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("ssh {}", args.join(" ")),
+            ..Default::default()
         }
     }
 
@@ -122,4 +125,29 @@ mod tests {
             Some("systemctl status foo".to_string())
         );
     }
+
+    #[test]
+    fn test_ssh_heredoc() {
+        let cmd = Command {
+            stdin: Some(crate::analyzer::Stdin {
+                input: Some(Input::Text("rm -rf /srv\n".to_string())),
+                ..Default::default()
+            }),
+            ..make_cmd(&["host"])
+        };
+        let result = unwrap(&cmd).unwrap();
+        assert_eq!(result.inner_command, Some("rm -rf /srv\n".to_string()));
+    }
+
+    #[test]
+    fn test_ssh_expansions_dynamic() {
+        let dynamic = |command: &str| {
+            let analysis = crate::analyzer::analyze(command);
+            unwrap(analysis.commands.last().unwrap()).unwrap().dynamic
+        };
+        assert!(dynamic("ssh host \"ls $X\""));
+        assert!(dynamic("ssh host ls `cat f`"));
+        assert!(!dynamic("ssh host 'ls $X'"));
+        assert!(!dynamic("ssh host ls -la"));
+    }
 }
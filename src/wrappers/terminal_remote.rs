@@ -1,6 +1,7 @@
 //! kitty-remote/wezterm-remote wrapper handling
 
 use crate::analyzer::Command;
+use crate::script::Language;
 use crate::wrappers::UnwrapResult;
 
 /// Unwrap kitty-remote/wezterm-remote commands
@@ -25,14 +26,14 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
         "--title",
     ];
 
-    for arg in &cmd.args {
+    for (i, arg) in cmd.args.iter().enumerate() {
         if skip_next {
             skip_next = false;
             continue;
         }
 
         if found_subcommand {
-            inner_parts.push(arg.clone());
+            inner_parts.push(cmd.raw_arg(i).to_string());
             continue;
         }
 
@@ -60,8 +61,12 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
 
     Some(UnwrapResult {
         inner_command: Some(inner_parts.join(" ")),
+        dynamic: false,
         host: None,
         wrapper: cmd.name.clone(),
+        script: None,
+        piped: None,
+        language: Language::Bash,
     })
 }
 
@@ -74,6 +79,7 @@ mod tests {
             name: name.to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("{} {}", name, args.join(" ")),
+            ..Default::default()
         }
     }
 
@@ -1,6 +1,7 @@
 //! timeout wrapper handling
 
 use crate::analyzer::Command;
+use crate::script::Language;
 use crate::wrappers::UnwrapResult;
 
 /// Unwrap timeout command
@@ -13,14 +14,14 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
 
     let opts_with_args = ["-k", "--kill-after", "-s", "--signal"];
 
-    for arg in &cmd.args {
+    for (i, arg) in cmd.args.iter().enumerate() {
         if skip_next {
             skip_next = false;
             continue;
         }
 
         if found_command {
-            inner_parts.push(arg.clone());
+            inner_parts.push(cmd.raw_arg(i).to_string());
             continue;
         }
 
@@ -44,7 +45,7 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
 
         // This is the command
         found_command = true;
-        inner_parts.push(arg.clone());
+        inner_parts.push(cmd.raw_arg(i).to_string());
     }
 
     if inner_parts.is_empty() {
@@ -53,8 +54,12 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
 
     Some(UnwrapResult {
         inner_command: Some(inner_parts.join(" ")),
+        dynamic: false,
         host: None,
         wrapper: "timeout".to_string(),
+        script: None,
+        piped: None,
+        language: Language::Bash,
     })
 }
 
@@ -67,6 +72,7 @@ mod tests {
             name: "timeout".to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("timeout {}", args.join(" ")),
+            ..Default::default()
         }
     }
 
@@ -1,14 +1,26 @@
-//! xargs wrapper handling
+//! xargs and GNU parallel wrapper handling
+//!
+//! Both run a command with operands read from their input. The operands are
+//! recorded as piped, so path checks on the inner command know they're unknown
+//! unless a producer like `find /tmp/x` bounds them.
 
 use crate::analyzer::Command;
+use crate::script::Language;
+use crate::stdin::PipedOperands;
 use crate::wrappers::UnwrapResult;
 
+/// Replacement strings GNU parallel derives from an input (`{.}` strips the extension)
+const PARALLEL_TOKENS: [&str; 7] = ["{}", "{.}", "{/}", "{//}", "{/.}", "{#}", "{%}"];
+
 /// Unwrap xargs command
 /// xargs [options] [command [args...]]
 pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
     let mut inner_parts = Vec::new();
     let mut skip_next = false;
     let mut found_command = false;
+    let mut replace: Option<String> = None;
+    let mut from_stdin = true;
+    let mut null_separated = false;
 
     // Options that take an argument
     let opts_with_args = [
@@ -29,18 +41,37 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
         "-E",
     ];
 
-    for arg in &cmd.args {
+    for (i, arg) in cmd.args.iter().enumerate() {
         if skip_next {
             skip_next = false;
             continue;
         }
 
         if found_command {
-            inner_parts.push(arg.clone());
+            inner_parts.push(cmd.raw_arg(i).to_string());
             continue;
         }
 
         if arg.starts_with('-') {
+            // -I R, -IR, -i[R] and --replace[=R] set the replace string
+            if arg == "-I" {
+                replace = cmd.args.get(i + 1).cloned();
+            } else if let Some(r) = arg.strip_prefix("-I").or(arg.strip_prefix("--replace=")) {
+                replace = Some(r.to_string());
+            } else if let Some(r) = arg.strip_prefix("-i") {
+                replace = Some(if r.is_empty() { "{}" } else { r }.to_string());
+            } else if arg == "--replace" {
+                replace = Some("{}".to_string());
+                continue;
+            } else if arg == "-a" || arg == "--arg-file" || arg.starts_with("--arg-file=") {
+                // Operands come from a file rather than stdin
+                from_stdin = false;
+            } else if arg == "-0" || arg == "--null" {
+                null_separated = true;
+            } else if arg == "-d" || arg.starts_with("--delimiter") {
+                null_separated = false;
+            }
+
             // Check if this option takes an argument
             let opt = if arg.contains('=') {
                 // --flag=value format, no need to skip next
@@ -57,22 +88,161 @@ pub fn unwrap(cmd: &Command) -> Option<UnwrapResult> {
 
         // First non-option is the command
         found_command = true;
-        inner_parts.push(arg.clone());
+        inner_parts.push(cmd.raw_arg(i).to_string());
     }
 
     if inner_parts.is_empty() {
         // xargs with no command defaults to echo, which is allowed
         return Some(UnwrapResult {
             inner_command: Some("echo".to_string()),
+            dynamic: false,
             host: None,
             wrapper: "xargs".to_string(),
+            script: None,
+            piped: None,
+            language: Language::Bash,
         });
     }
 
+    let mut piped = match replace {
+        Some(r) => PipedOperands::replacing(&r, &[]),
+        None => PipedOperands::appended(),
+    };
+    piped.from_stdin = from_stdin;
+    piped.null_separated = null_separated;
+
     Some(UnwrapResult {
         inner_command: Some(inner_parts.join(" ")),
+        dynamic: false,
         host: None,
         wrapper: "xargs".to_string(),
+        script: None,
+        piped: Some(piped),
+        language: Language::Bash,
+    })
+}
+
+/// Unwrap GNU parallel
+/// parallel [options] [command [args...]] [::: inputs | :::: files]
+/// The command is run through the shell like `sh -c`, so its words are joined
+/// unquoted; inputs given with `:::` are known and substituted directly
+pub fn unwrap_parallel(cmd: &Command) -> Option<UnwrapResult> {
+    let opts_with_args = [
+        "-j",
+        "--jobs",
+        "-P",
+        "-a",
+        "--arg-file",
+        "-d",
+        "--delimiter",
+        "-n",
+        "--max-args",
+        "-N",
+        "-L",
+        "--max-lines",
+        "-I",
+        "--colsep",
+        "--delay",
+        "--timeout",
+        "--joblog",
+        "--results",
+        "--tmpdir",
+        "-S",
+        "--sshlogin",
+    ];
+
+    let mut command = Vec::new();
+    let mut inputs = Vec::new();
+    let mut replace = "{}".to_string();
+    let mut from_stdin = true;
+    let mut from_files = false;
+    let mut null_separated = false;
+    let mut in_inputs = false;
+    let mut in_files = false;
+    let mut skip_next = false;
+
+    for (i, arg) in cmd.args.iter().enumerate() {
+        if skip_next {
+            skip_next = false;
+            continue;
+        }
+        match arg.as_str() {
+            // Inputs on the command line
+            ":::" | ":::+" => {
+                in_inputs = true;
+                in_files = false;
+            }
+            // Inputs read from files
+            "::::" | "::::+" => {
+                from_files = true;
+                in_files = true;
+                in_inputs = false;
+            }
+            _ if in_inputs => inputs.push(cmd.raw_arg(i).to_string()),
+            _ if in_files => {}
+            _ if !command.is_empty() => command.push(arg.clone()),
+            "-I" => {
+                replace = cmd.args.get(i + 1).cloned().unwrap_or_default();
+                skip_next = true;
+            }
+            "-a" | "--arg-file" => {
+                from_files = true;
+                skip_next = true;
+            }
+            "-0" | "--null" => null_separated = true,
+            "-d" | "--delimiter" => {
+                null_separated = false;
+                skip_next = true;
+            }
+            _ if arg.starts_with('-') => skip_next = opts_with_args.contains(&arg.as_str()),
+            _ => command.push(arg.clone()),
+        }
+        if !inputs.is_empty() || from_files {
+            from_stdin = false;
+        }
+    }
+
+    if command.is_empty() {
+        // Without a command, each input is itself a command line
+        return None;
+    }
+    let command = command.join(" ");
+    let uses_replace = command.contains(&replace);
+    let uses_other = PARALLEL_TOKENS
+        .iter()
+        .any(|t| *t != replace && command.contains(t));
+
+    let (inner_command, piped) = if !inputs.is_empty() && !from_files && !uses_other {
+        // Every input is known: substitute or append it
+        let inner = if uses_replace {
+            let commands: Vec<String> = inputs
+                .iter()
+                .map(|input| command.replace(&replace, input))
+                .collect();
+            commands.join("; ")
+        } else {
+            format!("{} {}", command, inputs.join(" "))
+        };
+        (inner, None)
+    } else {
+        let mut piped = if uses_replace || uses_other {
+            PipedOperands::replacing(&replace, &PARALLEL_TOKENS)
+        } else {
+            PipedOperands::appended()
+        };
+        piped.from_stdin = from_stdin;
+        piped.null_separated = null_separated;
+        (command, Some(piped))
+    };
+
+    Some(UnwrapResult {
+        inner_command: Some(inner_command),
+        dynamic: false,
+        host: None,
+        wrapper: "parallel".to_string(),
+        script: None,
+        piped,
+        language: Language::Bash,
     })
 }
 
@@ -85,6 +255,7 @@ mod tests {
             name: "xargs".to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
             text: format!("xargs {}", args.join(" ")),
+            ..Default::default()
         }
     }
 
@@ -130,4 +301,57 @@ mod tests {
         let result = unwrap(&cmd).unwrap();
         assert_eq!(result.inner_command, Some("rm -rf".to_string()));
     }
+
+    #[test]
+    fn test_xargs_piped_operands() {
+        let result = unwrap(&make_cmd(&["rm"])).unwrap();
+        let piped = result.piped.unwrap();
+        assert!(piped.appended && piped.from_stdin);
+
+        for args in [
+            &["-I", "X", "rm", "X"][..],
+            &["-IX", "rm", "X"],
+            &["--replace=X", "rm", "X"],
+        ] {
+            let piped = unwrap(&make_cmd(args)).unwrap().piped.unwrap();
+            assert!(!piped.appended);
+            assert!(piped.is_placeholder("X"));
+        }
+        let piped = unwrap(&make_cmd(&["-i", "rm", "{}"]))
+            .unwrap()
+            .piped
+            .unwrap();
+        assert!(piped.is_placeholder("{}"));
+
+        let piped = unwrap(&make_cmd(&["-a", "list", "rm"]))
+            .unwrap()
+            .piped
+            .unwrap();
+        assert!(!piped.from_stdin);
+    }
+
+    #[test]
+    fn test_parallel() {
+        let parallel = |args: &[&str]| {
+            unwrap_parallel(&Command {
+                name: "parallel".to_string(),
+                ..make_cmd(args)
+            })
+            .unwrap()
+        };
+
+        let result = parallel(&["gzip", ":::", "a", "b"]);
+        assert_eq!(result.inner_command.as_deref(), Some("gzip a b"));
+        assert!(result.piped.is_none());
+        let result = parallel(&["-j4", "rm -f {}", ":::", "a", "b"]);
+        assert_eq!(result.inner_command.as_deref(), Some("rm -f a; rm -f b"));
+
+        let result = parallel(&["rm"]);
+        assert_eq!(result.inner_command.as_deref(), Some("rm"));
+        assert!(result.piped.unwrap().appended);
+        let result = parallel(&["rm", "{.}.o", ":::", "a.c"]);
+        let piped = result.piped.unwrap();
+        assert!(piped.mentions("{.}.o") && !piped.from_stdin);
+        assert!(!parallel(&["-a", "files", "rm"]).piped.unwrap().from_stdin);
+    }
 }